@@ -3,7 +3,7 @@
 // exports the AssTrack type
 // also some errors I guess? I don't know how to organize Rust projects.
 
-use std::fmt;
+use std::{borrow::Cow, fmt};
 
 use thiserror::Error;
 
@@ -63,19 +63,22 @@ pub enum AssParseError {
 
 #[derive(Debug, Clone, Default)]
 pub struct AssTrack<'a> {
-    header: info::Header<'a>,
-    styleformat: Option<style::Format>,
-    styles: Vec<style::Style<'a>>,
-    eventformat: Option<event::Format>,
-    events: Vec<event::Event<'a>>,
+    pub(crate) header: info::Header<'a>,
+    pub(crate) styleformat: Option<style::Format>,
+    pub(crate) styles: Vec<style::Style<'a>>,
+    pub(crate) eventformat: Option<event::Format>,
+    pub(crate) events: Vec<event::Event<'a>>,
+    pub(crate) fonts: Vec<attachment::Attachment<'a>>,
+    pub(crate) graphics: Vec<attachment::Attachment<'a>>,
 }
 impl fmt::Display for AssTrack<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut v = Vec::<std::borrow::Cow<str>>::new();
-        v.push("[Script Info]".into());
-        v.push(self.header.to_string().into());
-        v.push("".into());
-        v.push("[V4+ Styles]".into());
+        let mut v: Vec<Cow<str>> = vec![
+            "[Script Info]".into(),
+            self.header.to_string().into(),
+            "".into(),
+            "[V4+ Styles]".into(),
+        ];
         v.push(
             self.styleformat
                 .as_ref()
@@ -98,6 +101,20 @@ impl fmt::Display for AssTrack<'_> {
         for event in &self.events {
             v.push(event.to_string().into());
         }
+        for (section, key, files) in &[
+            ("[Fonts]", "fontname", &self.fonts),
+            ("[Graphics]", "filename", &self.graphics),
+        ] {
+            if files.is_empty() {
+                continue;
+            }
+            v.push("".into());
+            v.push((*section).into());
+            for file in files.iter() {
+                v.push(format!("{}: {}", key, file.name).into());
+                v.push(file.to_string().into());
+            }
+        }
         write!(f, "{}", v.join("\n"))
     }
 }
@@ -105,10 +122,84 @@ impl<'a> AssTrack<'a> {
     pub fn parse_track(s: &'a str) -> Result<AssTrack<'a>, AssParseError> {
         parser::parse_track(s)
     }
+    // the same, plus which line everything came from. fatal errors say which
+    // line too.
+    pub fn parse_track_mapped(
+        s: &'a str,
+    ) -> Result<(AssTrack<'a>, SourceMap), (usize, AssParseError)> {
+        parser::parse_track_mapped(s)
+    }
+    // detaches the track from the string it was parsed from.
+    pub fn into_owned(self) -> AssTrack<'static> {
+        AssTrack {
+            header: self.header.into_owned(),
+            styleformat: self.styleformat,
+            styles: self.styles.into_iter().map(|x| x.into_owned()).collect(),
+            eventformat: self.eventformat,
+            events: self.events.into_iter().map(|x| x.into_owned()).collect(),
+            fonts: self.fonts.into_iter().map(|x| x.into_owned()).collect(),
+            graphics: self.graphics.into_iter().map(|x| x.into_owned()).collect(),
+        }
+    }
+    // for formats outside the crate, which build tracks from these and read
+    // them back through the accessors below.
+    pub fn new(
+        header: info::Header<'a>,
+        styles: Vec<style::Style<'a>>,
+        events: Vec<event::Event<'a>>,
+    ) -> Self {
+        AssTrack {
+            header,
+            styles,
+            events,
+            ..AssTrack::default()
+        }
+    }
+    pub fn header(&self) -> &info::Header<'a> {
+        &self.header
+    }
+    pub fn header_mut(&mut self) -> &mut info::Header<'a> {
+        &mut self.header
+    }
+    pub fn styles(&self) -> &[style::Style<'a>] {
+        &self.styles
+    }
+    pub fn styles_mut(&mut self) -> &mut Vec<style::Style<'a>> {
+        &mut self.styles
+    }
+    pub fn events(&self) -> &[event::Event<'a>] {
+        &self.events
+    }
+    pub fn events_mut(&mut self) -> &mut Vec<event::Event<'a>> {
+        &mut self.events
+    }
+    pub fn fonts(&self) -> &[attachment::Attachment<'a>] {
+        &self.fonts
+    }
+    pub fn fonts_mut(&mut self) -> &mut Vec<attachment::Attachment<'a>> {
+        &mut self.fonts
+    }
+    pub fn graphics(&self) -> &[attachment::Attachment<'a>] {
+        &self.graphics
+    }
+    pub fn graphics_mut(&mut self) -> &mut Vec<attachment::Attachment<'a>> {
+        &mut self.graphics
+    }
+    // falls back to Default, like the renderers do for unknown style names.
+    pub fn style(&self, name: Option<&str>) -> Option<&style::Style<'a>> {
+        let name = name.unwrap_or("Default");
+        self.styles
+            .iter()
+            .find(|x| x.name == name)
+            .or_else(|| self.styles.iter().find(|x| x.name == "Default"))
+    }
 }
 
-mod common;
-mod event;
-mod info;
+pub mod attachment;
+pub mod common;
+pub mod event;
+pub mod info;
 mod parser;
-mod style;
+pub use parser::SourceMap;
+pub mod style;
+pub mod text;
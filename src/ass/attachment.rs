@@ -0,0 +1,86 @@
+// embedded files from the [Fonts] and [Graphics] sections. each one starts
+// with a "fontname: x" or "filename: x" line, then the data in ssa's own
+// flavour of uuencoding: every 3 bytes become 4 characters of 6 bits + 33,
+// 80 characters to a line, and a short group at the end only writes the
+// characters it needs.
+
+use std::{borrow::Cow, fmt};
+
+const LINE_LEN: usize = 80;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Attachment<'a> {
+    pub(crate) name: Cow<'a, str>,
+    pub(crate) data: Vec<u8>,
+}
+impl fmt::Display for Attachment<'_> {
+    // just the encoded lines, the section decides what the name line says.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let encoded = encode(&self.data);
+        let lines: Vec<&str> = encoded
+            .as_bytes()
+            .chunks(LINE_LEN)
+            .map(|x| std::str::from_utf8(x).unwrap())
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+impl<'a> Attachment<'a> {
+    pub fn new(name: impl Into<Cow<'a, str>>, data: Vec<u8>) -> Self {
+        Attachment {
+            name: name.into(),
+            data,
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+    pub fn into_owned(self) -> Attachment<'static> {
+        Attachment {
+            name: Cow::Owned(self.name.into_owned()),
+            data: self.data,
+        }
+    }
+    // lines are always whole groups of 4 except the last, so they can be
+    // decoded one at a time as they're read.
+    pub(crate) fn push_line(&mut self, line: &str) {
+        self.data.extend(decode(line));
+    }
+}
+
+fn encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len() * 4 / 3 + 4);
+    for group in data.chunks(3) {
+        let b = [
+            group[0],
+            group.get(1).copied().unwrap_or(0),
+            group.get(2).copied().unwrap_or(0),
+        ];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+        for i in 0..=group.len() {
+            out.push(char::from((n >> (18 - 6 * i) & 0x3f) as u8 + 33));
+        }
+    }
+    out
+}
+
+fn decode(line: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(line.len() * 3 / 4);
+    let values: Vec<u32> = line
+        .bytes()
+        .filter(|x| (33..=96).contains(x))
+        .map(|x| u32::from(x - 33))
+        .collect();
+    for group in values.chunks(4) {
+        let n = group
+            .iter()
+            .enumerate()
+            .fold(0, |n, (i, x)| n | x << (18 - 6 * i));
+        let bytes = n.to_be_bytes();
+        out.extend(&bytes[1..group.len()]);
+    }
+    out
+}
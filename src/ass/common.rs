@@ -1,5 +1,4 @@
 use super::AssParseError;
-use enum_default::EnumDefault;
 use lazy_static::lazy_static;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 use parse_display::Display;
@@ -24,7 +23,7 @@ macro_rules! ass_num_enum {
     };
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive, EnumDefault)]
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive, Default)]
 #[repr(u8)]
 pub enum WrapStyle {
     #[default]
@@ -84,7 +83,7 @@ pub enum BorderStyle {
 
 ass_num_enum!(BorderStyle, BadBorderStyle);
 
-#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive, EnumDefault)]
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive, Default)]
 #[repr(u8)]
 pub enum Alignment {
     BottomLeft = 1,
@@ -101,7 +100,7 @@ pub enum Alignment {
 
 ass_num_enum!(Alignment, BadAlignment);
 
-#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive, EnumDefault)]
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive, IntoPrimitive, Default)]
 #[repr(u8)]
 pub enum Encoding {
     Ansi = 0,
@@ -128,6 +127,7 @@ pub enum Encoding {
 
 ass_num_enum!(Encoding, BadEncoding);
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Display, Debug, Clone, Copy, PartialEq, Default)]
 #[display("&H{0:02X}{1:02X}{2:02X}{3:02X}")]
 pub struct ABGR(u8, u8, u8, u8);
@@ -135,14 +135,35 @@ impl FromStr for ABGR {
     type Err = AssParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // TODO: both renderers are more permissive than this
-        if s.len() == 10 && &s[..2] == "&H" {
-            if let Ok(i) = u32::from_str_radix(&s[2..], 16) {
-                return Ok(Self::from(i));
+        // &HAABBGGRR, or with fewer digits and maybe a trailing &. ssa files
+        // mostly write them as plain decimal, signed when there's alpha.
+        let s = s.trim();
+        let n = match s.strip_prefix("&H").or_else(|| s.strip_prefix("&h")) {
+            Some(x) => {
+                let x = x.strip_suffix('&').unwrap_or(x);
+                match x.len() {
+                    1..=8 => u32::from_str_radix(x, 16).ok(),
+                    _ => None,
+                }
             }
-        }
-
-        Err(AssParseError::BadColourCode)
+            None => s
+                .parse::<u32>()
+                .ok()
+                .or_else(|| s.parse::<i32>().ok().map(|x| x as u32)),
+        };
+        n.map(Self::from).ok_or(AssParseError::BadColourCode)
+    }
+}
+impl ABGR {
+    pub fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        ABGR(0, b, g, r)
+    }
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        (self.3, self.2, self.1)
+    }
+    // ass alpha runs backwards, 0 is opaque.
+    pub fn alpha(&self) -> u8 {
+        self.0
     }
 }
 impl From<u32> for ABGR {
@@ -193,6 +214,15 @@ impl From<u32> for Timecode {
         Timecode(Duration::from_millis(i.into()))
     }
 }
+impl Timecode {
+    // rounds to the nearest millisecond so frame 24 at 24fps is exactly 1s.
+    pub fn from_frames(frames: u32, fps: f64) -> Self {
+        Timecode::from((f64::from(frames) * 1000.0 / fps).round() as u32)
+    }
+    pub fn as_millis(&self) -> u32 {
+        self.0.as_millis() as u32
+    }
+}
 
 pub fn split_line(s: &str) -> Result<(&str, &str), AssParseError> {
     lazy_static! {
@@ -5,7 +5,7 @@ use super::AssParseError::{
 };
 use parse_display::Display;
 use smart_default::SmartDefault;
-use std::{cmp::Ordering, fmt, str::FromStr};
+use std::{borrow::Cow, cmp::Ordering, fmt, str::FromStr};
 
 #[derive(Display, Debug, Clone, Copy, PartialEq)]
 pub enum Token {
@@ -19,6 +19,8 @@ pub enum Token {
     MarginV,
     Effect,
     Text,
+    // ssa's, where v4+ has Layer. always Marked=0 in practice.
+    Marked,
 }
 impl FromStr for Token {
     type Err = AssParseError;
@@ -34,6 +36,7 @@ impl FromStr for Token {
             "MarginV" => MarginV,
             "Effect" => Effect,
             "Text" => Text,
+            "Marked" => Marked,
             _ => return Err(BadEventToken),
         })
     }
@@ -80,29 +83,55 @@ impl Format {
     fn new() -> Format {
         Format(Vec::<Token>::new())
     }
+    // what an ssa [Events] section has when it doesn't say.
+    pub fn ssa() -> Format {
+        Format(vec![
+            Marked, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text,
+        ])
+    }
+    // ssa events are read into v4+ ones, and written out as those.
+    pub fn v4plus(&self) -> Format {
+        match self.0.contains(&Marked) {
+            true => Format::default(),
+            false => self.clone(),
+        }
+    }
     fn push(&mut self, t: Token) {
         self.0.push(t)
     }
 }
 
+// what a line is for, going by its descriptor. the ones besides dialogue and
+// comment are in the spec but nothing renders them.
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EventKind {
+    Dialogue,
+    Comment,
+    Picture,
+    Sound,
+    Movie,
+    Command,
+    Other,
+}
+
 #[derive(Debug, Clone, SmartDefault)]
 pub struct Event<'a> {
-    format: Format,
-    start_time: Timecode,
+    pub(crate) format: Format,
+    pub(crate) start_time: Timecode,
     #[default(Timecode::from(10_000))]
-    end_time: Timecode,
+    pub(crate) end_time: Timecode,
 
-    #[default("Dialogue")]
-    descriptor: &'a str,
-    layer: u32,
-    #[default(Some("Default"))]
-    style: Option<&'a str>,
-    actor: Option<&'a str>,
-    margin_l: i32,
-    margin_r: i32,
-    margin_v: i32,
-    effect: Option<&'a str>,
-    text: Option<&'a str>,
+    #[default(Cow::Borrowed("Dialogue"))]
+    pub(crate) descriptor: Cow<'a, str>,
+    pub(crate) layer: u32,
+    #[default(Some(Cow::Borrowed("Default")))]
+    pub(crate) style: Option<Cow<'a, str>>,
+    pub(crate) actor: Option<Cow<'a, str>>,
+    pub(crate) margin_l: i32,
+    pub(crate) margin_r: i32,
+    pub(crate) margin_v: i32,
+    pub(crate) effect: Option<Cow<'a, str>>,
+    pub(crate) text: Option<Cow<'a, str>>,
 }
 
 impl fmt::Display for Event<'_> {
@@ -111,24 +140,155 @@ impl fmt::Display for Event<'_> {
             self.format
                 .0
                 .iter()
-                .map(|x| match x {
-                    Layer => self.layer.to_string(),
-                    Start => self.start_time.to_string(),
-                    End => self.end_time.to_string(),
-                    Style => self.style.unwrap_or("").to_owned(),
-                    Name => self.actor.unwrap_or("").to_owned(),
-                    MarginL => self.margin_l.to_string(),
-                    MarginR => self.margin_r.to_string(),
-                    MarginV => self.margin_v.to_string(),
-                    Effect => self.effect.unwrap_or("").to_owned(),
-                    Text => self.text.unwrap_or("").to_owned(),
-                })
+                .map(|x| self.value(*x))
                 .collect::<Vec<String>>()
                 .join(",")
         })
     }
 }
 impl<'a> Event<'a> {
+    // a Dialogue line in the Default style. the with_ methods change the rest.
+    pub fn new(start: Timecode, end: Timecode, text: impl Into<Cow<'a, str>>) -> Self {
+        Event {
+            start_time: start,
+            end_time: end,
+            text: Some(text.into()),
+            ..Event::default()
+        }
+    }
+    pub fn with_descriptor(self, descriptor: impl Into<Cow<'a, str>>) -> Self {
+        Event {
+            descriptor: descriptor.into(),
+            ..self
+        }
+    }
+    pub fn with_layer(self, layer: u32) -> Self {
+        Event { layer, ..self }
+    }
+    pub fn with_style(self, style: impl Into<Cow<'a, str>>) -> Self {
+        Event {
+            style: Some(style.into()),
+            ..self
+        }
+    }
+    pub fn with_actor(self, actor: impl Into<Cow<'a, str>>) -> Self {
+        Event {
+            actor: Some(actor.into()),
+            ..self
+        }
+    }
+    pub fn with_margins(self, (margin_l, margin_r, margin_v): (i32, i32, i32)) -> Self {
+        Event {
+            margin_l,
+            margin_r,
+            margin_v,
+            ..self
+        }
+    }
+    pub fn with_effect(self, effect: impl Into<Cow<'a, str>>) -> Self {
+        Event {
+            effect: Some(effect.into()),
+            ..self
+        }
+    }
+    pub fn start(&self) -> Timecode {
+        self.start_time
+    }
+    pub fn end(&self) -> Timecode {
+        self.end_time
+    }
+    pub fn set_times(&mut self, start: Timecode, end: Timecode) {
+        self.start_time = start;
+        self.end_time = end;
+    }
+    pub fn descriptor(&self) -> &str {
+        &self.descriptor
+    }
+    pub fn layer(&self) -> u32 {
+        self.layer
+    }
+    pub fn style(&self) -> Option<&str> {
+        self.style.as_deref()
+    }
+    pub fn actor(&self) -> Option<&str> {
+        self.actor.as_deref()
+    }
+    // as written on the event, where 0 is the style's. margins() resolves
+    // them.
+    pub fn own_margins(&self) -> (i32, i32, i32) {
+        (self.margin_l, self.margin_r, self.margin_v)
+    }
+    pub fn effect(&self) -> Option<&str> {
+        self.effect.as_deref()
+    }
+    // with the override tags, see text for taking them apart.
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+    pub fn set_text(&mut self, text: impl Into<Cow<'a, str>>) {
+        self.text = Some(text.into());
+    }
+    pub fn kind(&self) -> EventKind {
+        match &*self.descriptor {
+            "Dialogue" => EventKind::Dialogue,
+            "Comment" => EventKind::Comment,
+            "Picture" => EventKind::Picture,
+            "Sound" => EventKind::Sound,
+            "Movie" => EventKind::Movie,
+            "Command" => EventKind::Command,
+            _ => EventKind::Other,
+        }
+    }
+    fn value(&self, token: Token) -> String {
+        match token {
+            Layer => self.layer.to_string(),
+            Start => self.start_time.to_string(),
+            End => self.end_time.to_string(),
+            Style => self.style.as_deref().unwrap_or("").to_owned(),
+            Name => self.actor.as_deref().unwrap_or("").to_owned(),
+            MarginL => self.margin_l.to_string(),
+            MarginR => self.margin_r.to_string(),
+            MarginV => self.margin_v.to_string(),
+            Effect => self.effect.as_deref().unwrap_or("").to_owned(),
+            Text => self.text.as_deref().unwrap_or("").to_owned(),
+            Marked => "Marked=0".to_owned(),
+        }
+    }
+    // every field by its format name, whatever format the event was read
+    // with, text last.
+    pub fn fields(&self) -> Vec<(String, String)> {
+        Format::default()
+            .0
+            .into_iter()
+            .map(|x| (x.to_string(), self.value(x)))
+            .collect()
+    }
+    pub fn into_owned(self) -> Event<'static> {
+        let owned = |x: Option<Cow<str>>| x.map(|x| Cow::Owned(x.into_owned()));
+        Event {
+            format: self.format,
+            start_time: self.start_time,
+            end_time: self.end_time,
+            descriptor: Cow::Owned(self.descriptor.into_owned()),
+            layer: self.layer,
+            style: owned(self.style),
+            actor: owned(self.actor),
+            margin_l: self.margin_l,
+            margin_r: self.margin_r,
+            margin_v: self.margin_v,
+            effect: owned(self.effect),
+            text: owned(self.text),
+        }
+    }
+    // (left, right, vertical). 0 on the event means use the style's.
+    pub fn margins(&self, style: &super::style::Style) -> (i32, i32, i32) {
+        let pick = |event: i32, style: i32| if event != 0 { event } else { style };
+        (
+            pick(self.margin_l, style.margin_l),
+            pick(self.margin_r, style.margin_r),
+            pick(self.margin_v, style.margin_v),
+        )
+    }
     pub fn parse(
         s: &'a str,
         d: Option<&'a str>,
@@ -136,7 +296,7 @@ impl<'a> Event<'a> {
     ) -> Result<Event<'a>, AssParseError> {
         let mut res = Event::default();
         if let Some(v) = d {
-            res.descriptor = v;
+            res.descriptor = Cow::Borrowed(v);
         }
         if let Some(v) = f {
             res.format = v.clone();
@@ -150,7 +310,8 @@ impl<'a> Event<'a> {
                 break;
             }
         }
-        data.push(&s[data.join(",").len() + 1..]);
+        // fewer fields than the format leaves nothing after the last comma.
+        data.push(s.get(data.join(",").len() + 1..).ok_or(EventTooShort)?);
         match data.len().cmp(&res.format.0.len()) {
             Ordering::Greater => return Err(EventTooLong),
             Ordering::Less => return Err(EventTooShort),
@@ -161,15 +322,17 @@ impl<'a> Event<'a> {
                 Layer => res.layer = value.parse().or(Err(EventNotMatchFormat(Layer)))?,
                 Start => res.start_time = value.parse().or(Err(EventNotMatchFormat(Start)))?,
                 End => res.end_time = value.parse().or(Err(EventNotMatchFormat(End)))?,
-                Style => res.style = Some(value),
-                Name => res.actor = Some(value),
+                Style => res.style = Some(Cow::Borrowed(value)),
+                Name => res.actor = Some(Cow::Borrowed(value)),
                 MarginL => res.margin_l = value.parse().or(Err(EventNotMatchFormat(MarginL)))?,
                 MarginR => res.margin_r = value.parse().or(Err(EventNotMatchFormat(MarginR)))?,
                 MarginV => res.margin_v = value.parse().or(Err(EventNotMatchFormat(MarginV)))?,
-                Effect => res.effect = Some(value),
-                Text => res.text = Some(value),
+                Effect => res.effect = Some(Cow::Borrowed(value)),
+                Text => res.text = Some(Cow::Borrowed(value)),
+                Marked => {}
             };
         }
+        res.format = res.format.v4plus();
         Ok(res)
     }
 }
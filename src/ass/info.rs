@@ -1,7 +1,7 @@
 use self::ConfigKind::*;
 use super::common::{WrapStyle, YCbCrMatrix};
 use super::AssParseError::{self, BadConfigData, BadConfigField};
-use std::fmt;
+use std::{borrow::Cow, fmt};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ConfigKind<'a> {
@@ -53,35 +53,45 @@ impl<'a> ConfigKind<'a> {
         })
     }
 }
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct Header<'a> {
-    title: Option<&'a str>,
-    script_type: Option<&'a str>,
-    wrap_style: Option<WrapStyle>,
-    play_res_x: Option<u32>,
-    play_res_y: Option<u32>,
-    scaled_border_and_shadow: Option<bool>,
-    ycbcr_matrix: Option<YCbCrMatrix>,
-    script: Option<&'a str>,
-    translation: Option<&'a str>,
-    editing: Option<&'a str>,
-    timing: Option<&'a str>,
-    synch_point: Option<&'a str>,
-    updated_by: Option<&'a str>,
-    update_details: Option<&'a str>,
-    kerning: Option<bool>,
-    language: Option<&'a str>,
+    pub(crate) title: Option<Cow<'a, str>>,
+    pub(crate) script_type: Option<Cow<'a, str>>,
+    pub(crate) wrap_style: Option<WrapStyle>,
+    pub(crate) play_res_x: Option<u32>,
+    pub(crate) play_res_y: Option<u32>,
+    pub(crate) scaled_border_and_shadow: Option<bool>,
+    pub(crate) ycbcr_matrix: Option<YCbCrMatrix>,
+    pub(crate) script: Option<Cow<'a, str>>,
+    pub(crate) translation: Option<Cow<'a, str>>,
+    pub(crate) editing: Option<Cow<'a, str>>,
+    pub(crate) timing: Option<Cow<'a, str>>,
+    pub(crate) synch_point: Option<Cow<'a, str>>,
+    pub(crate) updated_by: Option<Cow<'a, str>>,
+    pub(crate) update_details: Option<Cow<'a, str>>,
+    pub(crate) kerning: Option<bool>,
+    pub(crate) language: Option<Cow<'a, str>>,
 }
 impl fmt::Display for Header<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in self.fields() {
+            writeln!(f, "{}: {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+impl<'a> Header<'a> {
+    // the fields that are set, named and written the way the file has them.
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut res = Vec::new();
         let yesno = |x| match x {
-            true => "yes",
-            false => "no",
+            true => "yes".to_owned(),
+            false => "no".to_owned(),
         };
         macro_rules! field {
             ($field:ident, $name:literal) => {
-                if let Some(x) = self.$field {
-                    write!(f, concat!($name, ": {}\n"), x)?;
+                if let Some(x) = &self.$field {
+                    res.push(($name, x.to_string()));
                 }
             };
         }
@@ -91,7 +101,7 @@ impl fmt::Display for Header<'_> {
         field!(play_res_x, "PlayResX");
         field!(play_res_y, "PlayResY");
         if let Some(x) = self.scaled_border_and_shadow {
-            write!(f, "ScaledBorderAndShadow: {}\n", yesno(x))?;
+            res.push(("ScaledBorderAndShadow", yesno(x)));
         }
         field!(ycbcr_matrix, "YCbCr Matrix");
         field!(script, "Original Script");
@@ -102,20 +112,48 @@ impl fmt::Display for Header<'_> {
         field!(updated_by, "Script Updated By");
         field!(update_details, "Update Details");
         if let Some(x) = self.kerning {
-            write!(f, "Kerning: {}\n", yesno(x))?;
+            res.push(("Kerning", yesno(x)));
         }
         field!(language, "Language");
-        Ok(())
+        res
+    }
+    pub fn into_owned(self) -> Header<'static> {
+        let owned = |x: Option<Cow<str>>| x.map(|x| Cow::Owned(x.into_owned()));
+        Header {
+            title: owned(self.title),
+            script_type: owned(self.script_type),
+            wrap_style: self.wrap_style,
+            play_res_x: self.play_res_x,
+            play_res_y: self.play_res_y,
+            scaled_border_and_shadow: self.scaled_border_and_shadow,
+            ycbcr_matrix: self.ycbcr_matrix,
+            script: owned(self.script),
+            translation: owned(self.translation),
+            editing: owned(self.editing),
+            timing: owned(self.timing),
+            synch_point: owned(self.synch_point),
+            updated_by: owned(self.updated_by),
+            update_details: owned(self.update_details),
+            kerning: self.kerning,
+            language: owned(self.language),
+        }
+    }
+    // what renderers fall back to when one or both are missing.
+    pub fn play_res(&self) -> (u32, u32) {
+        match (self.play_res_x, self.play_res_y) {
+            (Some(x), Some(y)) => (x, y),
+            (Some(x), None) => (x, x * 3 / 4),
+            (None, Some(y)) => (y * 4 / 3, y),
+            (None, None) => (384, 288),
+        }
     }
-}
-impl<'a> Header<'a> {
     pub fn set(&mut self, c: ConfigKind<'a>) {
         match c {
             Title(x) => {
-                self.title.get_or_insert(x);
+                self.title.get_or_insert(Cow::Borrowed(x));
             }
             ScriptType(x) => {
-                self.script_type.get_or_insert(x);
+                self.script_type.get_or_insert(Cow::Borrowed(x));
             }
             WrapStyle(x) => {
                 self.wrap_style.get_or_insert(x);
@@ -133,31 +171,31 @@ impl<'a> Header<'a> {
                 self.ycbcr_matrix.get_or_insert(x);
             }
             ScriptCredit(x) => {
-                self.script.get_or_insert(x);
+                self.script.get_or_insert(Cow::Borrowed(x));
             }
             TranslationCredit(x) => {
-                self.translation.get_or_insert(x);
+                self.translation.get_or_insert(Cow::Borrowed(x));
             }
             EditingCredit(x) => {
-                self.editing.get_or_insert(x);
+                self.editing.get_or_insert(Cow::Borrowed(x));
             }
             TimingCredit(x) => {
-                self.timing.get_or_insert(x);
+                self.timing.get_or_insert(Cow::Borrowed(x));
             }
             SynchPoint(x) => {
-                self.synch_point.get_or_insert(x);
+                self.synch_point.get_or_insert(Cow::Borrowed(x));
             }
             UpdateCredit(x) => {
-                self.updated_by.get_or_insert(x);
+                self.updated_by.get_or_insert(Cow::Borrowed(x));
             }
             UpdateDetails(x) => {
-                self.update_details.get_or_insert(x);
+                self.update_details.get_or_insert(Cow::Borrowed(x));
             }
             Kerning(x) => {
                 self.kerning.get_or_insert(x);
             }
             Language(x) => {
-                self.language.get_or_insert(x);
+                self.language.get_or_insert(Cow::Borrowed(x));
             }
         };
     }
@@ -1,6 +1,7 @@
+use super::attachment::Attachment;
 use super::common::split_line;
 use super::AssParseError::{
-    self, EncounteredIllegalHeader, EnteredNoneState, NoParserState, UnknownSection,
+    self, BadLineFormat, EncounteredIllegalHeader, EnteredNoneState, NoParserState, UnknownSection,
 };
 use super::AssTrack;
 use super::{event, info, style};
@@ -13,6 +14,8 @@ pub enum ParserState {
     Info,
     Styles,
     Events,
+    Fonts,
+    Graphics,
     Other(String),
 }
 
@@ -20,6 +23,11 @@ struct AssParser<'a> {
     state: ParserState,
     track: AssTrack<'a>,
     previous_states: Vec<ParserState>,
+    // a [V4 Styles] section means ssa, whose styles and events are read with
+    // their own formats and kept as v4+ ones.
+    ssa: bool,
+    styleformat: Option<style::Format>,
+    eventformat: Option<event::Format>,
 }
 impl AssParser<'_> {
     fn switch_state(&mut self, new_state: ParserState) -> Result<(), AssParseError> {
@@ -36,7 +44,7 @@ impl AssParser<'_> {
                 }
             }
             Styles => {
-                if self.previous_states.iter().any(|x| *x == Events) {
+                if self.previous_states.contains(&Events) {
                     Err(EncounteredIllegalHeader)
                 } else {
                     self.previous_states.push(self.state.clone());
@@ -44,9 +52,9 @@ impl AssParser<'_> {
                     Ok(())
                 }
             }
-            Events => {
+            Events | Fonts | Graphics => {
                 self.previous_states.push(self.state.clone());
-                self.state = Events;
+                self.state = new_state;
                 Ok(())
             }
             Other(s) => {
@@ -59,26 +67,69 @@ impl AssParser<'_> {
 }
 
 pub fn parse_track<'a>(s: &'a str) -> Result<AssTrack<'a>, AssParseError> {
+    let (track, map) = parse_track_mapped(s).map_err(|(_, e)| e)?;
+    for (line_n, e) in map.dropped {
+        eprintln!("Dropped line {}: {}", line_n - 1, e);
+    }
+    Ok(track)
+}
+
+// where everything came from, for pointing people at the right line. line
+// numbers start at 1, like editors count them.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    pub styles: Vec<usize>,
+    pub events: Vec<usize>,
+    // lines that didn't parse and were left out, which includes everything
+    // in sections we don't know.
+    pub dropped: Vec<(usize, AssParseError)>,
+    // sections we don't know, which are skipped whole.
+    pub unknown_sections: Vec<(usize, String)>,
+}
+
+pub fn parse_track_mapped<'a>(
+    s: &'a str,
+) -> Result<(AssTrack<'a>, SourceMap), (usize, AssParseError)> {
     let mut parser = AssParser {
         state: ParserState::None,
         track: AssTrack::<'a>::default(),
         previous_states: Vec::<ParserState>::new(),
+        ssa: false,
+        styleformat: Option::None,
+        eventformat: Option::None,
     };
+    let mut map = SourceMap::default();
 
     // was too tired to write this. split into another function.
     for (line_n, line) in s.lines().enumerate() {
+        let line_n = line_n + 1;
         let line = line.trim();
-        if line == "" {
+        if line.is_empty() {
             continue;
         }
-        if let Err(e) = parse_line(&mut parser, line) {
-            match e {
-                NoParserState | EncounteredIllegalHeader => return Err(e),
-                _ => eprintln!("Dropped line {}: {}", line_n, e),
-            };
+        let (styles, events) = (parser.track.styles.len(), parser.track.events.len());
+        match parse_line(&mut parser, line) {
+            Ok(()) => {}
+            Err(e @ (NoParserState | EncounteredIllegalHeader)) => return Err((line_n, e)),
+            Err(e) => map.dropped.push((line_n, e)),
         }
+        if parser.track.styles.len() > styles {
+            map.styles.push(line_n);
+        }
+        if parser.track.events.len() > events {
+            map.events.push(line_n);
+        }
+        if let ParserState::Other(name) = &parser.state {
+            if line == name {
+                map.unknown_sections.push((line_n, name.clone()));
+            }
+        }
+    }
+    // it's written back out with v4+ sections, so it's a v4+ script now.
+    if parser.ssa {
+        parser.track.header.script_type = Some(std::borrow::Cow::Borrowed("v4.00+"));
     }
-    Ok(parser.track)
+    Ok((parser.track, map))
 }
 
 fn parse_line<'a>(parser: &mut AssParser<'a>, line: &'a str) -> Result<(), AssParseError> {
@@ -87,13 +138,40 @@ fn parse_line<'a>(parser: &mut AssParser<'a>, line: &'a str) -> Result<(), AssPa
         static ref H_RE: Regex = Regex::new(r"^\[.+\]$").unwrap();
     }
     let line = line.trim();
-    if H_RE.is_match(line) {
+    // [ and ] are in the attachment alphabet, so a data line can look like a
+    // header. the alphabet has no lowercase letters and every section name
+    // does, which tells them apart.
+    let in_files = matches!(parser.state, Fonts | Graphics);
+    if H_RE.is_match(line) && (!in_files || line.bytes().any(|x| x.is_ascii_lowercase())) {
         match line {
             "[Script Info]" => parser.switch_state(Info),
             "[V4+ Styles]" => parser.switch_state(Styles),
+            "[V4 Styles]" => {
+                parser.ssa = true;
+                parser.switch_state(Styles)
+            }
             "[Events]" => parser.switch_state(Events),
+            "[Fonts]" => parser.switch_state(Fonts),
+            "[Graphics]" => parser.switch_state(Graphics),
             _ => parser.switch_state(Other(line.to_owned())),
         }
+    } else if in_files {
+        // the data lines aren't Field: Data, and can't contain lowercase
+        // letters, so the name lines are easy to tell apart.
+        let files = match parser.state {
+            Fonts => &mut parser.track.fonts,
+            _ => &mut parser.track.graphics,
+        };
+        let name = match parser.state {
+            Fonts => line.strip_prefix("fontname:"),
+            _ => line.strip_prefix("filename:"),
+        };
+        if let Some(name) = name {
+            files.push(Attachment::new(name.trim(), Vec::new()));
+        } else {
+            files.last_mut().ok_or(BadLineFormat)?.push_line(line);
+        }
+        Ok(())
     } else {
         let (field, data) = split_line(line)?;
         match parser.state {
@@ -104,32 +182,40 @@ fn parse_line<'a>(parser: &mut AssParser<'a>, line: &'a str) -> Result<(), AssPa
                 .set(info::ConfigKind::parse(field, data)?),
             Styles => {
                 if field == "Format" {
-                    parser.track.styleformat = Some(data.parse()?)
-                } else {
-                    if parser.track.styleformat.is_none() {
-                        parser.track.styleformat = Some(style::Format::default())
-                    }
-                    parser.track.styles.push(style::Style::parse(
-                        data,
-                        parser.track.styleformat.as_ref(),
-                    )?)
+                    parser.styleformat = Some(data.parse()?)
+                } else if parser.styleformat.is_none() {
+                    parser.styleformat = Some(match parser.ssa {
+                        true => style::Format::ssa(),
+                        false => style::Format::default(),
+                    })
+                }
+                parser.track.styleformat = parser.styleformat.as_ref().map(|x| x.v4plus());
+                if field != "Format" {
+                    parser
+                        .track
+                        .styles
+                        .push(style::Style::parse(data, parser.styleformat.as_ref())?)
                 }
             }
             Events => {
                 if field == "Format" {
-                    parser.track.eventformat = Some(data.parse()?)
-                } else {
-                    if parser.track.eventformat.is_none() {
-                        parser.track.eventformat = Some(event::Format::default())
-                    }
+                    parser.eventformat = Some(data.parse()?)
+                } else if parser.eventformat.is_none() {
+                    parser.eventformat = Some(match parser.ssa {
+                        true => event::Format::ssa(),
+                        false => event::Format::default(),
+                    })
+                }
+                parser.track.eventformat = parser.eventformat.as_ref().map(|x| x.v4plus());
+                if field != "Format" {
                     parser.track.events.push(event::Event::parse(
                         data,
                         Some(field),
-                        parser.track.eventformat.as_ref(),
+                        parser.eventformat.as_ref(),
                     )?)
                 }
             }
-            Other(_) => return Err(UnknownSection),
+            Fonts | Graphics | Other(_) => return Err(UnknownSection),
         };
         Ok(())
     }
@@ -1,8 +1,8 @@
 use self::Token::*;
 use super::common::{Alignment, BorderStyle, Encoding, ABGR};
-use super::AssParseError::{self, BadAssBool, BadStyleToken, StyleNotMatchFormat};
+use super::AssParseError::{self, BadAlignment, BadAssBool, BadStyleToken, StyleNotMatchFormat};
 use parse_display::Display;
-use std::{fmt, str::FromStr};
+use std::{borrow::Cow, convert::TryInto, fmt, str::FromStr};
 
 #[derive(Display, Debug, Clone, Copy, PartialEq)]
 enum Token {
@@ -29,6 +29,10 @@ enum Token {
     MarginR,
     MarginV,
     Encoding,
+    // ssa v4 only. tertiary is what became the outline colour, and the alpha
+    // level was never used by anything.
+    TertiaryColour,
+    AlphaLevel,
 }
 impl FromStr for Token {
     type Err = AssParseError;
@@ -57,6 +61,8 @@ impl FromStr for Token {
             "MarginR" => MarginR,
             "MarginV" => MarginV,
             "Encoding" => Encoding,
+            "TertiaryColour" => TertiaryColour,
+            "AlphaLevel" => AlphaLevel,
             _ => return Err(BadStyleToken),
         })
     }
@@ -121,6 +127,39 @@ impl Format {
     fn new() -> Format {
         Format(Vec::<Token>::new())
     }
+    // what a [V4 Styles] section has when it doesn't say.
+    pub fn ssa() -> Format {
+        Format(vec![
+            Name,
+            Fontname,
+            Fontsize,
+            PrimaryColour,
+            SecondaryColour,
+            TertiaryColour,
+            BackColour,
+            Bold,
+            Italic,
+            BorderStyle,
+            Outline,
+            Shadow,
+            Alignment,
+            MarginL,
+            MarginR,
+            MarginV,
+            AlphaLevel,
+            Encoding,
+        ])
+    }
+    pub fn is_ssa(&self) -> bool {
+        self.0.contains(&TertiaryColour)
+    }
+    // ssa styles are read into v4+ ones, and written out as those.
+    pub fn v4plus(&self) -> Format {
+        match self.is_ssa() {
+            true => Format::default(),
+            false => self.clone(),
+        }
+    }
     fn push(&mut self, t: Token) {
         self.0.push(t)
     }
@@ -128,30 +167,30 @@ impl Format {
 
 #[derive(Debug, Clone)]
 pub struct Style<'a> {
-    format: Format,
-    name: &'a str,
-    font_name: &'a str,
-    font_size: u32,
-    primary_colour: ABGR,
-    secondary_colour: ABGR,
-    outline_colour: ABGR,
-    back_colour: ABGR,
-    bold: bool,
-    italic: bool,
-    underline: bool,
-    strikeout: bool,
-    scale_x: u32,
-    scale_y: u32,
-    spacing: f64,
-    angle: u32,
-    border_style: BorderStyle,
-    outline: f64,
-    shadow: f64,
-    alignment: Alignment,
-    margin_l: i32,
-    margin_r: i32,
-    margin_v: i32,
-    encoding: Encoding,
+    pub(crate) format: Format,
+    pub(crate) name: Cow<'a, str>,
+    pub(crate) font_name: Cow<'a, str>,
+    pub(crate) font_size: u32,
+    pub(crate) primary_colour: ABGR,
+    pub(crate) secondary_colour: ABGR,
+    pub(crate) outline_colour: ABGR,
+    pub(crate) back_colour: ABGR,
+    pub(crate) bold: bool,
+    pub(crate) italic: bool,
+    pub(crate) underline: bool,
+    pub(crate) strikeout: bool,
+    pub(crate) scale_x: u32,
+    pub(crate) scale_y: u32,
+    pub(crate) spacing: f64,
+    pub(crate) angle: u32,
+    pub(crate) border_style: BorderStyle,
+    pub(crate) outline: f64,
+    pub(crate) shadow: f64,
+    pub(crate) alignment: Alignment,
+    pub(crate) margin_l: i32,
+    pub(crate) margin_r: i32,
+    pub(crate) margin_v: i32,
+    pub(crate) encoding: Encoding,
 }
 impl fmt::Display for Style<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -159,31 +198,7 @@ impl fmt::Display for Style<'_> {
             self.format
                 .0
                 .iter()
-                .map(|x| match x {
-                    Name => self.name.to_owned(),
-                    Fontname => self.font_name.to_owned(),
-                    Fontsize => self.font_size.to_string(),
-                    PrimaryColour => self.primary_colour.to_string(),
-                    SecondaryColour => self.secondary_colour.to_string(),
-                    OutlineColour => self.outline_colour.to_string(),
-                    BackColour => self.back_colour.to_string(),
-                    Bold => bool_to_ass_bool(self.bold),
-                    Italic => bool_to_ass_bool(self.italic),
-                    Underline => bool_to_ass_bool(self.underline),
-                    StrikeOut => bool_to_ass_bool(self.strikeout),
-                    ScaleX => self.scale_x.to_string(),
-                    ScaleY => self.scale_y.to_string(),
-                    Spacing => self.spacing.to_string(),
-                    Angle => self.angle.to_string(),
-                    BorderStyle => self.border_style.to_string(),
-                    Outline => self.outline.to_string(),
-                    Shadow => self.shadow.to_string(),
-                    Alignment => self.alignment.to_string(),
-                    MarginL => self.margin_l.to_string(),
-                    MarginR => self.margin_r.to_string(),
-                    MarginV => self.margin_v.to_string(),
-                    Encoding => self.encoding.to_string(),
-                })
+                .map(|x| self.value(*x))
                 .collect::<Vec<String>>()
                 .join(",")
         })
@@ -193,8 +208,8 @@ impl Default for Style<'_> {
     fn default() -> Self {
         Style {
             format: Format::default(),
-            name: "Default",
-            font_name: "Arial",
+            name: Cow::Borrowed("Default"),
+            font_name: Cow::Borrowed("Arial"),
             font_size: 18,
             primary_colour: ABGR::from(0xffffff00),
             secondary_colour: ABGR::from(0x00ffff00),
@@ -220,6 +235,149 @@ impl Default for Style<'_> {
     }
 }
 impl<'a> Style<'a> {
+    // the default style under another name. the with_ methods change the rest,
+    // and parse takes a whole style line.
+    pub fn new(name: impl Into<Cow<'a, str>>) -> Self {
+        Style {
+            name: name.into(),
+            ..Style::default()
+        }
+    }
+    pub fn with_font(self, font_name: impl Into<Cow<'a, str>>, font_size: u32) -> Self {
+        Style {
+            font_name: font_name.into(),
+            font_size,
+            ..self
+        }
+    }
+    // primary, secondary, outline and back.
+    pub fn with_colours(self, colours: [ABGR; 4]) -> Self {
+        let [primary_colour, secondary_colour, outline_colour, back_colour] = colours;
+        Style {
+            primary_colour,
+            secondary_colour,
+            outline_colour,
+            back_colour,
+            ..self
+        }
+    }
+    // bold, italic, underline and strikeout.
+    pub fn with_emphasis(self, emphasis: [bool; 4]) -> Self {
+        let [bold, italic, underline, strikeout] = emphasis;
+        Style {
+            bold,
+            italic,
+            underline,
+            strikeout,
+            ..self
+        }
+    }
+    pub fn with_border(self, border_style: BorderStyle, outline: f64, shadow: f64) -> Self {
+        Style {
+            border_style,
+            outline,
+            shadow,
+            ..self
+        }
+    }
+    pub fn with_alignment(self, alignment: Alignment) -> Self {
+        Style { alignment, ..self }
+    }
+    pub fn with_margins(self, (margin_l, margin_r, margin_v): (i32, i32, i32)) -> Self {
+        Style {
+            margin_l,
+            margin_r,
+            margin_v,
+            ..self
+        }
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn font_name(&self) -> &str {
+        &self.font_name
+    }
+    pub fn font_size(&self) -> u32 {
+        self.font_size
+    }
+    pub fn colours(&self) -> [ABGR; 4] {
+        [
+            self.primary_colour,
+            self.secondary_colour,
+            self.outline_colour,
+            self.back_colour,
+        ]
+    }
+    pub fn emphasis(&self) -> [bool; 4] {
+        [self.bold, self.italic, self.underline, self.strikeout]
+    }
+    // (x, y) in percent.
+    pub fn scale(&self) -> (u32, u32) {
+        (self.scale_x, self.scale_y)
+    }
+    pub fn spacing(&self) -> f64 {
+        self.spacing
+    }
+    pub fn angle(&self) -> u32 {
+        self.angle
+    }
+    pub fn border(&self) -> (BorderStyle, f64, f64) {
+        (self.border_style, self.outline, self.shadow)
+    }
+    pub fn alignment(&self) -> Alignment {
+        self.alignment
+    }
+    pub fn margins(&self) -> (i32, i32, i32) {
+        (self.margin_l, self.margin_r, self.margin_v)
+    }
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+    fn value(&self, token: Token) -> String {
+        match token {
+            Name => self.name.to_string(),
+            Fontname => self.font_name.to_string(),
+            Fontsize => self.font_size.to_string(),
+            PrimaryColour => self.primary_colour.to_string(),
+            SecondaryColour => self.secondary_colour.to_string(),
+            OutlineColour => self.outline_colour.to_string(),
+            BackColour => self.back_colour.to_string(),
+            Bold => bool_to_ass_bool(self.bold),
+            Italic => bool_to_ass_bool(self.italic),
+            Underline => bool_to_ass_bool(self.underline),
+            StrikeOut => bool_to_ass_bool(self.strikeout),
+            ScaleX => self.scale_x.to_string(),
+            ScaleY => self.scale_y.to_string(),
+            Spacing => self.spacing.to_string(),
+            Angle => self.angle.to_string(),
+            BorderStyle => self.border_style.to_string(),
+            Outline => self.outline.to_string(),
+            Shadow => self.shadow.to_string(),
+            Alignment => self.alignment.to_string(),
+            MarginL => self.margin_l.to_string(),
+            MarginR => self.margin_r.to_string(),
+            MarginV => self.margin_v.to_string(),
+            Encoding => self.encoding.to_string(),
+            TertiaryColour => self.outline_colour.to_string(),
+            AlphaLevel => "0".to_owned(),
+        }
+    }
+    // every field by its format name, whatever format the style was read
+    // with.
+    pub fn fields(&self) -> Vec<(String, String)> {
+        Format::default()
+            .0
+            .into_iter()
+            .map(|x| (x.to_string(), self.value(x)))
+            .collect()
+    }
+    pub fn into_owned(self) -> Style<'static> {
+        Style {
+            name: Cow::Owned(self.name.into_owned()),
+            font_name: Cow::Owned(self.font_name.into_owned()),
+            ..self
+        }
+    }
     pub fn parse(s: &'a str, f: Option<&Format>) -> Result<Style<'a>, AssParseError> {
         let mut res = Style::default();
         if let Some(v) = f {
@@ -229,10 +387,11 @@ impl<'a> Style<'a> {
         if data.len() != res.format.0.len() {
             return Err(StyleNotMatchFormat);
         }
+        let ssa = res.format.is_ssa();
         for (token, value) in res.format.0.iter().zip(data.iter()) {
             match token {
-                Name => res.name = value,
-                Fontname => res.font_name = value,
+                Name => res.name = Cow::Borrowed(value),
+                Fontname => res.font_name = Cow::Borrowed(value),
                 Fontsize => res.font_size = value.parse().or(Err(StyleNotMatchFormat))?,
                 PrimaryColour => res.primary_colour = value.parse()?,
                 SecondaryColour => res.secondary_colour = value.parse()?,
@@ -249,17 +408,34 @@ impl<'a> Style<'a> {
                 BorderStyle => res.border_style = value.parse()?,
                 Outline => res.outline = value.parse().or(Err(StyleNotMatchFormat))?,
                 Shadow => res.shadow = value.parse().or(Err(StyleNotMatchFormat))?,
+                Alignment if ssa => res.alignment = ssa_alignment(value)?,
                 Alignment => res.alignment = value.parse()?,
                 MarginL => res.margin_l = value.parse().or(Err(StyleNotMatchFormat))?,
                 MarginR => res.margin_r = value.parse().or(Err(StyleNotMatchFormat))?,
                 MarginV => res.margin_v = value.parse().or(Err(StyleNotMatchFormat))?,
                 Encoding => res.encoding = value.parse()?,
+                TertiaryColour => res.outline_colour = value.parse()?,
+                AlphaLevel => {}
             };
         }
+        res.format = res.format.v4plus();
         Ok(res)
     }
 }
 
+// ssa counts 1-3 along the bottom, adds 4 for the top and 8 for the middle.
+fn ssa_alignment(s: &str) -> Result<Alignment, AssParseError> {
+    let n: u8 = s.trim().parse().or(Err(BadAlignment))?;
+    match n {
+        1..=3 => n,
+        5..=7 => n + 2,
+        9..=11 => n - 5,
+        _ => return Err(BadAlignment),
+    }
+    .try_into()
+    .or(Err(BadAlignment))
+}
+
 fn ass_bool_to_bool(s: &str) -> Result<bool, AssParseError> {
     Ok(match s {
         "-1" => true,
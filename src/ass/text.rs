@@ -0,0 +1,177 @@
+// helpers for the Text field of an event: splitting it into override blocks
+// and plain text, and picking the override tags apart.
+
+use super::common::{Alignment, ABGR};
+use std::convert::TryFrom;
+
+// tag names that are prefixes of other tag names have to come after them.
+const TAG_NAMES: &[&str] = &[
+    "xbord", "ybord", "xshad", "yshad", "alpha", "iclip", "clip", "move", "fscx", "fscy", "fade",
+    "blur", "bord", "shad", "fad", "fax", "fay", "frx", "fry", "frz", "fsp", "org", "pbo", "pos",
+    "1a", "2a", "3a", "4a", "1c", "2c", "3c", "4c", "an", "be", "fe", "fn", "fr", "fs", "kf", "ko",
+    "a", "b", "c", "i", "k", "K", "p", "q", "r", "s", "t", "u",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment<'a> {
+    // the inside of a {} block, without the braces.
+    Override(&'a str),
+    Text(&'a str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tag<'a> {
+    pub name: &'a str,
+    // everything after the name, parens included. may be empty.
+    pub arg: &'a str,
+}
+
+// an unclosed { is just text, same as the renderers treat it.
+pub fn segments(s: &str) -> Vec<Segment<'_>> {
+    let mut res = Vec::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        match rest
+            .find('{')
+            .and_then(|a| Some((a, a + rest[a..].find('}')?)))
+        {
+            Some((open, close)) => {
+                if open > 0 {
+                    res.push(Segment::Text(&rest[..open]));
+                }
+                res.push(Segment::Override(&rest[open + 1..close]));
+                rest = &rest[close + 1..];
+            }
+            None => {
+                res.push(Segment::Text(rest));
+                break;
+            }
+        }
+    }
+    res
+}
+
+// anything in a block that isn't a tag is a comment and gets skipped.
+pub fn tags(block: &str) -> Vec<Tag<'_>> {
+    let mut res = Vec::new();
+    let mut rest = block;
+    while let Some(n) = rest.find('\\') {
+        rest = &rest[n + 1..];
+        let name = match TAG_NAMES.iter().find(|x| rest.starts_with(*x)) {
+            Some(x) => *x,
+            None => continue,
+        };
+        let after = &rest[name.len()..];
+        let len = if after.trim_start().starts_with('(') {
+            // \t can nest other tags in its parens, so count them.
+            let mut depth = 0;
+            let mut end = after.len();
+            for (i, c) in after.char_indices() {
+                match c {
+                    '(' => depth += 1,
+                    ')' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            end = i + 1;
+                            break;
+                        }
+                    }
+                    _ => (),
+                }
+            }
+            end
+        } else {
+            after.find('\\').unwrap_or(after.len())
+        };
+        res.push(Tag {
+            name,
+            arg: after[..len].trim(),
+        });
+        rest = &after[len..];
+    }
+    res
+}
+
+// splits the arguments of a tag like \pos(1,2) into ["1", "2"].
+pub fn tag_args(arg: &str) -> Vec<&str> {
+    arg.trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .map(|x| x.trim())
+        .collect()
+}
+
+// the \an and \pos of a line, if it has them. first one wins, same as the
+// renderers.
+pub fn placement(s: &str) -> (Option<Alignment>, Option<(f64, f64)>) {
+    let (mut alignment, mut pos) = (None, None);
+    for segment in segments(s) {
+        if let Segment::Override(block) = segment {
+            for tag in tags(block) {
+                match tag.name {
+                    "an" if alignment.is_none() => {
+                        alignment = tag
+                            .arg
+                            .parse()
+                            .ok()
+                            .and_then(|x: u8| Alignment::try_from(x).ok())
+                    }
+                    "pos" if pos.is_none() => {
+                        if let [x, y] = tag_args(tag.arg)[..] {
+                            pos = x.parse().ok().zip(y.parse().ok());
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+    (alignment, pos)
+}
+
+// colours in tags are &HBBGGRR&, and the renderers don't insist on all six
+// digits or either of the &s.
+pub fn tag_colour(arg: &str) -> Option<ABGR> {
+    let hex = arg
+        .trim()
+        .trim_start_matches('&')
+        .trim_start_matches(['H', 'h'])
+        .trim_end_matches('&');
+    if hex.is_empty() || hex.len() > 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok().map(ABGR::from)
+}
+
+// alpha is the same but only ever two digits, &HAA&.
+pub fn tag_alpha(arg: &str) -> Option<u8> {
+    let hex = arg
+        .trim()
+        .trim_start_matches('&')
+        .trim_start_matches(['H', 'h'])
+        .trim_end_matches('&');
+    u8::from_str_radix(hex, 16).ok()
+}
+
+// \N is a hard break, \n a soft one (only honoured with WrapStyle 2, so a
+// space is closer to what people actually see), \h a non-breaking space.
+pub fn unescape(s: &str) -> String {
+    s.replace("\\N", "\n")
+        .replace("\\n", " ")
+        .replace("\\h", "\u{a0}")
+}
+
+pub fn strip_tags(s: &str) -> String {
+    segments(s)
+        .into_iter()
+        .filter_map(|x| match x {
+            Segment::Text(t) => Some(t),
+            Segment::Override(_) => None,
+        })
+        .collect()
+}
+
+pub fn plain_text(s: &str) -> String {
+    unescape(&strip_tags(s))
+}
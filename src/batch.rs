@@ -0,0 +1,155 @@
+// batch.rs
+// converting lots of files at once. inputs can be files, directories
+// (searched all the way down) or glob patterns, and the outputs go into one
+// directory laid out like wherever the inputs came from.
+//
+// output names come from a template, where {stem} is the input's name
+// without its extension, {name} is the whole name and {ext} is the output
+// format's extension.
+
+use std::{
+    error::Error,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+pub const DEFAULT_TEMPLATE: &str = "{stem}.{ext}";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    pub input: PathBuf,
+    // where the output goes, relative to the output directory.
+    pub relative: PathBuf,
+}
+
+// the part of a pattern before the first wildcard, which is what the
+// matches are laid out relative to.
+fn glob_root(pattern: &str) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in Path::new(pattern).components() {
+        let s = component.as_os_str().to_string_lossy();
+        if s.contains(['*', '?', '[']) {
+            break;
+        }
+        res.push(component);
+    }
+    res
+}
+
+fn walk(dir: &Path, keep: &dyn Fn(&Path) -> bool, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .map(|x| x.path())
+        .collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            walk(&path, keep, out)?;
+        } else if keep(&path) {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+// keep says which files found in directories are worth converting. files
+// named outright, or matched by a pattern, are always taken.
+pub fn collect(
+    inputs: &[String],
+    keep: &dyn Fn(&Path) -> bool,
+) -> Result<Vec<Job>, Box<dyn Error>> {
+    let mut res = Vec::new();
+    let mut add = |input: PathBuf, root: &Path| {
+        let relative = input.strip_prefix(root).unwrap_or(&input).to_path_buf();
+        // nothing from outside the output directory, or absolute.
+        let relative: PathBuf = relative
+            .components()
+            .filter(|x| matches!(x, Component::Normal(_)))
+            .collect();
+        if !res.iter().any(|x: &Job| x.input == input) {
+            res.push(Job { input, relative });
+        }
+    };
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            let mut found = Vec::new();
+            walk(path, keep, &mut found)?;
+            for x in found {
+                add(x, path);
+            }
+        } else if path.exists() {
+            add(
+                path.to_path_buf(),
+                path.parent().unwrap_or_else(|| Path::new("")),
+            );
+        } else {
+            let root = glob_root(input);
+            let mut any = false;
+            for x in glob::glob(input)? {
+                let x = x?;
+                if x.is_file() {
+                    add(x, &root);
+                    any = true;
+                }
+            }
+            if !any {
+                return Err(format!("no files match {}", input).into());
+            }
+        }
+    }
+    Ok(res)
+}
+
+pub fn output_path(job: &Job, out_dir: &Path, template: &str, ext: &str) -> PathBuf {
+    let name = job
+        .relative
+        .file_name()
+        .map_or("".into(), |x| x.to_string_lossy());
+    let stem = job
+        .relative
+        .file_stem()
+        .map_or("".into(), |x| x.to_string_lossy());
+    let file = template
+        .replace("{stem}", &stem)
+        .replace("{name}", &name)
+        .replace("{ext}", ext);
+    let dir = job.relative.parent().unwrap_or_else(|| Path::new(""));
+    out_dir.join(dir).join(file)
+}
+
+// runs f on every job over this many threads, and hands back the results in
+// job order.
+pub fn run<T, F>(jobs: &[Job], threads: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(&Job) -> T + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<T>>> = Mutex::new((0..jobs.len()).map(|_| None).collect());
+    thread::scope(|s| {
+        for _ in 0..threads.clamp(1, jobs.len().max(1)) {
+            s.spawn(|| loop {
+                let n = next.fetch_add(1, Ordering::Relaxed);
+                let job = match jobs.get(n) {
+                    Some(x) => x,
+                    None => break,
+                };
+                let res = f(job);
+                results.lock().unwrap()[n] = Some(res);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|x| x.expect("every job was run"))
+        .collect()
+}
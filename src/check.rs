@@ -0,0 +1,182 @@
+// check.rs
+// things that parse fine but are still probably mistakes.
+
+use serde::{Deserialize, Serialize};
+
+use crate::ass::{text, AssTrack};
+use crate::formats::Warning;
+
+// limits that depend on taste, so they're off unless a project sets them.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Thresholds {
+    // characters per second of plain text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_cps: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_line_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_lines: Option<usize>,
+    // in milliseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_duration: Option<u32>,
+}
+
+// one thing check found, with a name for it that scripts can match on and,
+// where there's an obvious one, what to do about it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    pub code: &'static str,
+    // index into the track's events or styles. neither is the header.
+    pub event: Option<usize>,
+    pub style: Option<usize>,
+    pub message: String,
+    pub fix: Option<String>,
+}
+impl From<Lint> for Warning {
+    fn from(x: Lint) -> Warning {
+        Warning {
+            event: x.event,
+            message: x.message,
+        }
+    }
+}
+
+pub fn check(track: &AssTrack) -> Vec<Warning> {
+    check_with(track, &Thresholds::default())
+}
+
+pub fn check_with(track: &AssTrack, limits: &Thresholds) -> Vec<Warning> {
+    lint(track, limits).into_iter().map(Into::into).collect()
+}
+
+pub fn lint(track: &AssTrack, limits: &Thresholds) -> Vec<Lint> {
+    let mut res = Vec::new();
+    if track.header.script_type.is_none() {
+        res.push(Lint {
+            code: "missing-script-type",
+            event: None,
+            style: None,
+            message: "no ScriptType in [Script Info]".to_owned(),
+            fix: Some("add \"ScriptType: v4.00+\" to [Script Info]".to_owned()),
+        });
+    }
+    let mut seen = Vec::new();
+    for (n, style) in track.styles.iter().enumerate() {
+        if seen.contains(&&style.name) {
+            res.push(Lint {
+                code: "duplicate-style",
+                event: None,
+                style: Some(n),
+                message: format!("style {:?} is defined more than once", style.name),
+                fix: Some("rename or remove one of them".to_owned()),
+            });
+        }
+        seen.push(&style.name);
+    }
+    for (n, event) in track.events.iter().enumerate() {
+        let mut warn = |code: &'static str, message: String, fix: Option<String>| {
+            res.push(Lint {
+                code,
+                event: Some(n),
+                style: None,
+                message,
+                fix,
+            })
+        };
+        if event.descriptor == "Comment" {
+            continue;
+        }
+        if event.end_time.as_millis() < event.start_time.as_millis() {
+            warn(
+                "end-before-start",
+                format!(
+                    "ends at {} before it starts at {}",
+                    event.end_time, event.start_time
+                ),
+                Some("swap the start and end times".to_owned()),
+            );
+        }
+        let style = event.style.as_deref().unwrap_or("Default");
+        if !track.styles.iter().any(|x| x.name == style) {
+            warn(
+                "undefined-style",
+                format!("uses style {:?}, which isn't defined", style),
+                Some(format!("add a style named {:?} or use another", style)),
+            );
+        }
+        let t = event.text.as_deref().unwrap_or("");
+        let opens = t.matches('{').count();
+        let closes = t.matches('}').count();
+        if opens != closes {
+            let fix = match opens > closes {
+                true => "close the override block with }",
+                false => "remove the stray }",
+            };
+            warn(
+                "unbalanced-braces",
+                "has unbalanced { } braces".to_owned(),
+                Some(fix.to_owned()),
+            );
+        }
+        let plain = text::plain_text(t);
+        if plain.trim().is_empty() && opens == 0 {
+            warn(
+                "empty-text",
+                "has no text".to_owned(),
+                Some("remove it, or make it a Comment".to_owned()),
+            );
+        }
+
+        let duration = event
+            .end_time
+            .as_millis()
+            .saturating_sub(event.start_time.as_millis());
+        if let Some(min) = limits.min_duration {
+            if duration < min {
+                warn(
+                    "short-duration",
+                    format!("is only on screen for {}ms", duration),
+                    Some(format!("make it at least {}ms long", min)),
+                );
+            }
+        }
+        if let Some(max) = limits.max_cps {
+            let chars = plain.chars().filter(|c| !c.is_whitespace()).count();
+            let cps = chars as f64 * 1000.0 / f64::from(duration.max(1));
+            if cps > max {
+                let needed = (chars as f64 * 1000.0 / max).ceil();
+                warn(
+                    "reading-speed",
+                    format!("reads at {:.1} characters per second", cps),
+                    Some(format!(
+                        "make it at least {}ms long, or shorten the text",
+                        needed
+                    )),
+                );
+            }
+        }
+        let lines: Vec<_> = plain.lines().collect();
+        if let Some(max) = limits.max_lines {
+            if lines.len() > max {
+                warn(
+                    "too-many-lines",
+                    format!("has {} lines", lines.len()),
+                    Some(format!("split it into events of at most {} lines", max)),
+                );
+            }
+        }
+        if let Some(max) = limits.max_line_length {
+            if let Some(longest) = lines.iter().map(|x| x.chars().count()).max() {
+                if longest > max {
+                    warn(
+                        "line-too-long",
+                        format!("has a line {} characters long", longest),
+                        Some(format!("break it with \\N before {} characters", max)),
+                    );
+                }
+            }
+        }
+    }
+    res
+}
@@ -0,0 +1,913 @@
+// cli.rs
+// the command line: a subcommand per job, with convert as the default so
+// `ass2ass in.ass out.ass` still does what it always did.
+
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    ffi::OsString,
+    fmt, fs,
+    io::{self, Cursor},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+
+use crate::ass::{AssParseError, AssTrack};
+use crate::batch;
+use crate::check::{check_with, lint};
+use crate::diagnostics::{self, Diagnostic, Kind};
+use crate::diff;
+use crate::edit::{self, MergeInput, MergeOptions, SplitBy, StyleClash};
+use crate::files;
+use crate::formats::Warning;
+use crate::project::{self, ProjectError, Settings};
+use crate::registry::{decode, read_all, Options, Registry, RegistryError};
+use crate::summary::summarize;
+use crate::threeway;
+use crate::watch;
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "ass2ass",
+    version,
+    about = "Converts, checks and edits subtitle scripts.",
+    arg_required_else_help = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// Format to read inputs as, instead of going by their extension
+    #[arg(long, global = true, value_name = "FORMAT")]
+    pub from: Option<String>,
+
+    /// Format to write outputs as, instead of going by their extension
+    #[arg(long, global = true, value_name = "FORMAT")]
+    pub to: Option<String>,
+
+    /// Frame rate for frame-based formats that don't say [default: 23.976]
+    #[arg(long, global = true, value_parser = parse_fps)]
+    pub fps: Option<f64>,
+
+    /// Don't print warnings
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Project settings to use instead of the nearest .ass2ass.toml
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<String>,
+
+    /// Ignore .ass2ass.toml files
+    #[arg(long, global = true, conflicts_with = "config")]
+    pub no_config: bool,
+
+    // the project file with the flags over it, worked out by run.
+    #[arg(skip)]
+    pub settings: Settings,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Convert a file to another format (the default command)
+    Convert { input: String, output: String },
+    /// Convert files, directories and glob patterns into one directory
+    Batch {
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        /// Where the outputs go, laid out like the inputs
+        #[arg(short, long, value_name = "DIR")]
+        out_dir: String,
+        /// Output file names, from {stem}, {name} and {ext}
+        #[arg(long, value_name = "TEMPLATE", default_value = batch::DEFAULT_TEMPLATE)]
+        name: String,
+        /// How many files to convert at once [default: one per CPU]
+        #[arg(short, long)]
+        jobs: Option<usize>,
+    },
+    /// Report problems in files, failing if there are any
+    ///
+    /// Exits with 1 if there are only warnings, 3 if a file couldn't be
+    /// parsed and 4 if a file couldn't be read.
+    #[command(arg_required_else_help = true)]
+    Check {
+        #[arg(required = true)]
+        files: Vec<String>,
+        /// How to print what's found
+        #[arg(long, value_enum, default_value_t = Report::Human)]
+        format: Report,
+    },
+    /// Rewrite a script in the standard layout, to OUTPUT or stdout
+    Fmt {
+        input: String,
+        #[arg(conflicts_with_all = ["in_place", "check"])]
+        output: Option<String>,
+        /// Overwrite INPUT with the result
+        #[arg(short, long, conflicts_with = "check")]
+        in_place: bool,
+        /// With --in-place, keep the original as INPUT + SUFFIX
+        #[arg(long, value_name = "SUFFIX", requires = "in_place")]
+        backup: Option<String>,
+        /// Write nothing, fail if INPUT isn't already formatted
+        #[arg(long)]
+        check: bool,
+    },
+    /// Show what changed between two scripts, line by line however they
+    /// were reordered or retimed
+    ///
+    /// Exits with 1 if there are differences.
+    Diff {
+        old: String,
+        new: String,
+        #[arg(long, value_enum, default_value_t = DiffOutput::Human)]
+        format: DiffOutput,
+    },
+    /// Summarize a script: header, styles, actors, timing, fonts and more
+    Info {
+        input: String,
+        #[arg(long, value_enum, default_value_t = Output::Human)]
+        format: Output,
+    },
+    /// Move every line earlier or later, by seconds, "250ms" or "0:00:01.50"
+    Shift {
+        input: String,
+        #[arg(allow_hyphen_values = true)]
+        offset: String,
+        output: String,
+    },
+    /// Put several files together into one script
+    ///
+    /// Everything is resampled to the first input's PlayResX/PlayResY, or
+    /// to --play-res.
+    Merge {
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        #[arg(short, long)]
+        output: String,
+        /// What to do with different styles of the same name: keep the
+        /// first, or put the input's file name in front of the later ones
+        #[arg(long, value_enum, default_value_t = Clash::Dedupe)]
+        styles: Clash,
+        /// Move an input's lines by this much, one per input in order
+        #[arg(long, allow_hyphen_values = true)]
+        offset: Vec<String>,
+        /// Resample everything to this instead, e.g. 1920x1080
+        #[arg(long, value_name = "WxH", conflicts_with = "no_resample")]
+        play_res: Option<String>,
+        /// Leave each input's sizes and positions as they are
+        #[arg(long)]
+        no_resample: bool,
+    },
+    /// Merge two edits of a script with the version they started from, for
+    /// git to call as a merge driver
+    ///
+    /// The result is written over OURS. Lines both sides changed differently
+    /// are kept twice between <<<<<<< ours, ======= and >>>>>>> theirs
+    /// Comment events, and it exits with 1 if there are any. Scripts with
+    /// lines that wouldn't survive being read and written again are merged
+    /// as text instead, with git merge-file's markers. To use it:
+    ///
+    ///   .gitattributes:  *.ass merge=ass2ass
+    ///   .git/config:     [merge "ass2ass"]
+    ///                        driver = ass2ass merge-driver %O %A %B
+    #[command(verbatim_doc_comment)]
+    MergeDriver {
+        base: String,
+        ours: String,
+        theirs: String,
+    },
+    /// Write a separate script for each style or actor
+    Split {
+        input: String,
+        #[arg(long, value_enum, default_value_t = SplitKey::Style)]
+        by: SplitKey,
+        /// Where the parts go [default: next to the input]
+        #[arg(short, long, value_name = "DIR")]
+        out_dir: Option<String>,
+    },
+    /// Check a script and convert it to OUTPUT again every time it's saved
+    Watch {
+        input: String,
+        output: Option<String>,
+        /// How long to wait for a save to finish, in milliseconds
+        #[arg(long, value_name = "MS", default_value_t = 200)]
+        debounce: u64,
+    },
+    /// Work with .ass2ass.toml project settings
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Print a shell completion script
+    Completions { shell: Shell },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Print the settings in effect for PATH [default: the current directory]
+    Show { path: Option<String> },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum DiffOutput {
+    Human,
+    Json,
+    Html,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+    Human,
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Clash {
+    Dedupe,
+    Namespace,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Report {
+    Human,
+    Json,
+    Sarif,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SplitKey {
+    Style,
+    Actor,
+}
+
+// a line by line merge into ours, with the usual markers, for scripts that
+// can't go through threeway. git is there whenever it runs a merge driver.
+// hands back the number of conflicts.
+fn merge_text(base: &str, ours: &str, theirs: &str) -> Result<i32, Box<dyn Error>> {
+    let status = std::process::Command::new("git")
+        .args(["merge-file", "-L", "ours", "-L", "base", "-L", "theirs"])
+        .args([ours, base, theirs])
+        .status()
+        .map_err(|e| format!("git merge-file: {}", e))?;
+    match status.code() {
+        Some(n @ 0..=127) => Ok(n),
+        _ => Err(format!("git merge-file failed: {}", status).into()),
+    }
+}
+
+// an error that wants its own exit code. with no message, whatever went
+// wrong has been printed already.
+#[derive(Debug)]
+pub struct Exit {
+    pub code: i32,
+    pub message: String,
+}
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+impl Error for Exit {}
+
+fn parse_fps(s: &str) -> Result<f64, String> {
+    s.parse()
+        .ok()
+        .filter(|x: &f64| x.is_finite() && *x > 0.0)
+        .ok_or_else(|| "must be a positive number".to_owned())
+}
+
+// anything that isn't a subcommand is the start of a convert.
+pub fn parse<I, T>(args: I) -> Cli
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+    match Cli::try_parse_from(&args) {
+        Ok(x) => x,
+        Err(e) if e.kind() == ErrorKind::InvalidSubcommand => {
+            let mut args = args;
+            args.insert(1, "convert".into());
+            Cli::try_parse_from(args).unwrap_or_else(|e| e.exit())
+        }
+        Err(e) => e.exit(),
+    }
+}
+
+impl Cli {
+    fn options(&self) -> Options {
+        self.settings.options()
+    }
+
+    // where to start looking for a project file: the (first) input, or
+    // here if there isn't one.
+    fn start(&self) -> &str {
+        let res = match &self.command {
+            Command::Convert { input, .. }
+            | Command::Fmt { input, .. }
+            | Command::Info { input, .. }
+            | Command::Shift { input, .. }
+            | Command::Split { input, .. }
+            | Command::Watch { input, .. } => input,
+            Command::Batch { inputs, .. } | Command::Merge { inputs, .. } => &inputs[0],
+            Command::Check { files, .. } => &files[0],
+            Command::Diff { old, .. } => old,
+            Command::MergeDriver { ours, .. } => ours,
+            Command::Config {
+                action: ConfigAction::Show { path },
+            } => path.as_deref().unwrap_or("."),
+            Command::Completions { .. } => ".",
+        };
+        match res {
+            files::STDIO => ".",
+            x => x,
+        }
+    }
+
+    fn project(&self) -> Result<(Option<PathBuf>, Settings), ProjectError> {
+        match (&self.config, self.no_config) {
+            (_, true) => Ok((None, Settings::default())),
+            (Some(x), false) => Ok((Some(x.into()), Settings::load(Path::new(x))?)),
+            (None, false) => Settings::discover(Path::new(self.start())),
+        }
+    }
+
+    fn flags(&self) -> Settings {
+        Settings {
+            from: self.from.clone(),
+            to: self.to.clone(),
+            fps: self.fps,
+            ..Default::default()
+        }
+    }
+
+    fn warn(&self, file: &str, warnings: &[Warning]) {
+        if self.quiet {
+            return;
+        }
+        for warning in warnings {
+            eprintln!("{}: {}", file, warning);
+        }
+    }
+
+    // the flags beat the extension, but the project settings only fill in
+    // for paths that don't have a known one. stdin and stdout have nothing
+    // to go by, so they're ass unless we're told otherwise.
+    fn format<'a>(
+        &'a self,
+        registry: &Registry,
+        flag: &'a Option<String>,
+        project: &'a Option<String>,
+        path: &str,
+    ) -> Option<&'a str> {
+        match registry.name_for(flag.as_deref(), project.as_deref(), path) {
+            None if path == files::STDIO => Some("ass"),
+            x => x,
+        }
+    }
+
+    fn input_format(&self, registry: &Registry, path: &str) -> Option<&str> {
+        self.format(registry, &self.from, &self.settings.from, path)
+    }
+
+    fn output_format(&self, registry: &Registry, path: &str) -> Option<&str> {
+        self.format(registry, &self.to, &self.settings.to, path)
+    }
+
+    fn read(&self, registry: &Registry, path: &str) -> Result<AssTrack<'static>, Box<dyn Error>> {
+        let reader = registry.reader(self.input_format(registry, path), path)?;
+        let mut source = files::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        reader
+            .read(&mut *source, &self.options())
+            .map_err(|e| format!("{}: {}", path, e).into())
+    }
+
+    // for when the bytes are needed for something else too.
+    fn parse(
+        &self,
+        registry: &Registry,
+        path: &str,
+        data: &[u8],
+    ) -> Result<AssTrack<'static>, Box<dyn Error>> {
+        let reader = registry.reader(self.input_format(registry, path), path)?;
+        reader
+            .read(&mut Cursor::new(data), &self.options())
+            .map_err(|e| format!("{}: {}", path, e).into())
+    }
+
+    fn write(
+        &self,
+        registry: &Registry,
+        track: &AssTrack,
+        path: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let out = self.render(registry, track, path)?;
+        files::write(path, &out).map_err(|e| format!("{}: {}", path, e).into())
+    }
+
+    // what write would put in path, without putting it there.
+    fn render(
+        &self,
+        registry: &Registry,
+        track: &AssTrack,
+        path: &str,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let writer = registry.writer(self.output_format(registry, path), path)?;
+        let mut track = track.clone();
+        self.settings.apply(&mut track);
+        let (out, warnings) = writer.write(&track, &self.options())?;
+        self.warn(path, &warnings);
+        Ok(out)
+    }
+
+    // everything wrong with one file, from not being there on down. ass gets
+    // parsed by hand so we know which line everything is on.
+    fn diagnose(&self, registry: &Registry, file: &str) -> Vec<Diagnostic> {
+        let io_error =
+            |e: io::Error| vec![Diagnostic::new(file, Kind::Io, "io-error", e.to_string())];
+        let mut source = match files::open(file) {
+            Ok(x) => x,
+            Err(e) => return io_error(e),
+        };
+        let format = match self.input_format(registry, file) {
+            Some(x) => registry.by_name(x),
+            None => registry.by_path(file),
+        };
+        let format = match format {
+            Ok(x) if x.reader.is_some() => x,
+            Ok(x) => {
+                let e = RegistryError::CannotRead(x.name.clone());
+                return vec![Diagnostic::new(
+                    file,
+                    Kind::Parse,
+                    "unknown-format",
+                    e.to_string(),
+                )];
+            }
+            Err(e) => {
+                return vec![Diagnostic::new(
+                    file,
+                    Kind::Parse,
+                    "unknown-format",
+                    e.to_string(),
+                )]
+            }
+        };
+        let limits = &self.settings.lint;
+        if format.name == "ass" {
+            let data = match read_all(&mut *source) {
+                Ok(x) => x,
+                Err(e) => return io_error(e),
+            };
+            let s = decode(&data);
+            return match AssTrack::parse_track_mapped(&s) {
+                Ok((track, map)) => {
+                    // unknown sections are somebody else's business (looking
+                    // at you, aegisub), not something wrong.
+                    let mut res: Vec<_> = map
+                        .dropped
+                        .iter()
+                        .filter(|(_, e)| *e != AssParseError::UnknownSection)
+                        .map(|(n, e)| Diagnostic {
+                            line: Some(*n),
+                            ..Diagnostic::new(file, Kind::Parse, "dropped-line", e.to_string())
+                        })
+                        .collect();
+                    res.extend(
+                        lint(&track, limits)
+                            .into_iter()
+                            .map(|x| Diagnostic::from_lint(file, x, Some(&map))),
+                    );
+                    res.sort_by_key(|x| x.line);
+                    res
+                }
+                Err((n, e)) => vec![Diagnostic {
+                    line: Some(n),
+                    ..Diagnostic::new(file, Kind::Parse, "parse-error", e.to_string())
+                }],
+            };
+        }
+        let reader = format.reader.as_deref().expect("checked above");
+        match reader.read(&mut *source, &self.options()) {
+            Ok(track) => lint(&track, limits)
+                .into_iter()
+                .map(|x| Diagnostic::from_lint(file, x, None))
+                .collect(),
+            Err(e) => vec![Diagnostic::new(
+                file,
+                Kind::Parse,
+                "parse-error",
+                e.to_string(),
+            )],
+        }
+    }
+
+    pub fn run(mut self, registry: &Registry) -> Result<(), Box<dyn Error>> {
+        let (project, settings) = self.project()?;
+        self.settings = settings.merge(self.flags());
+        match &self.command {
+            Command::Convert { input, output } => {
+                let track = self.read(registry, input)?;
+                self.write(registry, &track, output)
+            }
+            Command::Batch {
+                inputs,
+                out_dir,
+                name,
+                jobs,
+            } => {
+                // a --from says what everything is. the project's from is only
+                // for files named outright with an extension we don't know.
+                let keep = |path: &Path| {
+                    self.from.is_some()
+                        || registry
+                            .by_path(&path.to_string_lossy())
+                            .is_ok_and(|x| x.reader.is_some())
+                };
+                let jobs_list = batch::collect(inputs, &keep)?;
+                let ext = match &self.settings.to {
+                    Some(x) => {
+                        let format = registry.by_name(x)?;
+                        format.extensions.first().unwrap_or(&format.name).clone()
+                    }
+                    None => "ass".to_owned(),
+                };
+                // two inputs with the same name from different places would
+                // quietly overwrite each other.
+                let mut seen = HashMap::new();
+                for job in &jobs_list {
+                    let output = batch::output_path(job, Path::new(out_dir), name, &ext);
+                    if let Some(other) = seen.insert(output.clone(), &job.input) {
+                        return Err(format!(
+                            "{} and {} would both be written to {}",
+                            other.display(),
+                            job.input.display(),
+                            output.display()
+                        )
+                        .into());
+                    }
+                }
+                let threads = jobs
+                    .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |x| x.get()));
+                let results = batch::run(&jobs_list, threads, |job| {
+                    let output = batch::output_path(job, Path::new(out_dir), name, &ext);
+                    let output = output.to_string_lossy().into_owned();
+                    let res = self
+                        .read(registry, &job.input.to_string_lossy())
+                        .and_then(|track| {
+                            if let Some(dir) = Path::new(&output).parent() {
+                                fs::create_dir_all(dir)?;
+                            }
+                            self.write(registry, &track, &output)
+                        });
+                    res.map(|_| output).map_err(|e| e.to_string())
+                });
+                let mut failed = 0;
+                for (job, res) in jobs_list.iter().zip(&results) {
+                    match res {
+                        Ok(output) => println!("ok      {} -> {}", job.input.display(), output),
+                        Err(e) => {
+                            failed += 1;
+                            // read errors already say which file.
+                            println!("failed  {}", e);
+                        }
+                    }
+                }
+                println!("{} converted, {} failed", results.len() - failed, failed);
+                match failed {
+                    0 => Ok(()),
+                    n => Err(format!("{} of {} file(s) failed", n, results.len()).into()),
+                }
+            }
+            Command::Check { files, format } => {
+                let diagnostics: Vec<_> = files
+                    .iter()
+                    .flat_map(|x| self.diagnose(registry, x))
+                    .collect();
+                // the point of check is the warnings, quiet or not.
+                let out = match format {
+                    Report::Human => diagnostics.iter().map(|x| x.human() + "\n").collect(),
+                    Report::Json => diagnostics::to_json(&diagnostics) + "\n",
+                    Report::Sarif => diagnostics::to_sarif(&diagnostics) + "\n",
+                };
+                files::write(files::STDIO, out.as_bytes())?;
+                let worst = match diagnostics.iter().map(|x| x.kind).max() {
+                    Some(x) => x,
+                    None => return Ok(()),
+                };
+                let mut failed: Vec<_> = diagnostics.iter().map(|x| &x.file).collect();
+                failed.dedup();
+                let message = match format {
+                    Report::Human => {
+                        format!("{} of {} file(s) have problems", failed.len(), files.len())
+                    }
+                    Report::Json | Report::Sarif => String::new(),
+                };
+                let code = match worst {
+                    Kind::Lint => 1,
+                    Kind::Parse => 3,
+                    Kind::Io => 4,
+                };
+                Err(Exit { code, message }.into())
+            }
+            Command::Fmt {
+                input,
+                output,
+                in_place,
+                backup,
+                check,
+            } => {
+                // fmt always writes ass, which over anything else would lose it.
+                if *check || *in_place {
+                    let format = match self.input_format(registry, input) {
+                        Some(x) => registry.by_name(x)?,
+                        None => registry.by_path(input)?,
+                    };
+                    if format.name != "ass" {
+                        let flag = if *check { "--check" } else { "--in-place" };
+                        return Err(format!(
+                            "{}: fmt {} only works on ass scripts, not {}",
+                            input, flag, format.name
+                        )
+                        .into());
+                    }
+                }
+                let original = files::read(input)?;
+                let mut track = self.parse(registry, input, &original)?;
+                self.settings.apply(&mut track);
+                let formatted = format!("{}\n", track);
+                if *check {
+                    return match original == formatted.as_bytes() {
+                        true => Ok(()),
+                        false => Err(format!("{} would be reformatted", input).into()),
+                    };
+                }
+                match (output, *in_place) {
+                    (_, true) if input == files::STDIO => {
+                        files::write(input, formatted.as_bytes())?
+                    }
+                    (_, true) => files::replace(input, formatted.as_bytes(), backup.as_deref())?,
+                    (Some(x), false) => files::write(x, formatted.as_bytes())?,
+                    (None, false) => files::write(files::STDIO, formatted.as_bytes())?,
+                }
+                Ok(())
+            }
+            Command::Diff { old, new, format } => {
+                let (a, b) = (self.read(registry, old)?, self.read(registry, new)?);
+                let changes = diff::diff(&a, &b);
+                let out = match format {
+                    DiffOutput::Human => changes.to_string(),
+                    DiffOutput::Json => {
+                        serde_json::to_string_pretty(&changes.to_json()).unwrap_or_default() + "\n"
+                    }
+                    DiffOutput::Html => changes.to_html(old, new),
+                };
+                files::write(files::STDIO, out.as_bytes())?;
+                match changes.is_empty() {
+                    true => Ok(()),
+                    false => Err(Exit {
+                        code: 1,
+                        message: String::new(),
+                    }
+                    .into()),
+                }
+            }
+            Command::Info { input, format } => {
+                let data = files::read(input)?;
+                // ass gets parsed by hand for the sections we skipped.
+                let summary = match self.input_format(registry, input) {
+                    Some(x) => registry.by_name(x)?,
+                    None => registry.by_path(input)?,
+                };
+                let summary = match summary.name == "ass" {
+                    true => {
+                        let s = decode(&data);
+                        let (track, map) = AssTrack::parse_track_mapped(&s)
+                            .map_err(|(n, e)| format!("{}:{}: {}", input, n, e))?;
+                        summarize(&track, Some(&map))
+                    }
+                    false => summarize(&self.parse(registry, input, &data)?, None),
+                };
+                let out = match format {
+                    Output::Human => summary.to_string(),
+                    Output::Json => {
+                        serde_json::to_string_pretty(&summary.to_json()).unwrap_or_default() + "\n"
+                    }
+                };
+                files::write(files::STDIO, out.as_bytes())?;
+                Ok(())
+            }
+            Command::Shift {
+                input,
+                offset,
+                output,
+            } => {
+                let ms = edit::parse_offset(offset)
+                    .ok_or_else(|| format!("not a time offset: {}", offset))?;
+                let mut track = self.read(registry, input)?;
+                edit::shift(&mut track, ms);
+                self.write(registry, &track, output)
+            }
+            Command::Merge {
+                inputs,
+                output,
+                styles,
+                offset,
+                play_res,
+                no_resample,
+            } => {
+                if offset.len() > inputs.len() {
+                    return Err(
+                        format!("{} offsets for {} inputs", offset.len(), inputs.len()).into(),
+                    );
+                }
+                let play_res = match play_res {
+                    Some(x) => Some(
+                        x.split_once('x')
+                            .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                            .filter(|&(w, h): &(u32, u32)| w > 0 && h > 0)
+                            .ok_or_else(|| format!("not a resolution: {}", x))?,
+                    ),
+                    None => None,
+                };
+                let mut merged = Vec::new();
+                for (n, path) in inputs.iter().enumerate() {
+                    let ms = match offset.get(n) {
+                        Some(x) => edit::parse_offset(x)
+                            .ok_or_else(|| format!("not a time offset: {}", x))?,
+                        None => 0,
+                    };
+                    let name = Path::new(path)
+                        .file_stem()
+                        .map_or_else(|| (n + 1).to_string(), |x| x.to_string_lossy().into());
+                    merged.push(MergeInput {
+                        track: self.read(registry, path)?,
+                        name,
+                        offset: ms,
+                    });
+                }
+                let options = MergeOptions {
+                    styles: match styles {
+                        Clash::Dedupe => StyleClash::Dedupe,
+                        Clash::Namespace => StyleClash::Namespace,
+                    },
+                    play_res,
+                    resample: !no_resample,
+                };
+                self.write(registry, &edit::merge_with(merged, &options), output)
+            }
+            Command::MergeDriver { base, ours, theirs } => {
+                // git's temporary files have no extension to go by, and
+                // anything configured as a driver for .ass gets .ass.
+                //
+                // whatever the parser doesn't keep (header lines it doesn't
+                // know, sections like [Aegisub Project Garbage]) would be gone
+                // from the merge, so a file that doesn't come back out the way
+                // it went in gets merged as text instead, the way git would
+                // have. line endings and a byte order mark don't count.
+                let read = |path: &str| -> Result<Option<AssTrack<'static>>, Box<dyn Error>> {
+                    let data = files::read(path)?;
+                    let s = decode(&data);
+                    let track = match AssTrack::parse_track(&s) {
+                        Ok(x) => x,
+                        Err(_) => return Ok(None),
+                    };
+                    let again = format!("{}\n", track);
+                    match again.trim_end() == s.replace("\r\n", "\n").trim_end() {
+                        true => Ok(Some(track.into_owned())),
+                        false => Ok(None),
+                    }
+                };
+                let (b, o, t) = match (read(base)?, read(ours)?, read(theirs)?) {
+                    (Some(b), Some(o), Some(t)) => (b, o, t),
+                    _ => {
+                        return match merge_text(base, ours, theirs)? {
+                            0 => Ok(()),
+                            n => Err(Exit {
+                                code: 1,
+                                message: format!("{} conflict(s), merged as text", n),
+                            }
+                            .into()),
+                        }
+                    }
+                };
+                let merged = threeway::merge(&b, &o, &t);
+                let out = format!("{}\n", merged.track);
+                files::replace(ours, out.as_bytes(), None)?;
+                match merged.conflicts {
+                    0 => Ok(()),
+                    n => Err(Exit {
+                        code: 1,
+                        message: format!("{} conflict(s), marked with Comment events", n),
+                    }
+                    .into()),
+                }
+            }
+            Command::Split { input, by, out_dir } => {
+                let track = self.read(registry, input)?;
+                let by = match by {
+                    SplitKey::Style => SplitBy::Style,
+                    SplitKey::Actor => SplitBy::Actor,
+                };
+                let input_path = Path::new(input);
+                let dir = match out_dir {
+                    Some(x) => Path::new(x),
+                    None => input_path.parent().unwrap_or_else(|| Path::new("")),
+                };
+                fs::create_dir_all(dir)?;
+                let stem = input_path
+                    .file_stem()
+                    .map_or("split".into(), |x| x.to_string_lossy());
+                let ext = self.settings.to.as_deref().map_or_else(
+                    || {
+                        input_path
+                            .extension()
+                            .map_or("ass".into(), |x| x.to_string_lossy())
+                    },
+                    Into::into,
+                );
+                // Sign/Top and Sign:Top both come out as Sign_Top, and the file
+                // system might not care about case either, so later ones get
+                // numbered rather than written over earlier ones.
+                let mut used = HashSet::new();
+                for (key, part) in edit::split(&track, by) {
+                    let safe = edit::file_safe(&key);
+                    let mut key = safe.to_string();
+                    let mut n = 1;
+                    while !used.insert(key.to_lowercase()) {
+                        n += 1;
+                        key = format!("{}-{}", safe, n);
+                    }
+                    let name = format!("{}.{}.{}", stem, key, ext);
+                    let path = dir.join(name).to_string_lossy().into_owned();
+                    self.write(registry, &part, &path)?;
+                    println!("{}", path);
+                }
+                Ok(())
+            }
+            Command::Watch {
+                input,
+                output,
+                debounce,
+            } => {
+                // every write would be another save to run on, forever.
+                if let Some(x) = output {
+                    let same = |a: &str, b: &str| match (fs::canonicalize(a), fs::canonicalize(b)) {
+                        (Ok(a), Ok(b)) => a == b,
+                        _ => a == b,
+                    };
+                    if same(input, x) {
+                        return Err(format!("{}: can't watch a file and write to it too", x).into());
+                    }
+                }
+                let mut runs = 0;
+                watch::watch(Path::new(input), Duration::from_millis(*debounce), || {
+                    runs += 1;
+                    println!("-- run {} ({})", runs, input);
+                    // a broken save shouldn't stop the watch, the next one
+                    // might fix it.
+                    let res = self.read(registry, input).and_then(|track| {
+                        for warning in check_with(&track, &self.settings.lint) {
+                            println!("{}: {}", input, warning);
+                        }
+                        // whatever is showing the output may read it at any
+                        // moment, so it never gets to see half a file.
+                        if let Some(x) = output {
+                            let out = self.render(registry, &track, x)?;
+                            match x.as_str() {
+                                files::STDIO => files::write(x, &out),
+                                _ => files::replace(x, &out, None),
+                            }
+                            .map_err(|e| format!("{}: {}", x, e))?;
+                            println!("wrote {}", x);
+                        }
+                        Ok(())
+                    });
+                    if let Err(e) = res {
+                        println!("{}", e);
+                    }
+                })
+            }
+            Command::Config {
+                action: ConfigAction::Show { .. },
+            } => {
+                match project {
+                    Some(x) => println!("# {}", x.display()),
+                    None => println!("# no {} found, defaults only", project::FILE_NAME),
+                }
+                let shown = Settings {
+                    fps: Some(self.options().fps),
+                    ..self.settings.clone()
+                };
+                print!("{}", shown.to_toml());
+                Ok(())
+            }
+            Command::Completions { shell } => {
+                clap_complete::generate(*shell, &mut Cli::command(), "ass2ass", &mut io::stdout());
+                Ok(())
+            }
+        }
+    }
+}
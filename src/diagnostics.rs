@@ -0,0 +1,134 @@
+// diagnostics.rs
+// what check found, in a shape CI can read: plain lines for people, a json
+// array, or SARIF for the code scanning tools that take it.
+
+use serde_json::{json, Value};
+
+use crate::ass::SourceMap;
+use crate::check::Lint;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    Lint,
+    Parse,
+    Io,
+}
+
+impl Kind {
+    pub fn severity(self) -> &'static str {
+        match self {
+            Kind::Lint => "warning",
+            Kind::Parse | Kind::Io => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: Option<usize>,
+    pub kind: Kind,
+    pub code: &'static str,
+    pub message: String,
+    pub fix: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(file: &str, kind: Kind, code: &'static str, message: String) -> Self {
+        Diagnostic {
+            file: file.to_owned(),
+            line: None,
+            kind,
+            code,
+            message,
+            fix: None,
+        }
+    }
+
+    // lines only if we know where things came from, which is only for ass.
+    pub fn from_lint(file: &str, lint: Lint, map: Option<&SourceMap>) -> Self {
+        let line = map.and_then(|m| match (lint.event, lint.style) {
+            (Some(n), _) => m.events.get(n).copied(),
+            (None, Some(n)) => m.styles.get(n).copied(),
+            (None, None) => None,
+        });
+        let message = match lint.event {
+            Some(n) => format!("Event {}: {}", n, lint.message),
+            None => lint.message,
+        };
+        Diagnostic {
+            file: file.to_owned(),
+            line,
+            kind: Kind::Lint,
+            code: lint.code,
+            message,
+            fix: lint.fix,
+        }
+    }
+
+    pub fn human(&self) -> String {
+        match self.line {
+            Some(n) => format!("{}:{}: {}", self.file, n, self.message),
+            None => format!("{}: {}", self.file, self.message),
+        }
+    }
+}
+
+pub fn to_json(diagnostics: &[Diagnostic]) -> String {
+    let v: Vec<Value> = diagnostics
+        .iter()
+        .map(|x| {
+            json!({
+                "file": x.file,
+                "line": x.line,
+                "code": x.code,
+                "severity": x.kind.severity(),
+                "message": x.message,
+                "fix": x.fix,
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&v).unwrap_or_default()
+}
+
+// sarif's own fixes have to say exactly what to change in the file, which
+// ours don't, so they go in the result's properties.
+pub fn to_sarif(diagnostics: &[Diagnostic]) -> String {
+    let mut rules: Vec<&str> = diagnostics.iter().map(|x| x.code).collect();
+    rules.sort_unstable();
+    rules.dedup();
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|x| {
+            let mut location = json!({ "artifactLocation": { "uri": x.file } });
+            if let Some(n) = x.line {
+                location["region"] = json!({ "startLine": n });
+            }
+            let mut res = json!({
+                "ruleId": x.code,
+                "level": x.kind.severity(),
+                "message": { "text": x.message },
+                "locations": [{ "physicalLocation": location }],
+            });
+            if let Some(fix) = &x.fix {
+                res["properties"] = json!({ "fix": fix });
+            }
+            res
+        })
+        .collect();
+    let v = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "ass2ass",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules.iter().map(|x| json!({ "id": x })).collect::<Vec<_>>(),
+                }
+            },
+            "results": results,
+        }],
+    });
+    serde_json::to_string_pretty(&v).unwrap_or_default()
+}
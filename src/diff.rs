@@ -0,0 +1,568 @@
+// diff.rs
+// what changed between two versions of a script, by meaning rather than by
+// line: events are matched up across the tracks however they were reordered
+// or retimed, so a moved line reads as retimed rather than as one line gone
+// and another added.
+//
+// matching goes from surest to least sure. identical events first, then
+// events with the same text, then events close enough in time whose text is
+// similar enough. whatever's left was added or removed.
+
+use std::{collections::HashMap, fmt};
+
+use serde_json::{json, Value};
+
+use crate::ass::{event::Event, text, AssTrack};
+use crate::formats::escape_html;
+
+// how far apart two events can start and still be the same line reworded.
+const MAX_DRIFT: u32 = 5_000;
+// share of words two texts need in common to count as the same line.
+const MIN_SIMILARITY: f64 = 0.5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub name: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StyleChange {
+    Added(String),
+    Removed(String),
+    Changed(String, Vec<FieldChange>),
+}
+
+// indices are into the old track's events for removed, the new one's for
+// added.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventChange {
+    Added(usize),
+    Removed(usize),
+    Changed {
+        old: usize,
+        new: usize,
+        retimed: bool,
+        retexted: bool,
+        // anything else, like the style or actor.
+        fields: Vec<FieldChange>,
+    },
+}
+
+pub struct Diff<'a, 'b> {
+    pub old: &'a AssTrack<'a>,
+    pub new: &'b AssTrack<'b>,
+    pub header: Vec<FieldChange>,
+    pub styles: Vec<StyleChange>,
+    // in the order they happen.
+    pub events: Vec<EventChange>,
+}
+
+impl Diff<'_, '_> {
+    pub fn is_empty(&self) -> bool {
+        self.header.is_empty() && self.styles.is_empty() && self.events.is_empty()
+    }
+}
+
+fn field_changes<K: Into<String> + Clone + PartialEq>(
+    old: &[(K, String)],
+    new: &[(K, String)],
+) -> Vec<FieldChange> {
+    let find = |fields: &[(K, String)], name: &K| {
+        fields
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.clone())
+    };
+    let mut names: Vec<K> = old.iter().map(|(k, _)| k.clone()).collect();
+    for (k, _) in new {
+        if !names.contains(k) {
+            names.push(k.clone());
+        }
+    }
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let (old, new) = (find(old, &name), find(new, &name));
+            match old == new {
+                true => None,
+                false => Some(FieldChange {
+                    name: name.into(),
+                    old,
+                    new,
+                }),
+            }
+        })
+        .collect()
+}
+
+// the fields that aren't timing or text.
+fn event_fields(event: &Event) -> Vec<(&'static str, String)> {
+    let opt = |x: &Option<std::borrow::Cow<str>>| x.as_deref().unwrap_or("").to_owned();
+    vec![
+        ("Kind", event.descriptor.to_string()),
+        ("Layer", event.layer.to_string()),
+        ("Style", opt(&event.style)),
+        ("Name", opt(&event.actor)),
+        ("MarginL", event.margin_l.to_string()),
+        ("MarginR", event.margin_r.to_string()),
+        ("MarginV", event.margin_v.to_string()),
+        ("Effect", opt(&event.effect)),
+    ]
+}
+
+fn text_of<'e>(event: &'e Event) -> &'e str {
+    event.text.as_deref().unwrap_or("")
+}
+
+// dice coefficient over the words. signs and drawings have no plain text,
+// so those get compared with their tags in.
+fn similarity(a: &str, b: &str) -> f64 {
+    let words = |s: &str| -> Vec<String> {
+        let plain = text::plain_text(s);
+        let s = match plain.trim().is_empty() {
+            true => s.to_owned(),
+            false => plain,
+        };
+        s.split_whitespace().map(str::to_lowercase).collect()
+    };
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    // longest common subsequence, a row at a time.
+    let mut row = vec![0usize; b.len() + 1];
+    for x in &a {
+        let mut diagonal = 0;
+        for (j, y) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = match x == y {
+                true => diagonal + 1,
+                false => above.max(row[j]),
+            };
+            diagonal = above;
+        }
+    }
+    2.0 * row[b.len()] as f64 / (a.len() + b.len()) as f64
+}
+
+fn times(event: &Event) -> (u32, u32) {
+    (event.start_time.as_millis(), event.end_time.as_millis())
+}
+
+// which event in old became which in new, unchanged ones included.
+pub fn pair_events(old: &[Event], new: &[Event]) -> Vec<(usize, usize)> {
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+    let mut old_left: Vec<bool> = vec![true; old.len()];
+    let mut new_left: Vec<bool> = vec![true; new.len()];
+
+    // same everything, then the same text, then the same slot on the
+    // timeline with the text rewritten, each taken in order so repeats pair
+    // up first with first.
+    let exact = |e: &Event| e.to_string();
+    let same_text = |e: &Event| (e.descriptor.to_string(), text_of(e).to_owned());
+    let same_slot = |e: &Event| {
+        (
+            e.descriptor.to_string(),
+            e.layer,
+            times(e),
+            e.style.as_deref().map(str::to_owned),
+        )
+    };
+    macro_rules! pair_by {
+        ($key:expr) => {
+            let mut waiting: HashMap<_, Vec<usize>> = HashMap::new();
+            for (n, e) in new.iter().enumerate().rev() {
+                if new_left[n] {
+                    waiting.entry($key(e)).or_default().push(n);
+                }
+            }
+            for (n, e) in old.iter().enumerate() {
+                if !old_left[n] {
+                    continue;
+                }
+                if let Some(m) = waiting.get_mut(&$key(e)).and_then(|x| x.pop()) {
+                    pairs.push((n, m));
+                    old_left[n] = false;
+                    new_left[m] = false;
+                }
+            }
+        };
+    }
+    pair_by!(exact);
+    pair_by!(same_text);
+    pair_by!(same_slot);
+
+    // the rest, best match first.
+    let mut candidates = Vec::new();
+    for (n, a) in old.iter().enumerate().filter(|(n, _)| old_left[*n]) {
+        let (a_start, a_end) = times(a);
+        for (m, b) in new.iter().enumerate().filter(|(m, _)| new_left[*m]) {
+            let (b_start, b_end) = times(b);
+            let overlap = a_start < b_end && b_start < a_end;
+            let drift = a_start.abs_diff(b_start);
+            if !overlap && drift > MAX_DRIFT {
+                continue;
+            }
+            let score = similarity(text_of(a), text_of(b));
+            if score >= MIN_SIMILARITY {
+                candidates.push((score, drift, n, m));
+            }
+        }
+    }
+    candidates.sort_by(|x, y| y.0.total_cmp(&x.0).then(x.1.cmp(&y.1)));
+    for (_, _, n, m) in candidates {
+        if old_left[n] && new_left[m] {
+            pairs.push((n, m));
+            old_left[n] = false;
+            new_left[m] = false;
+        }
+    }
+    pairs
+}
+
+pub fn diff<'a, 'b>(old: &'a AssTrack<'a>, new: &'b AssTrack<'b>) -> Diff<'a, 'b> {
+    let header = field_changes(&old.header.fields(), &new.header.fields());
+
+    let mut styles = Vec::new();
+    for style in &old.styles {
+        match new.styles.iter().find(|x| x.name == style.name) {
+            Some(x) => {
+                let changes = field_changes(&style.fields(), &x.fields());
+                if !changes.is_empty() {
+                    styles.push(StyleChange::Changed(style.name.to_string(), changes));
+                }
+            }
+            None => styles.push(StyleChange::Removed(style.name.to_string())),
+        }
+    }
+    for style in &new.styles {
+        if !old.styles.iter().any(|x| x.name == style.name) {
+            styles.push(StyleChange::Added(style.name.to_string()));
+        }
+    }
+
+    let pairs = pair_events(&old.events, &new.events);
+    let mut old_left: Vec<bool> = vec![true; old.events.len()];
+    let mut new_left: Vec<bool> = vec![true; new.events.len()];
+    for &(n, m) in &pairs {
+        old_left[n] = false;
+        new_left[m] = false;
+    }
+
+    // sorted by when they happen, in the new track if they're in it.
+    let mut events: Vec<(u32, EventChange)> = Vec::new();
+    for (n, m) in pairs {
+        let (a, b) = (&old.events[n], &new.events[m]);
+        let retimed = times(a) != times(b);
+        let retexted = text_of(a) != text_of(b);
+        let fields = field_changes(&event_fields(a), &event_fields(b));
+        if retimed || retexted || !fields.is_empty() {
+            let change = EventChange::Changed {
+                old: n,
+                new: m,
+                retimed,
+                retexted,
+                fields,
+            };
+            events.push((times(b).0, change));
+        }
+    }
+    for (n, left) in old_left.into_iter().enumerate() {
+        if left {
+            events.push((times(&old.events[n]).0, EventChange::Removed(n)));
+        }
+    }
+    for (m, left) in new_left.into_iter().enumerate() {
+        if left {
+            events.push((times(&new.events[m]).0, EventChange::Added(m)));
+        }
+    }
+    events.sort_by_key(|(t, _)| *t);
+
+    Diff {
+        old,
+        new,
+        header,
+        styles,
+        events: events.into_iter().map(|(_, x)| x).collect(),
+    }
+}
+
+//------------------------------------------------------------------------------
+// output
+//------------------------------------------------------------------------------
+
+fn span(event: &Event) -> String {
+    format!("{}-{}", event.start_time, event.end_time)
+}
+
+fn show(x: &Option<String>) -> &str {
+    x.as_deref().unwrap_or("(unset)")
+}
+
+impl fmt::Display for Diff<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for x in &self.header {
+            writeln!(
+                f,
+                "~ header {}: {} -> {}",
+                x.name,
+                show(&x.old),
+                show(&x.new)
+            )?;
+        }
+        for x in &self.styles {
+            match x {
+                StyleChange::Added(name) => writeln!(f, "+ style {}", name)?,
+                StyleChange::Removed(name) => writeln!(f, "- style {}", name)?,
+                StyleChange::Changed(name, fields) => {
+                    for x in fields {
+                        writeln!(
+                            f,
+                            "~ style {} {}: {} -> {}",
+                            name,
+                            x.name,
+                            show(&x.old),
+                            show(&x.new)
+                        )?;
+                    }
+                }
+            }
+        }
+        for x in &self.events {
+            match x {
+                EventChange::Added(m) => {
+                    let e = &self.new.events[*m];
+                    writeln!(f, "+ {} {}", span(e), text_of(e))?;
+                }
+                EventChange::Removed(n) => {
+                    let e = &self.old.events[*n];
+                    writeln!(f, "- {} {}", span(e), text_of(e))?;
+                }
+                EventChange::Changed {
+                    old,
+                    new,
+                    retimed,
+                    retexted,
+                    fields,
+                } => {
+                    let (a, b) = (&self.old.events[*old], &self.new.events[*new]);
+                    match retimed {
+                        true => write!(f, "~ {} -> {}", span(a), span(b))?,
+                        false => write!(f, "~ {}", span(b))?,
+                    }
+                    match retexted {
+                        true => writeln!(f, " {}\n    -> {}", text_of(a), text_of(b))?,
+                        false => writeln!(f, " {}", text_of(b))?,
+                    }
+                    for x in fields {
+                        writeln!(f, "    {}: {} -> {}", x.name, show(&x.old), show(&x.new))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn fields_json(fields: &[FieldChange]) -> Vec<Value> {
+    fields
+        .iter()
+        .map(|x| json!({ "field": x.name, "old": x.old, "new": x.new }))
+        .collect()
+}
+
+fn event_json(event: &Event) -> Value {
+    json!({
+        "start": event.start_time.to_string(),
+        "end": event.end_time.to_string(),
+        "style": event.style,
+        "actor": event.actor,
+        "text": text_of(event),
+    })
+}
+
+impl Diff<'_, '_> {
+    pub fn to_json(&self) -> Value {
+        let styles: Vec<Value> = self
+            .styles
+            .iter()
+            .map(|x| match x {
+                StyleChange::Added(name) => json!({ "change": "added", "name": name }),
+                StyleChange::Removed(name) => json!({ "change": "removed", "name": name }),
+                StyleChange::Changed(name, fields) => json!({
+                    "change": "changed",
+                    "name": name,
+                    "fields": fields_json(fields),
+                }),
+            })
+            .collect();
+        let events: Vec<Value> = self
+            .events
+            .iter()
+            .map(|x| match x {
+                EventChange::Added(m) => json!({
+                    "change": "added",
+                    "new": { "index": m, "event": event_json(&self.new.events[*m]) },
+                }),
+                EventChange::Removed(n) => json!({
+                    "change": "removed",
+                    "old": { "index": n, "event": event_json(&self.old.events[*n]) },
+                }),
+                EventChange::Changed {
+                    old,
+                    new,
+                    retimed,
+                    retexted,
+                    fields,
+                } => json!({
+                    "change": "changed",
+                    "retimed": retimed,
+                    "retexted": retexted,
+                    "old": { "index": old, "event": event_json(&self.old.events[*old]) },
+                    "new": { "index": new, "event": event_json(&self.new.events[*new]) },
+                    "fields": fields_json(fields),
+                }),
+            })
+            .collect();
+        json!({
+            "header": fields_json(&self.header),
+            "styles": styles,
+            "events": events,
+        })
+    }
+
+    // a page that stands on its own, for attaching to a review.
+    pub fn to_html(&self, old_name: &str, new_name: &str) -> String {
+        let mut rows = Vec::new();
+        let mut row = |class: &str, what: String, old: String, new: String| {
+            rows.push(format!(
+                "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td></tr>",
+                class, what, old, new
+            ))
+        };
+        let event_cell = |e: &Event| {
+            format!(
+                "<span class=\"time\">{}</span> {}",
+                span(e),
+                escape_html(text_of(e))
+            )
+        };
+        let field_rows = |fields: &[FieldChange]| -> String {
+            fields
+                .iter()
+                .map(|x| {
+                    format!(
+                        "<br><span class=\"field\">{}: {} &rarr; {}</span>",
+                        escape_html(&x.name),
+                        escape_html(show(&x.old)),
+                        escape_html(show(&x.new))
+                    )
+                })
+                .collect()
+        };
+        for x in &self.header {
+            row(
+                "changed",
+                format!("header {}", escape_html(&x.name)),
+                escape_html(show(&x.old)),
+                escape_html(show(&x.new)),
+            );
+        }
+        for x in &self.styles {
+            match x {
+                StyleChange::Added(name) => row(
+                    "added",
+                    format!("style {}", escape_html(name)),
+                    String::new(),
+                    "added".to_owned(),
+                ),
+                StyleChange::Removed(name) => row(
+                    "removed",
+                    format!("style {}", escape_html(name)),
+                    "removed".to_owned(),
+                    String::new(),
+                ),
+                StyleChange::Changed(name, fields) => {
+                    for x in fields {
+                        row(
+                            "changed",
+                            format!("style {} {}", escape_html(name), escape_html(&x.name)),
+                            escape_html(show(&x.old)),
+                            escape_html(show(&x.new)),
+                        );
+                    }
+                }
+            }
+        }
+        for x in &self.events {
+            match x {
+                EventChange::Added(m) => row(
+                    "added",
+                    "added".to_owned(),
+                    String::new(),
+                    event_cell(&self.new.events[*m]),
+                ),
+                EventChange::Removed(n) => row(
+                    "removed",
+                    "removed".to_owned(),
+                    event_cell(&self.old.events[*n]),
+                    String::new(),
+                ),
+                EventChange::Changed {
+                    old,
+                    new,
+                    retimed,
+                    retexted,
+                    fields,
+                } => {
+                    let what: Vec<&str> = [(*retimed, "retimed"), (*retexted, "retexted")]
+                        .iter()
+                        .filter(|(x, _)| *x)
+                        .map(|(_, x)| *x)
+                        .chain(match fields.is_empty() {
+                            true => None,
+                            false => Some("changed"),
+                        })
+                        .collect();
+                    row(
+                        "changed",
+                        what.join(", "),
+                        event_cell(&self.old.events[*old]),
+                        event_cell(&self.new.events[*new]) + &field_rows(fields),
+                    );
+                }
+            }
+        }
+        format!(
+            "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{old} &rarr; {new}</title>
+<style>
+body {{ font-family: sans-serif; }}
+table {{ border-collapse: collapse; width: 100%; }}
+td, th {{ border: 1px solid #ccc; padding: 0.3em 0.6em; vertical-align: top; text-align: left; }}
+tr.added td {{ background: #e6ffec; }}
+tr.removed td {{ background: #ffebe9; }}
+tr.changed td {{ background: #fff8c5; }}
+.time, .field {{ color: #666; font-size: 0.9em; }}
+</style>
+</head>
+<body>
+<table>
+<tr><th></th><th>{old}</th><th>{new}</th></tr>
+{rows}
+</table>
+</body>
+</html>
+",
+            old = escape_html(old_name),
+            new = escape_html(new_name),
+            rows = rows.join("\n"),
+        )
+    }
+}
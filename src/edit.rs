@@ -0,0 +1,331 @@
+// edit.rs
+// whole-track edits the cli does: moving everything in time, putting
+// tracks together and taking them apart.
+
+use std::{borrow::Cow, collections::BTreeMap, convert::TryFrom};
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::ass::{
+    common::Timecode,
+    text::{self, Segment},
+    AssTrack,
+};
+use crate::resample::resample;
+
+// moves every event by ms, which can be negative. nothing goes below zero,
+// or past the latest time a script can hold.
+pub fn shift(track: &mut AssTrack, ms: i64) {
+    let moved = |t: Timecode| {
+        let ms = i64::from(t.as_millis()).saturating_add(ms).max(0);
+        Timecode::from(u32::try_from(ms).unwrap_or(u32::MAX))
+    };
+    for event in track.events.iter_mut() {
+        event.start_time = moved(event.start_time);
+        event.end_time = moved(event.end_time);
+    }
+}
+
+// "1.5", "-250ms", "+0:00:01.00" or "-1:02.5". plain numbers are seconds.
+pub fn parse_offset(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let (sign, s) = match s.strip_prefix('-') {
+        Some(x) => (-1, x),
+        None => (1, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let ms = if let Some(x) = s.strip_suffix("ms") {
+        x.trim().parse::<f64>().ok()?
+    } else {
+        let x = s.strip_suffix('s').unwrap_or(s);
+        // h:mm:ss.cc or m:ss.cc, each part counting 60 of the one after.
+        x.split(':').try_fold(0.0, |acc, part| {
+            Some(acc * 60.0 + part.parse::<f64>().ok()?)
+        })? * 1000.0
+    };
+    match ms.is_finite() && ms >= 0.0 {
+        true => Some(sign * ms.round() as i64),
+        false => None,
+    }
+}
+
+// what to do with a style that's in more than one track under the same name
+// but isn't the same style. ones that are the same are only kept once either
+// way.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StyleClash {
+    // the first track's wins.
+    #[default]
+    Dedupe,
+    // the later ones get the input's name in front, and their events follow.
+    Namespace,
+}
+
+pub struct MergeInput<'a> {
+    pub track: AssTrack<'a>,
+    // what goes in front of its styles when they're namespaced.
+    pub name: String,
+    // ms to move its events by before they go in.
+    pub offset: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeOptions {
+    pub styles: StyleClash,
+    // resample everything to this. None is the first track's.
+    pub play_res: Option<(u32, u32)>,
+    pub resample: bool,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            styles: StyleClash::default(),
+            play_res: None,
+            resample: true,
+        }
+    }
+}
+
+// \r to a style that's been renamed has to follow it.
+fn rename_reset(text: &str, old: &str, new: &str) -> String {
+    let mut res = String::new();
+    for segment in text::segments(text) {
+        match segment {
+            Segment::Override(block) => {
+                let mut rest = block;
+                res.push('{');
+                for tag in text::tags(block) {
+                    if tag.name != "r" || tag.arg != old {
+                        continue;
+                    }
+                    let at = tag.arg.as_ptr() as usize - rest.as_ptr() as usize;
+                    res.push_str(&rest[..at]);
+                    res.push_str(new);
+                    rest = &rest[at + tag.arg.len()..];
+                }
+                res.push_str(rest);
+                res.push('}');
+            }
+            Segment::Text(t) => res.push_str(t),
+        }
+    }
+    res
+}
+
+fn rename_style(track: &mut AssTrack, old: &str, new: &str) {
+    for style in track.styles.iter_mut().filter(|x| x.name == old) {
+        style.name = Cow::Owned(new.to_owned());
+    }
+    for event in track.events.iter_mut() {
+        if event.style.as_deref() == Some(old) {
+            event.style = Some(Cow::Owned(new.to_owned()));
+        }
+        if let Some(t) = &event.text {
+            if t.contains("\\r") {
+                event.text = Some(Cow::Owned(rename_reset(t, old, new)));
+            }
+        }
+    }
+}
+
+// the first track's header, with the styles and events of the rest added,
+// everything on one canvas and each input moved by its offset.
+pub fn merge_with<'a>(inputs: Vec<MergeInput<'a>>, options: &MergeOptions) -> AssTrack<'a> {
+    let mut inputs = inputs.into_iter();
+    let mut res = match inputs.next() {
+        Some(mut x) => {
+            shift(&mut x.track, x.offset);
+            x.track
+        }
+        None => return AssTrack::default(),
+    };
+    let play_res = options.play_res.unwrap_or_else(|| res.header.play_res());
+    if options.resample {
+        resample(&mut res, play_res);
+    }
+    for MergeInput {
+        mut track,
+        name,
+        offset,
+    } in inputs
+    {
+        shift(&mut track, offset);
+        if options.resample {
+            resample(&mut track, play_res);
+        }
+        let mut renames = Vec::new();
+        for style in &track.styles {
+            let same = match res.styles.iter().find(|x| x.name == style.name) {
+                Some(x) => x.to_string() == style.to_string(),
+                None => continue,
+            };
+            if same || options.styles == StyleClash::Dedupe {
+                continue;
+            }
+            let mut new = format!("{}:{}", name, style.name);
+            let mut n = 1;
+            while res
+                .styles
+                .iter()
+                .chain(&track.styles)
+                .any(|x| x.name == new)
+            {
+                n += 1;
+                new = format!("{}:{}{}", name, style.name, n);
+            }
+            renames.push((style.name.to_string(), new));
+        }
+        for (old, new) in &renames {
+            rename_style(&mut track, old, new);
+        }
+        for style in track.styles {
+            if !res.styles.iter().any(|x| x.name == style.name) {
+                res.styles.push(style);
+            }
+        }
+        res.events.extend(track.events);
+        for font in track.fonts {
+            if !res.fonts.iter().any(|x| x.name() == font.name()) {
+                res.fonts.push(font);
+            }
+        }
+        for graphic in track.graphics {
+            if !res.graphics.iter().any(|x| x.name() == graphic.name()) {
+                res.graphics.push(graphic);
+            }
+        }
+    }
+    res.events
+        .sort_by_key(|x| (x.start_time.as_millis(), x.end_time.as_millis()));
+    res
+}
+
+// merge_with, as is and with the defaults.
+pub fn merge<'a>(tracks: Vec<AssTrack<'a>>) -> AssTrack<'a> {
+    let inputs = tracks
+        .into_iter()
+        .enumerate()
+        .map(|(n, track)| MergeInput {
+            track,
+            name: (n + 1).to_string(),
+            offset: 0,
+        })
+        .collect();
+    merge_with(inputs, &MergeOptions::default())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitBy {
+    Style,
+    Actor,
+}
+
+// one track per style or actor, each with only the styles it uses. events
+// without an actor go under "".
+pub fn split<'a>(track: &AssTrack<'a>, by: SplitBy) -> Vec<(String, AssTrack<'a>)> {
+    let mut groups: BTreeMap<String, Vec<_>> = BTreeMap::new();
+    for event in &track.events {
+        let key = match by {
+            SplitBy::Style => &event.style,
+            SplitBy::Actor => &event.actor,
+        };
+        groups
+            .entry(key.as_deref().unwrap_or("").to_owned())
+            .or_default()
+            .push(event.clone());
+    }
+    groups
+        .into_iter()
+        .map(|(key, events)| {
+            let styles = track
+                .styles
+                .iter()
+                .filter(|s| {
+                    events
+                        .iter()
+                        .any(|e| e.style.as_deref().unwrap_or("Default") == s.name)
+                })
+                .cloned()
+                .collect();
+            let part = AssTrack {
+                styles,
+                events,
+                ..track.clone()
+            };
+            (key, part)
+        })
+        .collect()
+}
+
+// what split's keys become in file names.
+pub fn file_safe(s: &str) -> Cow<'_, str> {
+    if s.is_empty() {
+        return Cow::Borrowed("_");
+    }
+    match s.contains(|c: char| !(c.is_alphanumeric() || " -_.".contains(c))) {
+        true => Cow::Owned(
+            s.chars()
+                .map(|c| match c.is_alphanumeric() || " -_.".contains(c) {
+                    true => c,
+                    false => '_',
+                })
+                .collect(),
+        ),
+        false => Cow::Borrowed(s),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnicodeForm {
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+// house rules for tidying a script up on its way out. all off by default.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Normalize {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unicode: Option<UnicodeForm>,
+    // whitespace at either end of the text, and around \N.
+    pub trim: bool,
+    pub sort: bool,
+    pub drop_comments: bool,
+}
+
+pub fn normalize(track: &mut AssTrack, rules: &Normalize) {
+    if rules.drop_comments {
+        track.events.retain(|x| x.descriptor != "Comment");
+    }
+    for event in track.events.iter_mut() {
+        let text = match &event.text {
+            Some(x) => x,
+            None => continue,
+        };
+        let mut res = match rules.unicode {
+            Some(UnicodeForm::Nfc) => text.nfc().collect(),
+            Some(UnicodeForm::Nfd) => text.nfd().collect(),
+            Some(UnicodeForm::Nfkc) => text.nfkc().collect(),
+            Some(UnicodeForm::Nfkd) => text.nfkd().collect(),
+            None => text.to_string(),
+        };
+        if rules.trim {
+            res = res
+                .split("\\N")
+                .map(str::trim)
+                .collect::<Vec<_>>()
+                .join("\\N");
+        }
+        if res != *text {
+            event.text = Some(Cow::Owned(res));
+        }
+    }
+    if rules.sort {
+        // stable, so lines that start together keep their order.
+        track.events.sort_by_key(|x| x.start_time.as_millis());
+    }
+}
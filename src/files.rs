@@ -0,0 +1,80 @@
+// files.rs
+// reading and writing for the cli, where "-" is stdin or stdout, and
+// rewriting a file in place without leaving it half written if we die.
+
+use std::{
+    fs,
+    io::{self, BufReader, Cursor, Read, Write},
+    path::Path,
+};
+
+use crate::registry::Source;
+
+pub const STDIO: &str = "-";
+
+// for readers. stdin can't seek, so that gets read into memory first.
+pub fn open(path: &str) -> io::Result<Box<dyn Source>> {
+    match path {
+        STDIO => Ok(Box::new(Cursor::new(read(path)?))),
+        _ => Ok(Box::new(BufReader::new(fs::File::open(path)?))),
+    }
+}
+
+pub fn read(path: &str) -> io::Result<Vec<u8>> {
+    match path {
+        STDIO => {
+            let mut res = Vec::new();
+            io::stdin().lock().read_to_end(&mut res)?;
+            Ok(res)
+        }
+        _ => fs::read(path),
+    }
+}
+
+pub fn write(path: &str, data: &[u8]) -> io::Result<()> {
+    match path {
+        STDIO => {
+            let mut out = io::stdout().lock();
+            out.write_all(data)?;
+            out.flush()
+        }
+        _ => fs::write(path, data),
+    }
+}
+
+// writes next to the file and renames over it, so anything reading it sees
+// the old contents or the new, never part of either. with a backup suffix
+// the old file is kept as path + suffix.
+pub fn replace(path: &str, data: &[u8], backup: Option<&str>) -> io::Result<()> {
+    let path = Path::new(path);
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+    let mut tmp_name = name.to_owned();
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp = path.with_file_name(tmp_name);
+
+    let res = write_synced(&tmp, data, path).and_then(|_| {
+        if let Some(suffix) = backup {
+            let mut backup_name = name.to_owned();
+            backup_name.push(suffix);
+            fs::copy(path, path.with_file_name(backup_name))?;
+        }
+        fs::rename(&tmp, path)
+    });
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    res
+}
+
+fn write_synced(tmp: &Path, data: &[u8], original: &Path) -> io::Result<()> {
+    let mut file = fs::File::create(tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    // keep whatever permissions the original had.
+    if let Ok(meta) = fs::metadata(original) {
+        fs::set_permissions(tmp, meta.permissions())?;
+    }
+    Ok(())
+}
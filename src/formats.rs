@@ -0,0 +1,420 @@
+// formats.rs
+// readers and writers for subtitle formats that aren't ass.
+// everything goes through AssTrack, so each format only has to know how to
+// get to and from that.
+
+use std::{borrow::Cow, fmt};
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use thiserror::Error;
+
+use crate::ass::{
+    common::{Timecode, ABGR},
+    event::Event,
+    info::Header,
+    style::Style,
+    text::{self, Segment},
+    AssTrack,
+};
+
+//------------------------------------------------------------------------------
+// errors
+//------------------------------------------------------------------------------
+
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum FormatError {
+    #[error("Line does not match Audacity start<tab>end<tab>label format.")]
+    BadAudacityLabel,
+    #[error("No scene headings or dialogue found in Fountain screenplay.")]
+    BadFountainDocument,
+    #[error("Frame rate must be a positive number.")]
+    BadFrameRate,
+    #[error("Not a YouTube JSON3 timedtext document.")]
+    BadJson3Document,
+    #[error("Event start or duration is out of range.")]
+    BadJson3Event,
+    #[error("Line does not match LRC [mm:ss.xx]text format.")]
+    BadLrcLine,
+    #[error("Not a Matroska file, or one too damaged to read.")]
+    BadMatroskaFile,
+    #[error("Line does not match MicroDVD {{start}}{{end}}text format.")]
+    BadMicroDvdLine,
+    #[error("Line does not match MPL2 [start][end]text format.")]
+    BadMpl2Line,
+    #[error("Line is not a PO keyword, string or comment.")]
+    BadPoLine,
+    #[error("No <SYNC> blocks found in SAMI document.")]
+    BadSamiDocument,
+    #[error("Block does not start with an SBV start,end time line.")]
+    BadSbvBlock,
+    #[error("Block has no start --> end time line.")]
+    BadSrtBlock,
+    #[error("Not an EBU STL file, the GSI block is missing or invalid.")]
+    BadStlHeader,
+    #[error("Table has no header row with id and text columns.")]
+    BadTableHeader,
+    #[error("Line does not match TMPlayer hh:mm:ss:text format.")]
+    BadTmPlayerLine,
+    #[error("Cue has no valid start --> end time line.")]
+    BadVttCue,
+    #[error("Not a WebVTT document, it doesn't start with WEBVTT.")]
+    BadVttDocument,
+    #[error("Word has no text, or no start or end time in seconds.")]
+    BadWord,
+    #[error("Not a word timing JSON document.")]
+    BadWordsDocument,
+    #[error("Not an XLIFF document, or one too malformed to read.")]
+    BadXliffDocument,
+    #[error("No ASS or SSA track in Matroska file.")]
+    NoMatroskaTrack,
+}
+
+// writers for formats that can't hold everything report what they had to
+// change instead of failing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    // index into the track's events, None for things like the header.
+    pub event: Option<usize>,
+    pub message: String,
+}
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.event {
+            Some(n) => write!(f, "Event {}: {}", n, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+//------------------------------------------------------------------------------
+// shared helpers
+//------------------------------------------------------------------------------
+
+// lines without an end time stay up until the next line, but no longer than this.
+const OPEN_ENDED_DURATION: u32 = 5_000;
+
+// builds a track with a default style and the given events.
+fn track_from_events(events: Vec<Event<'static>>) -> AssTrack<'static> {
+    AssTrack {
+        header: Header {
+            script_type: Some(Cow::Borrowed("v4.00+")),
+            ..Header::default()
+        },
+        styles: vec![Style::default()],
+        events,
+        ..AssTrack::default()
+    }
+}
+
+fn dialogue(start: u32, end: u32, text: String) -> Event<'static> {
+    Event {
+        start_time: Timecode::from(start),
+        end_time: Timecode::from(end),
+        text: Some(Cow::Owned(text)),
+        ..Event::default()
+    }
+}
+
+// (start, end, text) where a missing end runs until the next start.
+fn close_open_ends(lines: Vec<(u32, Option<u32>, String)>) -> Vec<Event<'static>> {
+    let next_starts: Vec<Option<u32>> = lines
+        .iter()
+        .skip(1)
+        .map(|x| Some(x.0))
+        .chain(std::iter::once(None))
+        .collect();
+    lines
+        .into_iter()
+        .zip(next_starts)
+        .map(|((start, end, text), next)| {
+            let limit = start.saturating_add(OPEN_ENDED_DURATION);
+            let end = end.unwrap_or_else(|| next.map_or(limit, |n| n.clamp(start, limit)));
+            dialogue(start, end, text)
+        })
+        .collect()
+}
+
+// joins |-separated lines with \N. a leading / italicises that line only.
+fn join_pipe_lines(s: &str) -> String {
+    let mut out = String::new();
+    let mut italic = false;
+    for (n, line) in s.split('|').enumerate() {
+        if n > 0 {
+            out.push_str("\\N");
+        }
+        match line.strip_prefix('/') {
+            Some(rest) => {
+                if !italic {
+                    out.push_str("{\\i1}");
+                }
+                italic = true;
+                out.push_str(rest.trim_start());
+            }
+            None => {
+                if italic {
+                    out.push_str("{\\i0}");
+                }
+                italic = false;
+                out.push_str(line);
+            }
+        }
+    }
+    out
+}
+
+// lines in all of the line-based formats are "timing then text", and a bad
+// line shouldn't take the whole file with it.
+fn parse_lines<T, F>(s: &str, mut f: F) -> Vec<T>
+where
+    F: FnMut(&str) -> Result<Option<T>, FormatError>,
+{
+    let mut res = Vec::new();
+    for (line_n, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match f(line) {
+            Ok(Some(x)) => res.push(x),
+            Ok(None) => (),
+            Err(e) => eprintln!("Dropped line {}: {}", line_n, e),
+        }
+    }
+    res
+}
+
+pub(crate) fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub(crate) fn decode_entities(s: &str) -> String {
+    lazy_static! {
+        static ref ENTITY_RE: Regex = Regex::new(r"&(#[xX]?[0-9a-fA-F]+|[a-zA-Z]+);").unwrap();
+    }
+    ENTITY_RE
+        .replace_all(s, |caps: &Captures| {
+            let e = &caps[1];
+            let c = match e.to_ascii_lowercase().as_str() {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                x if x.starts_with("#x") => u32::from_str_radix(&x[2..], 16)
+                    .ok()
+                    .and_then(std::char::from_u32),
+                x if x.starts_with('#') => x[1..].parse().ok().and_then(std::char::from_u32),
+                _ => None,
+            };
+            c.map_or_else(|| caps[0].to_owned(), |c| c.to_string())
+        })
+        .into_owned()
+}
+
+// #rrggbb, #rgb or one of the basic html colour names.
+fn css_colour(s: &str) -> Option<ABGR> {
+    let s = s.trim().trim_matches('"').to_ascii_lowercase();
+    let hex = match s.as_str() {
+        "black" => "000000",
+        "white" => "ffffff",
+        "red" => "ff0000",
+        "lime" => "00ff00",
+        "green" => "008000",
+        "blue" => "0000ff",
+        "yellow" => "ffff00",
+        "cyan" | "aqua" => "00ffff",
+        "magenta" | "fuchsia" => "ff00ff",
+        "gray" | "grey" => "808080",
+        "silver" => "c0c0c0",
+        "maroon" => "800000",
+        "navy" => "000080",
+        "olive" => "808000",
+        "purple" => "800080",
+        "teal" => "008080",
+        "orange" => "ffa500",
+        x => x.strip_prefix('#')?,
+    };
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| vec![c, c]).collect(),
+        6 => hex.to_owned(),
+        _ => return None,
+    };
+    let [_, r, g, b] = u32::from_str_radix(&hex, 16).ok()?.to_be_bytes();
+    Some(ABGR::from_rgb(r, g, b))
+}
+
+fn html_colour(c: ABGR) -> String {
+    let (r, g, b) = c.rgb();
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// the tags ass_to_html writes back to override tags, for sami and the
+// formats that borrowed its markup.
+fn html_to_ass(s: &str) -> String {
+    lazy_static! {
+        static ref TAG_RE: Regex = Regex::new(r"(?s)<(/?)([a-zA-Z]+)([^>]*)>").unwrap();
+        static ref WS_RE: Regex = Regex::new(r"\s+").unwrap();
+        static ref COLOUR_RE: Regex = Regex::new(r#"(?i)\bcolor\s*=\s*"?([#\w]+)"#).unwrap();
+        static ref FACE_RE: Regex = Regex::new(r#"(?i)\bface\s*=\s*"([^"]+)"|face=(\S+)"#).unwrap();
+    }
+    let s = WS_RE.replace_all(s, " ");
+    let mut out = String::new();
+    // what each open <font> has to reset when it closes.
+    let mut fonts = Vec::<String>::new();
+    let mut last = 0;
+    for caps in TAG_RE.captures_iter(&s) {
+        let tag = caps.get(0).unwrap();
+        out.push_str(&decode_entities(&s[last..tag.start()]));
+        last = tag.end();
+        let closing = !caps[1].is_empty();
+        let toggle = |name| format!("{{\\{}{}}}", name, if closing { 0 } else { 1 });
+        match caps[2].to_ascii_lowercase().as_str() {
+            "br" => out.push_str("\\N"),
+            "i" | "em" => out.push_str(&toggle("i")),
+            "b" | "strong" => out.push_str(&toggle("b")),
+            "u" => out.push_str(&toggle("u")),
+            "s" | "strike" => out.push_str(&toggle("s")),
+            "font" if closing => {
+                if let Some(reset) = fonts.pop().filter(|x| !x.is_empty()) {
+                    out.push_str(&format!("{{{}}}", reset));
+                }
+            }
+            "font" => {
+                let (mut tags, mut reset) = (String::new(), String::new());
+                if let Some(c) = COLOUR_RE.captures(&caps[3]).and_then(|x| css_colour(&x[1])) {
+                    tags.push_str(&format!("\\c{}", ass_colour(c)));
+                    reset.push_str("\\c");
+                }
+                if let Some(face) = FACE_RE
+                    .captures(&caps[3])
+                    .and_then(|x| x.get(1).or_else(|| x.get(2)))
+                {
+                    tags.push_str(&format!("\\fn{}", face.as_str()));
+                    reset.push_str("\\fn");
+                }
+                if !tags.is_empty() {
+                    out.push_str(&format!("{{{}}}", tags));
+                }
+                fonts.push(reset);
+            }
+            _ => (),
+        }
+    }
+    out.push_str(&decode_entities(&s[last..]));
+    out.replace(" \\N", "\\N")
+        .replace("\\N ", "\\N")
+        .trim_matches(' ')
+        .to_owned()
+}
+
+// the override tag form, which leaves out the alpha.
+fn ass_colour(c: ABGR) -> String {
+    let (r, g, b) = c.rgb();
+    format!("&H{:02X}{:02X}{:02X}&", b, g, r)
+}
+
+// <i>, <b>, <u>, <s> and <font color>, which is as much as most players
+// take. html escapes everything and breaks lines with <br>, srt takes the
+// text as it is.
+fn ass_to_html(s: &str, html: bool) -> String {
+    let mut out = String::new();
+    // (name, the whole opening tag), innermost last.
+    let mut open = Vec::<(&str, String)>::new();
+    // html has to close in the order it opened, so whatever was opened
+    // inside name gets closed with it and opened again after.
+    let close = |out: &mut String, open: &mut Vec<(&str, String)>, name: &str| {
+        let at = match open.iter().rposition(|x| x.0 == name) {
+            Some(x) => x,
+            None => return,
+        };
+        let inner = open.split_off(at + 1);
+        for x in inner.iter().rev() {
+            out.push_str(&format!("</{}>", x.0));
+        }
+        open.pop();
+        out.push_str(&format!("</{}>", name));
+        for x in inner {
+            out.push_str(&x.1);
+            open.push(x);
+        }
+    };
+    for segment in text::segments(s) {
+        match segment {
+            Segment::Text(t) => {
+                let t = text::unescape(t);
+                match html {
+                    true => out.push_str(
+                        &escape_html(&t)
+                            .replace('\n', "<br>")
+                            .replace('\u{a0}', "&nbsp;"),
+                    ),
+                    false => out.push_str(&t),
+                }
+            }
+            Segment::Override(block) => {
+                for tag in text::tags(block) {
+                    let name = match tag.name {
+                        "i" | "b" | "u" | "s" => tag.name,
+                        "c" | "1c" => {
+                            close(&mut out, &mut open, "font");
+                            if let Some(c) = text::tag_colour(tag.arg) {
+                                let tag = format!("<font color=\"{}\">", html_colour(c));
+                                out.push_str(&tag);
+                                open.push(("font", tag));
+                            }
+                            continue;
+                        }
+                        "r" => {
+                            while let Some(x) = open.pop() {
+                                out.push_str(&format!("</{}>", x.0));
+                            }
+                            continue;
+                        }
+                        _ => continue,
+                    };
+                    // an empty argument goes back to the style, which is off
+                    // as far as html is concerned.
+                    let on = !matches!(tag.arg, "" | "0");
+                    let is_open = open.iter().any(|x| x.0 == name);
+                    if on && !is_open {
+                        let tag = format!("<{}>", name);
+                        out.push_str(&tag);
+                        open.push((name, tag));
+                    } else if !on && is_open {
+                        close(&mut out, &mut open, name);
+                    }
+                }
+            }
+        }
+    }
+    while let Some(x) = open.pop() {
+        out.push_str(&format!("</{}>", x.0));
+    }
+    out
+}
+
+pub mod audacity;
+pub mod fountain;
+pub mod json3;
+pub mod lrc;
+pub mod matroska;
+pub mod microdvd;
+pub mod mpl2;
+mod placeholders;
+pub mod po;
+pub mod sami;
+pub mod sbv;
+pub mod scc;
+pub mod srt;
+pub mod stl;
+pub mod table;
+pub mod tmplayer;
+pub mod transcript;
+pub mod vtt;
+pub mod words;
+pub mod xliff;
@@ -0,0 +1,80 @@
+// audacity label tracks (File > Export > Labels): start, end and label
+// separated by tabs, times in seconds. a label with a frequency range is
+// followed by a line starting with a tab that holds the low and high
+// frequency, which we don't have any use for.
+//
+// a point label (start == end) lasts until the next label. the label text is
+// the event text as is, tags and all, so timing can go back and forth
+// without losing anything.
+
+use super::FormatError::{self, BadAudacityLabel};
+use super::{close_open_ends, track_from_events};
+use crate::ass::{event::Event, style::Style, AssTrack};
+use std::borrow::Cow;
+
+fn parse_seconds(s: &str) -> Option<u32> {
+    // some locales export with a decimal comma.
+    let secs = s.trim().replace(',', ".").parse::<f64>().ok()?;
+    match secs >= 0.0 {
+        true => Some((secs * 1000.0).round() as u32),
+        false => None,
+    }
+}
+
+// every event gets the named style, which is added to the track.
+pub fn parse_track(s: &str, style: &str) -> Result<AssTrack<'static>, FormatError> {
+    let mut labels = Vec::new();
+    for (line_n, line) in s.lines().enumerate() {
+        // spectral selection lines belong to the label before them.
+        if line.starts_with('\t') || line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = line.splitn(3, '\t');
+        let start = fields.next().and_then(parse_seconds);
+        let end = fields.next().and_then(parse_seconds);
+        match (start, end) {
+            (Some(start), Some(end)) => {
+                let text = fields
+                    .next()
+                    .unwrap_or("")
+                    .trim_end_matches('\r')
+                    .to_owned();
+                let end = Some(end).filter(|x| *x > start);
+                labels.push((start, end, text));
+            }
+            _ => eprintln!("Dropped line {}: {}", line_n, BadAudacityLabel),
+        }
+    }
+    labels.sort_by_key(|x| x.0);
+
+    let events = close_open_ends(labels)
+        .into_iter()
+        .map(|x| Event {
+            style: Some(Cow::Owned(style.to_owned())),
+            ..x
+        })
+        .collect();
+    let mut track = track_from_events(events);
+    track.styles = vec![Style {
+        name: Cow::Owned(style.to_owned()),
+        ..Style::default()
+    }];
+    Ok(track)
+}
+
+pub fn write_track(track: &AssTrack) -> String {
+    let seconds = |ms: u32| format!("{}.{:03}000", ms / 1000, ms % 1000);
+    track
+        .events
+        .iter()
+        .filter(|x| x.descriptor != "Comment")
+        .map(|x| {
+            format!(
+                "{}\t{}\t{}\n",
+                seconds(x.start_time.as_millis()),
+                seconds(x.end_time.as_millis()),
+                x.text.as_deref().unwrap_or("").replace('\t', " ")
+            )
+        })
+        .collect()
+}
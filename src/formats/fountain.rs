@@ -0,0 +1,201 @@
+// fountain screenplays, to give timers a skeleton to start from. each
+// dialogue block becomes one event with the character as its actor, scene
+// headings become Comment events, and everything else (action, transitions,
+// sections, notes) is dropped.
+//
+// parentheticals stay where they were in the line, as {(beat)} comments,
+// which renderers skip. *italic*, **bold** and _underline_ become tags.
+//
+// there's no timing in a screenplay, so lines are either all at zero or
+// spaced evenly, one every `spacing` ms, scene headings taking no time.
+
+use super::FormatError::{self, BadFountainDocument};
+use super::{dialogue, track_from_events};
+use crate::ass::{event::Event, AssTrack};
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+
+enum Block {
+    Scene(String),
+    // (character, dialogue lines). parentheticals are lines too.
+    Dialogue(String, Vec<String>),
+}
+
+// /* boneyard */ and [[notes]] can span lines, so they go first.
+fn strip_hidden(s: &str) -> String {
+    lazy_static! {
+        static ref HIDDEN_RE: Regex = Regex::new(r"(?s)/\*.*?\*/|\[\[.*?\]\]").unwrap();
+    }
+    HIDDEN_RE.replace_all(s, "").into_owned()
+}
+
+// Title: and friends at the very top, up to the first blank line.
+fn split_title_page(s: &str) -> (Option<String>, &str) {
+    lazy_static! {
+        static ref KEY_RE: Regex = Regex::new(r"^([A-Za-z][A-Za-z ]*):(.*)$").unwrap();
+    }
+    let first = s.lines().next().unwrap_or("");
+    if !KEY_RE.is_match(first.trim_start()) {
+        return (None, s);
+    }
+    let end = s.find("\n\n").map_or(s.len(), |x| x + 2);
+    let mut title = None;
+    let mut in_title = false;
+    for line in s[..end].lines() {
+        match KEY_RE.captures(line.trim_start()) {
+            Some(caps) if !line.starts_with([' ', '\t']) => {
+                in_title = caps[1].eq_ignore_ascii_case("title");
+                let value = caps[2].trim();
+                if in_title && !value.is_empty() {
+                    title = Some(value.replace(['*', '_'], ""));
+                }
+            }
+            // values can carry on in indented lines.
+            _ if in_title => {
+                let t = title.get_or_insert_with(String::new);
+                if !t.is_empty() {
+                    t.push(' ');
+                }
+                t.push_str(&line.trim().replace(['*', '_'], ""));
+            }
+            _ => (),
+        }
+    }
+    (title, &s[end..])
+}
+
+fn scene_heading(line: &str) -> Option<String> {
+    lazy_static! {
+        static ref SCENE_RE: Regex =
+            Regex::new(r"(?i)^(int|ext|est|int\./ext|int/ext|i/e)[. ]").unwrap();
+    }
+    match line.strip_prefix('.') {
+        Some(rest) if !rest.starts_with('.') => Some(rest.trim().to_owned()),
+        Some(_) => None,
+        None if SCENE_RE.is_match(line) => Some(line.trim().to_owned()),
+        None => None,
+    }
+}
+
+// JOHN (V.O.) ^ -> JOHN. @ forces a cue that isn't all caps.
+fn character(line: &str) -> Option<String> {
+    let line = line.trim();
+    let (name, forced) = match line.strip_prefix('@') {
+        Some(x) => (x, true),
+        None => (line, false),
+    };
+    let name = name.trim_end_matches('^').trim();
+    let name = name.split('(').next().unwrap_or("").trim();
+    let caps = name.chars().any(char::is_alphabetic) && !name.chars().any(char::is_lowercase);
+    // forced action, transitions, centred text, sections and synopses.
+    let other = line.starts_with(['!', '>', '#', '=', '~']);
+    match (forced || caps) && !name.is_empty() && !other {
+        true => Some(name.to_owned()),
+        false => None,
+    }
+}
+
+fn blocks(s: &str) -> Vec<Block> {
+    let lines: Vec<&str> = s.lines().map(|x| x.trim_end()).collect();
+    let mut res = Vec::new();
+    let mut n = 0;
+    while n < lines.len() {
+        let line = lines[n].trim_start();
+        let after_blank = n == 0 || lines[n - 1].trim().is_empty();
+        let before_text = lines.get(n + 1).is_some_and(|x| !x.trim().is_empty());
+        if line.is_empty() || !after_blank {
+            n += 1;
+            continue;
+        }
+        if let Some(heading) = scene_heading(line) {
+            res.push(Block::Scene(heading));
+            n += 1;
+            continue;
+        }
+        match character(line).filter(|_| before_text) {
+            Some(name) => {
+                n += 1;
+                let mut said = Vec::new();
+                while let Some(x) = lines.get(n).filter(|x| !x.trim().is_empty()) {
+                    said.push(x.trim().trim_start_matches('~').trim_start().to_owned());
+                    n += 1;
+                }
+                res.push(Block::Dialogue(name, said));
+            }
+            None => n += 1,
+        }
+    }
+    res
+}
+
+fn emphasis(s: &str) -> String {
+    lazy_static! {
+        static ref EMPHASIS_RE: Regex =
+            Regex::new(r"\*\*\*(.+?)\*\*\*|\*\*(.+?)\*\*|\*(.+?)\*|_(.+?)_").unwrap();
+    }
+    EMPHASIS_RE
+        .replace_all(s, |caps: &Captures| {
+            let (tags, inner) = match (caps.get(1), caps.get(2), caps.get(3), caps.get(4)) {
+                (Some(x), ..) => (&["b", "i"][..], x.as_str()),
+                (_, Some(x), ..) => (&["b"][..], x.as_str()),
+                (_, _, Some(x), _) => (&["i"][..], x.as_str()),
+                (.., Some(x)) => (&["u"][..], x.as_str()),
+                _ => return caps[0].to_owned(),
+            };
+            let on: String = tags.iter().map(|t| format!("\\{}1", t)).collect();
+            let off: String = tags.iter().map(|t| format!("\\{}0", t)).collect();
+            format!("{{{}}}{}{{{}}}", on, inner, off)
+        })
+        .into_owned()
+}
+
+fn dialogue_text(lines: &[String]) -> String {
+    let mut out = String::new();
+    let mut after_text = false;
+    for line in lines {
+        // a literal { or } would start or end a block, and \ an escape.
+        let line = line.replace(['{', '}', '\\'], "");
+        if line.starts_with('(') && line.ends_with(')') {
+            out.push_str(&format!("{{{}}}", line));
+            continue;
+        }
+        if after_text {
+            out.push_str("\\N");
+        }
+        out.push_str(&emphasis(&line));
+        after_text = true;
+    }
+    out
+}
+
+// spacing is how long each line gets, 0 leaves everything at zero.
+pub fn parse_track(s: &str, spacing: u32) -> Result<AssTrack<'static>, FormatError> {
+    let s = strip_hidden(&s.replace("\r\n", "\n"));
+    let (title, body) = split_title_page(&s);
+    let blocks = blocks(body);
+    if blocks.is_empty() {
+        return Err(BadFountainDocument);
+    }
+
+    let mut events = Vec::new();
+    let mut at = 0;
+    for block in blocks {
+        match block {
+            Block::Scene(heading) => events.push(Event {
+                descriptor: Cow::Borrowed("Comment"),
+                ..dialogue(at, at, heading)
+            }),
+            Block::Dialogue(name, lines) => {
+                events.push(Event {
+                    actor: Some(Cow::Owned(name)),
+                    ..dialogue(at, at + spacing, dialogue_text(&lines))
+                });
+                at += spacing;
+            }
+        }
+    }
+    let mut track = track_from_events(events);
+    track.header.title = title.map(Cow::Owned);
+    Ok(track)
+}
@@ -0,0 +1,320 @@
+// youtube timedtext json3 (fmt=json3). events carry the text as segs, each
+// with a pen (bold/italic/underline/colours). where a line sits comes from a
+// window position: an anchor point 0-8 plus a horizontal and vertical
+// position in percent of the video. positions and pens are referenced by
+// index, and an event can also inherit them from an earlier "window" event
+// that has an id and no segs.
+
+use super::track_from_events;
+use super::FormatError::{self, BadJson3Document, BadJson3Event};
+use crate::ass::{
+    common::{Alignment, Timecode, ABGR},
+    event::Event,
+    style::Style,
+    text::{self, Segment},
+    AssTrack,
+};
+use serde_json::{json, Map, Value};
+use std::{borrow::Cow, collections::HashMap, convert::TryFrom};
+
+// imported tracks get a resolution so positions have something to scale to.
+const PLAY_RES: (u32, u32) = (1920, 1080);
+
+pub fn parse_track(s: &str) -> Result<AssTrack<'static>, FormatError> {
+    let root: Value = serde_json::from_str(s).or(Err(BadJson3Document))?;
+    let events = root["events"].as_array().ok_or(BadJson3Document)?;
+    let pens = root["pens"].as_array().map_or(&[][..], |x| &x[..]);
+    let positions = root["wpWinPositions"]
+        .as_array()
+        .map_or(&[][..], |x| &x[..]);
+
+    let windows: HashMap<u64, &Value> = events
+        .iter()
+        .filter(|x| x["segs"].is_null())
+        .filter_map(|x| Some((x["id"].as_u64()?, x)))
+        .collect();
+
+    let mut res = Vec::new();
+    for (n, event) in events.iter().enumerate() {
+        let segs = match event["segs"].as_array() {
+            Some(x) => x,
+            None => continue,
+        };
+        let window = event["wWinId"].as_u64().and_then(|x| windows.get(&x));
+        let inherited = |key: &str| {
+            event[key]
+                .as_u64()
+                .or_else(|| window.and_then(|w| w[key].as_u64()))
+        };
+
+        let mut out = String::new();
+        if let Some(p) = inherited("wpWinPosId").and_then(|x| positions.get(x as usize)) {
+            out.push_str(&position_tags(p));
+        }
+        let mut previous = String::new();
+        for seg in segs {
+            let pen = seg["pPenId"].as_u64().and_then(|x| pens.get(x as usize));
+            let tags = pen.map_or(String::new(), pen_tags);
+            if tags != previous {
+                let reset = if previous.is_empty() { "" } else { "\\r" };
+                out.push_str(&format!("{{{}{}}}", reset, tags));
+                previous = tags;
+            }
+            out.push_str(&seg["utf8"].as_str().unwrap_or("").replace('\n', "\\N"));
+        }
+        if text::plain_text(&out).trim().is_empty() {
+            continue;
+        }
+        let ms = |key: &str| u32::try_from(event[key].as_u64().unwrap_or(0)).ok();
+        let (start, duration) = match (ms("tStartMs"), ms("dDurationMs")) {
+            (Some(a), Some(b)) => (a, b),
+            _ => {
+                eprintln!("Dropped event {}: {}", n, BadJson3Event);
+                continue;
+            }
+        };
+        res.push(Event {
+            start_time: Timecode::from(start),
+            end_time: Timecode::from(start.saturating_add(duration)),
+            text: Some(Cow::Owned(out)),
+            ..Event::default()
+        });
+    }
+
+    let mut track = track_from_events(res);
+    track.header.play_res_x = Some(PLAY_RES.0);
+    track.header.play_res_y = Some(PLAY_RES.1);
+    Ok(track)
+}
+
+fn position_tags(p: &Value) -> String {
+    let mut tags = String::new();
+    let point = p["apPoint"].as_u64().filter(|x| *x < 9);
+    if let Some(point) = point {
+        tags.push_str(&format!(
+            "\\an{}",
+            u8::from(point_to_alignment(point as u8))
+        ));
+    }
+    if let (Some(h), Some(v)) = (p["ahHorPos"].as_f64(), p["avVerPos"].as_f64()) {
+        if point.is_none() {
+            // youtube's default anchor is bottom centre.
+            tags.push_str("\\an2");
+        }
+        let x = (h / 100.0 * f64::from(PLAY_RES.0)).round();
+        let y = (v / 100.0 * f64::from(PLAY_RES.1)).round();
+        tags.push_str(&format!("\\pos({},{})", x, y));
+    }
+    match tags.is_empty() {
+        true => tags,
+        false => format!("{{{}}}", tags),
+    }
+}
+
+fn pen_tags(pen: &Value) -> String {
+    let mut tags = String::new();
+    for (key, tag) in &[("bAttr", "b"), ("iAttr", "i"), ("uAttr", "u")] {
+        if pen[*key].as_u64() == Some(1) {
+            tags.push_str(&format!("\\{}1", tag));
+        }
+    }
+    if let Some(c) = pen["fcForeColor"].as_u64() {
+        let [_, r, g, b] = (c as u32).to_be_bytes();
+        tags.push_str(&format!("\\c&H{:02X}{:02X}{:02X}&", b, g, r));
+    }
+    if let Some(a) = pen["foForeAlpha"].as_u64().filter(|x| *x < 255) {
+        tags.push_str(&format!("\\1a&H{:02X}&", 255 - a));
+    }
+    tags
+}
+
+// anchor points go left to right, top to bottom.
+fn point_to_alignment(point: u8) -> Alignment {
+    let row = [7, 4, 1][usize::from(point / 3)];
+    Alignment::try_from(row + point % 3).unwrap_or_default()
+}
+
+fn alignment_to_point(a: Alignment) -> u8 {
+    let n = u8::from(a);
+    let row = match n {
+        7..=9 => 0,
+        4..=6 => 1,
+        _ => 2,
+    };
+    row * 3 + (n - 1) % 3
+}
+
+#[derive(Clone, PartialEq)]
+struct Pen {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    colour: ABGR,
+    alpha: u8,
+}
+impl Pen {
+    fn from_style(style: &Style) -> Self {
+        Pen {
+            bold: style.bold,
+            italic: style.italic,
+            underline: style.underline,
+            colour: style.primary_colour,
+            alpha: style.primary_colour.alpha(),
+        }
+    }
+    fn to_json(&self) -> Value {
+        let mut pen = Map::new();
+        for (set, key) in &[
+            (self.bold, "bAttr"),
+            (self.italic, "iAttr"),
+            (self.underline, "uAttr"),
+        ] {
+            if *set {
+                pen.insert(key.to_string(), json!(1));
+            }
+        }
+        let (r, g, b) = self.colour.rgb();
+        if (r, g, b) != (255, 255, 255) {
+            pen.insert(
+                "fcForeColor".to_owned(),
+                json!(u32::from_be_bytes([0, r, g, b])),
+            );
+        }
+        if self.alpha != 0 {
+            pen.insert("foForeAlpha".to_owned(), json!(255 - self.alpha));
+        }
+        Value::Object(pen)
+    }
+}
+
+fn intern(list: &mut Vec<Value>, v: Value) -> usize {
+    match list.iter().position(|x| *x == v) {
+        Some(n) => n,
+        None => {
+            list.push(v);
+            list.len() - 1
+        }
+    }
+}
+
+pub fn write_track(track: &AssTrack) -> String {
+    let (res_x, res_y) = track.header.play_res();
+    let (res_x, res_y) = (f64::from(res_x), f64::from(res_y));
+    let default_style = Style::default();
+    let mut pens = vec![json!({})];
+    let mut win_styles = vec![json!({})];
+    let mut positions = vec![json!({})];
+    let mut events = Vec::new();
+
+    for event in track.events.iter().filter(|x| x.descriptor != "Comment") {
+        let style = track
+            .style(event.style.as_deref())
+            .unwrap_or(&default_style);
+        let mut alignment = style.alignment;
+        let mut pos = None;
+        let mut pen = Pen::from_style(style);
+        let mut segs = Vec::new();
+        for segment in text::segments(event.text.as_deref().unwrap_or("")) {
+            match segment {
+                Segment::Override(block) => {
+                    for tag in text::tags(block) {
+                        let on = !matches!(tag.arg, "" | "0");
+                        match tag.name {
+                            "an" => {
+                                if let Some(a) = tag
+                                    .arg
+                                    .parse::<u8>()
+                                    .ok()
+                                    .and_then(|x| Alignment::try_from(x).ok())
+                                {
+                                    alignment = a;
+                                }
+                            }
+                            "pos" => {
+                                let args = text::tag_args(tag.arg);
+                                if let [x, y] = args[..] {
+                                    if let (Ok(x), Ok(y)) = (x.parse::<f64>(), y.parse::<f64>()) {
+                                        pos = Some((x, y));
+                                    }
+                                }
+                            }
+                            "b" => pen.bold = on,
+                            "i" => pen.italic = on,
+                            "u" => pen.underline = on,
+                            "c" | "1c" => {
+                                pen.colour =
+                                    text::tag_colour(tag.arg).unwrap_or(style.primary_colour)
+                            }
+                            "1a" | "alpha" => {
+                                pen.alpha =
+                                    text::tag_alpha(tag.arg).unwrap_or(style.primary_colour.alpha())
+                            }
+                            "r" => pen = Pen::from_style(style),
+                            _ => (),
+                        }
+                    }
+                }
+                Segment::Text(t) => {
+                    let t = text::unescape(t).replace('\u{a0}', " ");
+                    if t.is_empty() {
+                        continue;
+                    }
+                    let mut seg = Map::new();
+                    seg.insert("utf8".to_owned(), json!(t));
+                    let id = intern(&mut pens, pen.to_json());
+                    if id != 0 {
+                        seg.insert("pPenId".to_owned(), json!(id));
+                    }
+                    segs.push(Value::Object(seg));
+                }
+            }
+        }
+        if segs.is_empty() {
+            continue;
+        }
+
+        let (margin_l, margin_r, margin_v) = event.margins(style);
+        let point = alignment_to_point(alignment);
+        let (x, y) = pos.unwrap_or_else(|| {
+            let x = match point % 3 {
+                0 => f64::from(margin_l),
+                1 => res_x / 2.0,
+                _ => res_x - f64::from(margin_r),
+            };
+            let margin_v = f64::from(margin_v);
+            let y = match point / 3 {
+                0 => margin_v,
+                1 => res_y / 2.0,
+                _ => res_y - margin_v,
+            };
+            (x, y)
+        });
+        let percent = |a: f64, b: f64| (a / b * 100.0).round().clamp(0.0, 100.0) as u32;
+        let position = json!({
+            "apPoint": point,
+            "ahHorPos": percent(x, res_x),
+            "avVerPos": percent(y, res_y),
+        });
+        // justification is 0 left, 1 right, 2 centre.
+        let justify = [0, 2, 1][usize::from(point % 3)];
+        let win_style = json!({ "juJustifCode": justify });
+
+        let start = event.start_time.as_millis();
+        events.push(json!({
+            "tStartMs": start,
+            "dDurationMs": event.end_time.as_millis().saturating_sub(start),
+            "wpWinPosId": intern(&mut positions, position),
+            "wsWinStyleId": intern(&mut win_styles, win_style),
+            "segs": segs,
+        }));
+    }
+
+    let root = json!({
+        "wireMagic": "pb3",
+        "pens": pens,
+        "wsWinStyles": win_styles,
+        "wpWinPositions": positions,
+        "events": events,
+    });
+    serde_json::to_string_pretty(&root).unwrap()
+}
@@ -0,0 +1,272 @@
+// .lrc lyrics: [mm:ss.xx]text, one line per lyric, each lasting until the
+// next one starts. a line can have several timestamps if it repeats. an empty
+// line ends the previous one early.
+//
+// enhanced lrc adds <mm:ss.xx> before each word, plus one after the last
+// word for when the line ends. those become \k tags.
+//
+// the [ti:] tag is the title, [ar:] (the artist) goes in Original Script and
+// [by:] (whoever made the file) in Original Timing. [offset:] is in ms and
+// positive means earlier.
+
+use super::FormatError::{self, BadLrcLine};
+use super::{parse_lines, track_from_events, OPEN_ENDED_DURATION};
+use crate::ass::{
+    common::Timecode,
+    event::Event,
+    info::Header,
+    text::{self, Segment},
+    AssTrack,
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::{borrow::Cow, convert::TryFrom};
+
+struct Line {
+    start: u32,
+    // from the trailing word timestamp, if there is one.
+    end: Option<u32>,
+    // text before the first word timestamp.
+    lead: String,
+    // (start, text) of each timed word.
+    words: Vec<(u32, String)>,
+}
+
+fn parse_time(s: &str) -> Option<u32> {
+    lazy_static! {
+        static ref TIME_RE: Regex = Regex::new(r"^(\d+):(\d{1,2})(?:[.:](\d{1,3}))?$").unwrap();
+    }
+    let caps = TIME_RE.captures(s.trim())?;
+    let fraction = match caps.get(3) {
+        // .x is tenths, .xx hundredths, .xxx ms.
+        Some(x) => x.as_str().parse::<u32>().ok()? * [100, 10, 1][x.as_str().len() - 1],
+        None => 0,
+    };
+    // minutes have no upper bound.
+    caps[1]
+        .parse::<u32>()
+        .ok()?
+        .checked_mul(60_000)?
+        .checked_add(caps[2].parse::<u32>().ok()? * 1000 + fraction)
+}
+
+pub fn parse_track(s: &str) -> Result<AssTrack<'static>, FormatError> {
+    lazy_static! {
+        static ref TAG_RE: Regex = Regex::new(r"^\[([^\]]*)\]").unwrap();
+        static ref WORD_RE: Regex = Regex::new(r"<([\d:.]+)>").unwrap();
+    }
+    let mut header = Header::default();
+    let mut offset = 0i64;
+    let lines = parse_lines(s, |line| {
+        let mut starts = Vec::new();
+        let mut rest = line;
+        while let Some(caps) = TAG_RE.captures(rest) {
+            let tag = caps.get(1).unwrap().as_str();
+            match parse_time(tag) {
+                Some(t) => starts.push(t),
+                None if starts.is_empty() => {
+                    let (key, value) = tag.split_once(':').ok_or(BadLrcLine)?;
+                    let value = Some(Cow::Owned(value.trim().to_owned()));
+                    match key.trim().to_ascii_lowercase().as_str() {
+                        "ti" => header.title = value,
+                        "ar" => header.script = value,
+                        "by" => header.timing = value,
+                        "offset" => offset = value.and_then(|x| x.parse().ok()).unwrap_or(0),
+                        _ => (),
+                    }
+                    return Ok(None);
+                }
+                None => break,
+            }
+            rest = &rest[caps[0].len()..];
+        }
+        if starts.is_empty() {
+            return Err(BadLrcLine);
+        }
+
+        // word times are absolute, so a repeated line shifts them along.
+        let mut lead = String::new();
+        let mut words = Vec::new();
+        let mut end = None;
+        let mut last = 0;
+        for caps in WORD_RE.captures_iter(rest) {
+            let m = caps.get(0).unwrap();
+            let before = &rest[last..m.start()];
+            match words.last_mut() {
+                Some((_, text)) => *text += before,
+                None => lead += before,
+            }
+            last = m.end();
+            if let Some(t) = parse_time(&caps[1]) {
+                words.push((t, String::new()));
+            }
+        }
+        match words.last_mut() {
+            Some((_, text)) => *text += &rest[last..],
+            None => lead += &rest[last..],
+        }
+        // a timestamp with nothing after it is when the line ends.
+        if words.last().is_some_and(|x| x.1.trim().is_empty()) {
+            end = words.pop().map(|x| x.0);
+        }
+        let first = starts[0];
+        Ok(Some(
+            starts
+                .into_iter()
+                .map(|start| {
+                    let shift = |t: u32| t.saturating_add(start).saturating_sub(first);
+                    Line {
+                        start,
+                        end: end.map(shift),
+                        lead: lead.clone(),
+                        words: words.iter().map(|(t, w)| (shift(*t), w.clone())).collect(),
+                    }
+                })
+                .collect::<Vec<Line>>(),
+        ))
+    });
+
+    let shift =
+        |t: u32| u32::try_from(i64::from(t).saturating_sub(offset).max(0)).unwrap_or(u32::MAX);
+    let mut lines: Vec<Line> = lines.into_iter().flatten().collect();
+    for line in lines.iter_mut() {
+        line.start = shift(line.start);
+        line.end = line.end.map(shift);
+        for word in line.words.iter_mut() {
+            word.0 = shift(word.0);
+        }
+    }
+    lines.sort_by_key(|x| x.start);
+
+    let mut events = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if line.lead.trim().is_empty() && line.words.is_empty() {
+            continue;
+        }
+        let limit = line.start.saturating_add(OPEN_ENDED_DURATION);
+        let end = line.end.unwrap_or_else(|| {
+            lines
+                .get(i + 1)
+                .map_or(limit, |x| x.start.clamp(line.start, limit))
+        });
+        events.push(Event {
+            start_time: Timecode::from(line.start),
+            end_time: Timecode::from(end),
+            text: Some(Cow::Owned(karaoke_text(line, end))),
+            ..Event::default()
+        });
+    }
+
+    let mut track = track_from_events(events);
+    track.header = Header {
+        script_type: track.header.script_type,
+        ..header
+    };
+    Ok(track)
+}
+
+// \k durations are in centiseconds. they're worked out from rounded absolute
+// times so the rounding doesn't pile up over a long line.
+fn karaoke_text(line: &Line, end: u32) -> String {
+    if line.words.is_empty() {
+        return line.lead.trim().to_owned();
+    }
+    let cs = |t: u32| (t.saturating_sub(line.start) + 5) / 10;
+    let mut out = String::new();
+    let lead = line.lead.trim_start();
+    if !lead.is_empty() {
+        out.push_str(&format!("{{\\k{}}}{}", cs(line.words[0].0), lead));
+    }
+    for (n, (t, word)) in line.words.iter().enumerate() {
+        let next = line.words.get(n + 1).map_or(end, |x| x.0);
+        let word = match n + 1 == line.words.len() {
+            true => word.trim_end(),
+            false => word,
+        };
+        out.push_str(&format!(
+            "{{\\k{}}}{}",
+            cs(next.max(*t)) - cs(*t),
+            match out.is_empty() {
+                true => word.trim_start(),
+                false => word,
+            }
+        ));
+    }
+    out
+}
+
+fn time(ms: u32) -> String {
+    format!(
+        "{:02}:{:02}.{:02}",
+        ms / 60_000,
+        ms / 1000 % 60,
+        ms % 1000 / 10
+    )
+}
+
+pub fn write_track(track: &AssTrack) -> String {
+    let mut out = Vec::new();
+    let header = &track.header;
+    for (tag, value) in &[
+        ("ti", &header.title),
+        ("ar", &header.script),
+        ("by", &header.timing),
+    ] {
+        if let Some(x) = value {
+            out.push(format!("[{}:{}]", tag, x));
+        }
+    }
+
+    let mut events: Vec<&Event> = track
+        .events
+        .iter()
+        .filter(|x| x.descriptor != "Comment")
+        .collect();
+    events.sort_by_key(|x| x.start_time.0);
+    for (i, event) in events.iter().enumerate() {
+        let start = event.start_time.as_millis();
+        let end = event.end_time.as_millis();
+        let line = lyric(event.text.as_deref().unwrap_or(""), start, end);
+        if line.trim().is_empty() {
+            continue;
+        }
+        out.push(format!("[{}]{}", time(start), line));
+        // plain lrc has no end times, so an empty line clears the lyric when
+        // there's a gap before the next one.
+        if events
+            .get(i + 1)
+            .is_none_or(|x| x.start_time.as_millis() > end)
+        {
+            out.push(format!("[{}]", time(end)));
+        }
+    }
+    out.push(String::new());
+    out.join("\n")
+}
+
+// the text of a line, with word timestamps if it has \k tags.
+fn lyric(s: &str, start: u32, end: u32) -> String {
+    let single_line = |t: &str| text::unescape(t).replace(['\n', '\u{a0}'], " ");
+    let mut out = String::new();
+    let mut at = start;
+    let mut karaoke = false;
+    for segment in text::segments(s) {
+        match segment {
+            Segment::Override(block) => {
+                for tag in text::tags(block) {
+                    if let "k" | "K" | "kf" | "ko" = tag.name {
+                        let cs = tag.arg.parse::<u32>().unwrap_or(0);
+                        out.push_str(&format!("<{}>", time(at)));
+                        at = at.saturating_add(cs.saturating_mul(10));
+                        karaoke = true;
+                    }
+                }
+            }
+            Segment::Text(t) => out.push_str(&single_line(t)),
+        }
+    }
+    if karaoke {
+        out.push_str(&format!("<{}>", time(end)));
+    }
+    out
+}
@@ -0,0 +1,587 @@
+// pulling ass/ssa tracks out of matroska (.mkv/.mks) files.
+//
+// matroska is ebml: every element is an id, a size and then either data or
+// more elements. ids and sizes are variable length ints where the number of
+// leading zero bits in the first byte says how many more bytes follow. the
+// id keeps that marker bit, the size doesn't, and a size of all ones means
+// "unknown" (runs until something that can't be a child turns up).
+//
+// an ass track keeps the script header ([Script Info], styles and the
+// [Events] format line) in its CodecPrivate, and every event is a block
+// timed by the container, with the rest of the line in packet order. files
+// are read with seeks rather than all at once since they're mostly video.
+
+use super::FormatError::BadMatroskaFile;
+use super::OPEN_ENDED_DURATION;
+use crate::ass::{attachment::Attachment, AssTrack};
+use std::{
+    convert::TryFrom,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+pub mod packet;
+
+const EBML: u32 = 0x1a45_dfa3;
+const SEGMENT: u32 = 0x1853_8067;
+const SEEK_HEAD: u32 = 0x114d_9b74;
+const INFO: u32 = 0x1549_a966;
+const TIMECODE_SCALE: u32 = 0x2a_d7b1;
+const TRACKS: u32 = 0x1654_ae6b;
+const TRACK_ENTRY: u32 = 0xae;
+const TRACK_NUMBER: u32 = 0xd7;
+const CODEC_ID: u32 = 0x86;
+const CODEC_PRIVATE: u32 = 0x63a2;
+const LANGUAGE: u32 = 0x22_b59c;
+const LANGUAGE_BCP47: u32 = 0x22_b59d;
+const NAME: u32 = 0x536e;
+const CONTENT_ENCODINGS: u32 = 0x6d80;
+const CONTENT_ENCODING: u32 = 0x6240;
+const CONTENT_ENCODING_SCOPE: u32 = 0x5032;
+const CONTENT_ENCODING_TYPE: u32 = 0x5033;
+const CONTENT_COMPRESSION: u32 = 0x5034;
+const CONTENT_COMP_ALGO: u32 = 0x4254;
+const CONTENT_COMP_SETTINGS: u32 = 0x4255;
+const CLUSTER: u32 = 0x1f43_b675;
+const TIMECODE: u32 = 0xe7;
+const SIMPLE_BLOCK: u32 = 0xa3;
+const BLOCK_GROUP: u32 = 0xa0;
+const BLOCK: u32 = 0xa1;
+const BLOCK_DURATION: u32 = 0x9b;
+const CUES: u32 = 0x1c53_bb6b;
+const CHAPTERS: u32 = 0x1043_a770;
+const TAGS: u32 = 0x1254_c367;
+const ATTACHMENTS: u32 = 0x1941_a469;
+const ATTACHED_FILE: u32 = 0x61a7;
+const FILE_NAME: u32 = 0x466e;
+const FILE_MIME_TYPE: u32 = 0x4660;
+const FILE_DATA: u32 = 0x465c;
+
+// segment children, for working out where a cluster of unknown size ends.
+const TOP_LEVEL: &[u32] = &[
+    SEEK_HEAD,
+    INFO,
+    TRACKS,
+    CLUSTER,
+    CUES,
+    CHAPTERS,
+    TAGS,
+    ATTACHMENTS,
+];
+
+const CODECS: &[&str] = &["S_TEXT/ASS", "S_TEXT/SSA", "S_ASS", "S_SSA"];
+
+#[derive(Debug, Clone)]
+pub struct SubtitleTrack {
+    pub number: u64,
+    pub language: Option<String>,
+    pub name: Option<String>,
+    pub track: AssTrack<'static>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AttachedFile {
+    pub name: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+impl AttachedFile {
+    // the name comes from the file, so don't let it point anywhere else.
+    pub fn file_name(&self) -> Option<String> {
+        Path::new(&self.name)
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+    }
+    // muxers don't agree on a mime type for fonts, so check the name too.
+    pub fn is_font(&self) -> bool {
+        let mime = self.mime_type.to_ascii_lowercase();
+        let name = self.name.to_ascii_lowercase();
+        mime.starts_with("font/")
+            || mime.contains("truetype")
+            || mime.contains("opentype")
+            || mime.contains("font-")
+            || [".ttf", ".otf", ".ttc", ".otc"]
+                .iter()
+                .any(|x| name.ends_with(x))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Matroska {
+    pub tracks: Vec<SubtitleTrack>,
+    pub attachments: Vec<AttachedFile>,
+}
+impl Matroska {
+    // copies the font attachments into every track's [Fonts].
+    pub fn embed_fonts(&mut self) {
+        let fonts: Vec<Attachment<'static>> = self
+            .attachments
+            .iter()
+            .filter(|x| x.is_font())
+            .filter_map(|x| Some(Attachment::new(x.file_name()?, x.data.clone())))
+            .collect();
+        for track in self.tracks.iter_mut() {
+            track.track.fonts.extend(fonts.iter().cloned());
+        }
+    }
+    // writes the font attachments into dir, returning where they went.
+    pub fn save_fonts(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(dir)?;
+        let mut res = Vec::new();
+        for file in self.attachments.iter().filter(|x| x.is_font()) {
+            let name = match file.file_name() {
+                Some(x) => x,
+                None => continue,
+            };
+            let path = dir.join(name);
+            std::fs::write(&path, &file.data)?;
+            res.push(path);
+        }
+        Ok(res)
+    }
+}
+
+fn invalid() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, BadMatroskaFile)
+}
+
+// how a track's blocks (scope 1) and codec private (scope 2) were packed.
+#[derive(Debug, Clone)]
+struct Compression {
+    algorithm: u64,
+    settings: Vec<u8>,
+    scope: u64,
+}
+impl Compression {
+    fn undo(&self, data: Vec<u8>) -> io::Result<Vec<u8>> {
+        match self.algorithm {
+            // zlib
+            0 => miniz_oxide::inflate::decompress_to_vec_zlib(&data).or(Err(invalid())),
+            // header stripping, the stripped bytes are in the settings.
+            3 => Ok([&self.settings[..], &data[..]].concat()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct TrackEntry {
+    number: u64,
+    codec: String,
+    private: Vec<u8>,
+    language: Option<String>,
+    name: Option<String>,
+    compression: Vec<Compression>,
+}
+impl TrackEntry {
+    fn decompress(&self, mut data: Vec<u8>, scope: u64) -> io::Result<Vec<u8>> {
+        // encodings are listed in the order they were applied.
+        for c in self
+            .compression
+            .iter()
+            .rev()
+            .filter(|x| x.scope & scope != 0)
+        {
+            data = c.undo(data)?;
+        }
+        Ok(data)
+    }
+}
+
+struct Block {
+    track: u64,
+    // in timecode scale units.
+    time: i64,
+    duration: Option<u64>,
+    data: Vec<u8>,
+}
+
+struct Reader<R> {
+    r: R,
+    timecode_scale: u64,
+    tracks: Vec<TrackEntry>,
+    blocks: Vec<Block>,
+    attachments: Vec<AttachedFile>,
+}
+
+pub fn read<R: Read + Seek>(r: R) -> io::Result<Matroska> {
+    let mut reader = Reader {
+        r,
+        timecode_scale: 1_000_000,
+        tracks: Vec::new(),
+        blocks: Vec::new(),
+        attachments: Vec::new(),
+    };
+    match reader.header()? {
+        Some((EBML, Some(size))) => reader.skip(size)?,
+        _ => return Err(invalid()),
+    }
+    while let Some((id, size)) = reader.header()? {
+        let end = reader.end(size)?;
+        if id == SEGMENT {
+            reader.segment(end)?;
+        }
+        reader.seek_to(end)?;
+    }
+    reader.finish()
+}
+
+impl<R: Read + Seek> Reader<R> {
+    fn pos(&mut self) -> io::Result<u64> {
+        self.r.stream_position()
+    }
+    fn skip(&mut self, n: u64) -> io::Result<()> {
+        self.r.seek(SeekFrom::Current(n as i64)).map(|_| ())
+    }
+    fn seek_to(&mut self, end: Option<u64>) -> io::Result<()> {
+        if let Some(end) = end {
+            self.r.seek(SeekFrom::Start(end))?;
+        }
+        Ok(())
+    }
+    // where an element that starts here ends. None is the end of the file.
+    fn end(&mut self, size: Option<u64>) -> io::Result<Option<u64>> {
+        Ok(match size {
+            Some(size) => Some(self.pos()? + size),
+            None => None,
+        })
+    }
+    fn more(&mut self, end: Option<u64>) -> io::Result<bool> {
+        match end {
+            Some(end) => Ok(self.pos()? < end),
+            None => {
+                let mut b = [0];
+                let more = self.r.read(&mut b)? == 1;
+                if more {
+                    self.r.seek(SeekFrom::Current(-1))?;
+                }
+                Ok(more)
+            }
+        }
+    }
+
+    // returns the value, and the length with the marker bit still set so
+    // ids can be read with the same thing.
+    fn vint(&mut self) -> io::Result<Option<(u64, u64)>> {
+        let mut first = [0];
+        if self.r.read(&mut first)? == 0 {
+            return Ok(None);
+        }
+        let len = first[0].leading_zeros() as usize + 1;
+        if len > 8 {
+            return Err(invalid());
+        }
+        let mut rest = [0; 7];
+        self.r.read_exact(&mut rest[..len - 1])?;
+        let raw = rest[..len - 1]
+            .iter()
+            .fold(u64::from(first[0]), |n, x| n << 8 | u64::from(*x));
+        Ok(Some((raw, len as u64)))
+    }
+    fn header(&mut self) -> io::Result<Option<(u32, Option<u64>)>> {
+        let id = match self.vint()? {
+            Some((id, len)) if len <= 4 => id as u32,
+            Some(_) => return Err(invalid()),
+            None => return Ok(None),
+        };
+        let (raw, len) = self.vint()?.ok_or_else(invalid)?;
+        let value = raw & ((1 << (7 * len)) - 1);
+        let size = match value == (1 << (7 * len)) - 1 {
+            true => None,
+            false => Some(value),
+        };
+        Ok(Some((id, size)))
+    }
+    fn bytes(&mut self, size: Option<u64>) -> io::Result<Vec<u8>> {
+        let size = size.ok_or_else(invalid)?;
+        let mut res = Vec::new();
+        (&mut self.r).take(size).read_to_end(&mut res)?;
+        match res.len() as u64 == size {
+            true => Ok(res),
+            false => Err(invalid()),
+        }
+    }
+    fn uint(&mut self, size: Option<u64>) -> io::Result<u64> {
+        match size {
+            Some(0..=8) => Ok(self
+                .bytes(size)?
+                .iter()
+                .fold(0, |n, x| n << 8 | u64::from(*x))),
+            _ => Err(invalid()),
+        }
+    }
+    fn string(&mut self, size: Option<u64>) -> io::Result<String> {
+        let b = self.bytes(size)?;
+        Ok(String::from_utf8_lossy(&b)
+            .trim_end_matches('\0')
+            .to_owned())
+    }
+
+    // calls f with each child element. anything f doesn't read is skipped.
+    fn children<F>(&mut self, end: Option<u64>, mut f: F) -> io::Result<()>
+    where
+        F: FnMut(&mut Self, u32, Option<u64>) -> io::Result<()>,
+    {
+        while self.more(end)? {
+            let (id, size) = match self.header()? {
+                Some(x) => x,
+                None => break,
+            };
+            let child_end = match size {
+                Some(_) => self.end(size)?,
+                None => end,
+            };
+            f(self, id, size)?;
+            self.seek_to(child_end)?;
+        }
+        Ok(())
+    }
+
+    fn segment(&mut self, end: Option<u64>) -> io::Result<()> {
+        while self.more(end)? {
+            let (id, size) = match self.header()? {
+                Some(x) => x,
+                None => break,
+            };
+            let child_end = self.end(size)?;
+            match id {
+                INFO => self.children(child_end, |r, id, size| {
+                    if id == TIMECODE_SCALE {
+                        r.timecode_scale = r.uint(size)?.max(1);
+                    }
+                    Ok(())
+                })?,
+                TRACKS => self.children(child_end, |r, id, size| {
+                    if id == TRACK_ENTRY {
+                        let end = r.end(size)?;
+                        let entry = r.track_entry(end)?;
+                        if CODECS.contains(&entry.codec.as_str()) {
+                            r.tracks.push(entry);
+                        }
+                    }
+                    Ok(())
+                })?,
+                CLUSTER => self.cluster(child_end.or(end))?,
+                ATTACHMENTS => self.children(child_end, |r, id, size| {
+                    if id == ATTACHED_FILE {
+                        let end = r.end(size)?;
+                        r.attached_file(end)?;
+                    }
+                    Ok(())
+                })?,
+                // anything else of unknown size can't be skipped.
+                _ if size.is_none() => return Err(invalid()),
+                _ => (),
+            }
+            if size.is_some() {
+                self.seek_to(child_end)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn track_entry(&mut self, end: Option<u64>) -> io::Result<TrackEntry> {
+        let mut entry = TrackEntry::default();
+        let mut language = None;
+        let mut bcp47 = None;
+        self.children(end, |r, id, size| {
+            match id {
+                TRACK_NUMBER => entry.number = r.uint(size)?,
+                CODEC_ID => entry.codec = r.string(size)?,
+                CODEC_PRIVATE => entry.private = r.bytes(size)?,
+                LANGUAGE => language = Some(r.string(size)?),
+                LANGUAGE_BCP47 => bcp47 = Some(r.string(size)?),
+                NAME => entry.name = Some(r.string(size)?),
+                CONTENT_ENCODINGS => {
+                    let end = r.end(size)?;
+                    entry.compression = r.content_encodings(end)?;
+                }
+                _ => (),
+            }
+            Ok(())
+        })?;
+        // "und" is the default when there's no language element.
+        entry.language = bcp47.or(language).filter(|x| x != "und");
+        Ok(entry)
+    }
+
+    fn content_encodings(&mut self, end: Option<u64>) -> io::Result<Vec<Compression>> {
+        let mut res = Vec::new();
+        self.children(end, |r, id, size| {
+            if id != CONTENT_ENCODING {
+                return Ok(());
+            }
+            let mut c = Compression {
+                algorithm: 0,
+                settings: Vec::new(),
+                scope: 1,
+            };
+            let mut compressed = true;
+            let end = r.end(size)?;
+            r.children(end, |r, id, size| {
+                match id {
+                    CONTENT_ENCODING_SCOPE => c.scope = r.uint(size)?,
+                    // 1 is encryption, which we can't do anything with.
+                    CONTENT_ENCODING_TYPE => compressed = r.uint(size)? == 0,
+                    CONTENT_COMPRESSION => {
+                        let end = r.end(size)?;
+                        r.children(end, |r, id, size| {
+                            match id {
+                                CONTENT_COMP_ALGO => c.algorithm = r.uint(size)?,
+                                CONTENT_COMP_SETTINGS => c.settings = r.bytes(size)?,
+                                _ => (),
+                            }
+                            Ok(())
+                        })?;
+                    }
+                    _ => (),
+                }
+                Ok(())
+            })?;
+            match compressed {
+                true => res.push(c),
+                false => return Err(invalid()),
+            }
+            Ok(())
+        })?;
+        Ok(res)
+    }
+
+    fn cluster(&mut self, end: Option<u64>) -> io::Result<()> {
+        let mut timecode = 0;
+        while self.more(end)? {
+            let start = self.pos()?;
+            let (id, size) = match self.header()? {
+                Some(x) => x,
+                None => break,
+            };
+            if TOP_LEVEL.contains(&id) {
+                // the end of a cluster with unknown size.
+                self.r.seek(SeekFrom::Start(start))?;
+                break;
+            }
+            let child_end = self.end(size)?;
+            match id {
+                TIMECODE => timecode = self.uint(size)?,
+                SIMPLE_BLOCK => {
+                    if let Some(block) = self.block(size, timecode)? {
+                        self.blocks.push(block);
+                    }
+                }
+                BLOCK_GROUP => {
+                    let mut block = None;
+                    let mut duration = None;
+                    self.children(child_end, |r, id, size| {
+                        match id {
+                            BLOCK => block = r.block(size, timecode)?,
+                            BLOCK_DURATION => duration = Some(r.uint(size)?),
+                            _ => (),
+                        }
+                        Ok(())
+                    })?;
+                    if let Some(block) = block {
+                        self.blocks.push(Block { duration, ..block });
+                    }
+                }
+                _ => (),
+            }
+            self.seek_to(child_end)?;
+        }
+        Ok(())
+    }
+
+    // only reads the whole thing if it's for one of our tracks.
+    fn block(&mut self, size: Option<u64>, timecode: u64) -> io::Result<Option<Block>> {
+        let size = size.ok_or_else(invalid)?;
+        let (raw, len) = self.vint()?.ok_or_else(invalid)?;
+        let track = raw & ((1 << (7 * len)) - 1);
+        if !self.tracks.iter().any(|x| x.number == track) || size < len + 3 {
+            return Ok(None);
+        }
+        let mut head = [0; 3];
+        self.r.read_exact(&mut head)?;
+        let relative = i16::from_be_bytes([head[0], head[1]]);
+        if head[2] & 0x06 != 0 {
+            // subtitle packets are never laced in practice.
+            eprintln!("Dropped laced block for track {}", track);
+            return Ok(None);
+        }
+        Ok(Some(Block {
+            track,
+            time: i64::try_from(timecode)
+                .ok()
+                .and_then(|x| x.checked_add(i64::from(relative)))
+                .ok_or_else(invalid)?,
+            duration: None,
+            data: self.bytes(Some(size - len - 3))?,
+        }))
+    }
+
+    fn attached_file(&mut self, end: Option<u64>) -> io::Result<()> {
+        let mut file = AttachedFile {
+            name: String::new(),
+            mime_type: String::new(),
+            data: Vec::new(),
+        };
+        self.children(end, |r, id, size| {
+            match id {
+                FILE_NAME => file.name = r.string(size)?,
+                FILE_MIME_TYPE => file.mime_type = r.string(size)?,
+                FILE_DATA => file.data = r.bytes(size)?,
+                _ => (),
+            }
+            Ok(())
+        })?;
+        self.attachments.push(file);
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<Matroska> {
+        // the scale is whatever the file says, so damaged ones can give times
+        // too far out for a script.
+        let ms = |units: u64| {
+            let ns = units.checked_mul(self.timecode_scale)?;
+            u32::try_from(ns / 1_000_000).ok()
+        };
+        let mut tracks = Vec::new();
+        for entry in &self.tracks {
+            let private = entry.decompress(entry.private.clone(), 2)?;
+            let private = String::from_utf8_lossy(&private);
+            let mut packets = Vec::new();
+            for block in self.blocks.iter().filter(|x| x.track == entry.number) {
+                let data = entry.decompress(block.data.clone(), 1)?;
+                let start = ms(u64::try_from(block.time).unwrap_or(0));
+                let duration = block.duration.map_or(Some(OPEN_ENDED_DURATION), ms);
+                let (start, duration) = match (start, duration) {
+                    (Some(a), Some(b)) => (a, b),
+                    _ => {
+                        eprintln!("Dropped out of range block for track {}", entry.number);
+                        continue;
+                    }
+                };
+                packets.push(packet::Packet {
+                    start,
+                    end: start.saturating_add(duration),
+                    data: String::from_utf8_lossy(&data).into_owned(),
+                });
+            }
+            let track = match packet::parse_track(&private, &packets) {
+                Ok(x) => x,
+                Err(e) => {
+                    eprintln!("Dropped track {}: {}", entry.number, e);
+                    continue;
+                }
+            };
+            tracks.push(SubtitleTrack {
+                number: entry.number,
+                language: entry.language.clone(),
+                name: entry.name.clone(),
+                track,
+            });
+        }
+        Ok(Matroska {
+            tracks,
+            attachments: self.attachments,
+        })
+    }
+}
@@ -0,0 +1,122 @@
+// an event as matroska stores it: the timing goes on the block, and the
+// packet holds ReadOrder,Layer,Style,Name,MarginL,MarginR,MarginV,Effect,Text.
+// ReadOrder is where the line was in the script, since blocks have to be
+// sorted by time. ssa has Marked where ass has Layer.
+//
+// the CodecPrivate is the script up to and including the [Events] format
+// line. comments aren't muxed, same as mkvmerge.
+
+use crate::ass::{
+    common::Timecode,
+    event::Event,
+    AssParseError::{self, EventNotMatchFormat, EventTooShort},
+    AssTrack,
+};
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packet {
+    // ms
+    pub start: u32,
+    pub end: u32,
+    pub data: String,
+}
+
+impl Packet {
+    pub fn from_event(event: &Event, read_order: u64) -> Self {
+        Packet {
+            start: event.start_time.as_millis(),
+            end: event.end_time.as_millis(),
+            data: format!(
+                "{},{},{},{},{},{},{},{},{}",
+                read_order,
+                event.layer,
+                event.style.as_deref().unwrap_or(""),
+                event.actor.as_deref().unwrap_or(""),
+                event.margin_l,
+                event.margin_r,
+                event.margin_v,
+                event.effect.as_deref().unwrap_or(""),
+                event.text.as_deref().unwrap_or(""),
+            ),
+        }
+    }
+}
+
+pub fn codec_private(track: &AssTrack) -> String {
+    let header_only = AssTrack {
+        header: track.header.clone(),
+        styleformat: track.styleformat.clone(),
+        styles: track.styles.clone(),
+        eventformat: track.eventformat.clone(),
+        ..AssTrack::default()
+    };
+    format!("{}\n", header_only)
+}
+
+// the track's events as packets, in the order they'd be muxed.
+pub fn write_packets(track: &AssTrack) -> Vec<Packet> {
+    let mut packets: Vec<Packet> = track
+        .events
+        .iter()
+        .filter(|x| x.descriptor != "Comment")
+        .enumerate()
+        .map(|(n, x)| Packet::from_event(x, n as u64))
+        .collect();
+    packets.sort_by_key(|x| x.start);
+    packets
+}
+
+// rebuilds a track from the CodecPrivate text and the track's packets, in
+// their original order. packets that don't parse are dropped.
+pub fn parse_track(private: &str, packets: &[Packet]) -> Result<AssTrack<'static>, AssParseError> {
+    let mut track = AssTrack::parse_track(private)?.into_owned();
+    let format = track.eventformat.clone().unwrap_or_default();
+    let mut events = Vec::new();
+    for (n, packet) in packets.iter().enumerate() {
+        match parse_packet(packet) {
+            Ok((order, event)) => events.push((
+                order,
+                Event {
+                    format: format.clone(),
+                    ..event
+                },
+            )),
+            Err(e) => eprintln!("Dropped packet {}: {}", n, e),
+        }
+    }
+    events.sort_by_key(|x| x.0);
+    track.events = events.into_iter().map(|x| x.1).collect();
+    Ok(track)
+}
+
+fn parse_packet(packet: &Packet) -> Result<(u64, Event<'static>), AssParseError> {
+    use crate::ass::event::Token::*;
+    let fields: Vec<&str> = packet.data.splitn(9, ',').collect();
+    if fields.len() < 9 {
+        return Err(EventTooShort);
+    }
+    let order = fields[0]
+        .trim()
+        .parse()
+        .or(Err(EventNotMatchFormat(Layer)))?;
+    let owned = |x: &str| Some(Cow::Owned(x.to_owned()));
+    let margin = |x: &str, t| x.trim().parse().or(Err(EventNotMatchFormat(t)));
+    Ok((
+        order,
+        Event {
+            start_time: Timecode::from(packet.start),
+            end_time: Timecode::from(packet.end),
+            // Marked=0 from ssa isn't a layer.
+            layer: fields[1].trim().parse().unwrap_or(0),
+            style: owned(fields[2]),
+            actor: owned(fields[3]),
+            margin_l: margin(fields[4], MarginL)?,
+            margin_r: margin(fields[5], MarginR)?,
+            margin_v: margin(fields[6], MarginV)?,
+            effect: owned(fields[7]),
+            text: owned(fields[8]),
+            ..Event::default()
+        },
+    ))
+}
@@ -0,0 +1,120 @@
+// microdvd .sub: {start frame}{end frame}text, lines split with |.
+// control codes like {y:i} apply to one line, {Y:i} to the whole subtitle.
+
+use super::FormatError::{self, BadFrameRate, BadMicroDvdLine};
+use super::{close_open_ends, parse_lines, track_from_events};
+use crate::ass::{common::Timecode, AssTrack};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+pub fn parse_track(s: &str, fps: f64) -> Result<AssTrack<'static>, FormatError> {
+    lazy_static! {
+        static ref LINE_RE: Regex = Regex::new(r"^\{(\d+)\}\{(\d*)\}(.*)$").unwrap();
+    }
+    if !fps.is_finite() || fps <= 0.0 {
+        return Err(BadFrameRate);
+    }
+    let ms = |frames: &str| -> Result<u32, FormatError> {
+        let frames = frames.parse().or(Err(BadMicroDvdLine))?;
+        Ok(Timecode::from_frames(frames, fps).as_millis())
+    };
+    let lines = parse_lines(s, |line| {
+        let caps = LINE_RE.captures(line).ok_or(BadMicroDvdLine)?;
+        let text = &caps[3];
+        // {1}{1}23.976 declares the frame rate the file was made for. we go with
+        // what we were told, but it isn't a subtitle either way.
+        if &caps[1] == "1" && &caps[2] == "1" && text.trim().parse::<f64>().is_ok() {
+            return Ok(None);
+        }
+        let start = ms(&caps[1])?;
+        let end = match &caps[2] {
+            "" => None,
+            x => Some(ms(x)?),
+        };
+        Ok(Some((start, end, convert_text(text))))
+    });
+    Ok(track_from_events(close_open_ends(lines)))
+}
+
+fn convert_text(s: &str) -> String {
+    lazy_static! {
+        static ref CODE_RE: Regex = Regex::new(r"\{([A-Za-z]):([^}]*)\}").unwrap();
+    }
+    let mut global = String::new();
+    let mut lines = Vec::<(String, String)>::new();
+    for line in s.split('|') {
+        let mut local = String::new();
+        for caps in CODE_RE.captures_iter(line) {
+            let code = caps[1].chars().next().unwrap();
+            if let Some(tags) = control_code_to_tags(code.to_ascii_lowercase(), caps[2].trim()) {
+                if code.is_ascii_uppercase() {
+                    global.push_str(&tags);
+                } else {
+                    local.push_str(&tags);
+                }
+            }
+        }
+        let mut text = CODE_RE.replace_all(line, "").into_owned();
+        if let Some(rest) = text.strip_prefix('/') {
+            local.push_str("\\i1");
+            text = rest.trim_start().to_owned();
+        }
+        lines.push((local, text));
+    }
+
+    // local tags have to be undone before the next line. \r drops everything,
+    // so the global ones get written again after it.
+    let mut out = String::new();
+    let mut dirty = false;
+    for (n, (local, text)) in lines.iter().enumerate() {
+        let mut tags = String::new();
+        if n > 0 {
+            out.push_str("\\N");
+            if dirty {
+                tags.push_str("\\r");
+                tags.push_str(&global);
+            }
+        } else {
+            tags.push_str(&global);
+        }
+        tags.push_str(local);
+        if !tags.is_empty() {
+            out.push_str(&format!("{{{}}}", tags));
+        }
+        out.push_str(text);
+        dirty = !local.is_empty();
+    }
+    out
+}
+
+fn control_code_to_tags(code: char, value: &str) -> Option<String> {
+    Some(match code {
+        'y' => value
+            .split(',')
+            .filter_map(|x| match x.trim() {
+                "i" => Some("\\i1"),
+                "b" => Some("\\b1"),
+                "u" => Some("\\u1"),
+                "s" => Some("\\s1"),
+                _ => None,
+            })
+            .collect(),
+        // microdvd colours are already in ass byte order.
+        'c' => {
+            let bgr = value.strip_prefix('$')?;
+            if bgr.len() != 6 || u32::from_str_radix(bgr, 16).is_err() {
+                return None;
+            }
+            format!("\\c&H{}&", bgr.to_ascii_uppercase())
+        }
+        'f' if !value.is_empty() => format!("\\fn{}", value),
+        's' => format!("\\fs{}", value.parse::<u32>().ok()?),
+        'p' => {
+            let (x, y) = value.split_once(',')?;
+            let x: i32 = x.trim().parse().ok()?;
+            let y: i32 = y.trim().parse().ok()?;
+            format!("\\pos({},{})", x, y)
+        }
+        _ => return None,
+    })
+}
@@ -0,0 +1,29 @@
+// mpl2: [start][end]text with times in deciseconds, lines split with |.
+
+use super::FormatError::{self, BadMpl2Line};
+use super::{close_open_ends, join_pipe_lines, parse_lines, track_from_events};
+use crate::ass::AssTrack;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+pub fn parse_track(s: &str) -> Result<AssTrack<'static>, FormatError> {
+    lazy_static! {
+        static ref LINE_RE: Regex = Regex::new(r"^\[(\d+)\]\[(\d*)\](.*)$").unwrap();
+    }
+    let ms = |ds: &str| -> Result<u32, FormatError> {
+        ds.parse::<u32>()
+            .ok()
+            .and_then(|x| x.checked_mul(100))
+            .ok_or(BadMpl2Line)
+    };
+    let lines = parse_lines(s, |line| {
+        let caps = LINE_RE.captures(line).ok_or(BadMpl2Line)?;
+        let start = ms(&caps[1])?;
+        let end = match &caps[2] {
+            "" => None,
+            x => Some(ms(x)?),
+        };
+        Ok(Some((start, end, join_pipe_lines(&caps[3]))))
+    });
+    Ok(track_from_events(close_open_ends(lines)))
+}
@@ -0,0 +1,168 @@
+// the shared part of the localization formats (po, xliff). every dialogue
+// line becomes a unit whose text has its override blocks swapped for
+// numbered placeholders, so translators can move them around but can't
+// break them. \N is a real line break in the unit text.
+//
+// units are keyed by the event's index, so translations have to be merged
+// into the script they were exported from. a unit whose source text no
+// longer matches the event is skipped.
+
+use super::Warning;
+use crate::ass::{
+    common::Timecode,
+    text::{self, Segment},
+    AssTrack,
+};
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Piece {
+    Text(String),
+    // placeholders are numbered from 1 within a unit.
+    Code(usize),
+}
+
+pub(super) struct Unit {
+    pub id: usize,
+    pub source: Vec<Piece>,
+    // the override blocks, braces included, in placeholder order.
+    pub codes: Vec<String>,
+    pub style: String,
+    pub actor: String,
+    pub start: Timecode,
+    pub end: Timecode,
+}
+impl Unit {
+    pub fn time(&self) -> String {
+        format!("{} --> {}", self.start, self.end)
+    }
+}
+
+fn pieces(s: &str) -> (Vec<Piece>, Vec<String>) {
+    let mut pieces = Vec::new();
+    let mut codes = Vec::new();
+    for segment in text::segments(s) {
+        match segment {
+            Segment::Override(block) => {
+                codes.push(format!("{{{}}}", block));
+                pieces.push(Piece::Code(codes.len()));
+            }
+            Segment::Text(t) => pieces.push(Piece::Text(t.replace("\\N", "\n"))),
+        }
+    }
+    (pieces, codes)
+}
+
+// merges neighbouring text, which is what comes back after a round trip.
+pub(super) fn normalize(pieces: Vec<Piece>) -> Vec<Piece> {
+    let mut res: Vec<Piece> = Vec::new();
+    for piece in pieces {
+        match (res.last_mut(), piece) {
+            (_, Piece::Text(t)) if t.is_empty() => (),
+            (Some(Piece::Text(a)), Piece::Text(b)) => a.push_str(&b),
+            (_, piece) => res.push(piece),
+        }
+    }
+    res
+}
+
+pub(super) fn units(track: &AssTrack) -> Vec<Unit> {
+    let mut res = Vec::new();
+    for (id, event) in track.events.iter().enumerate() {
+        let t = event.text.as_deref().unwrap_or("");
+        if event.descriptor == "Comment" || text::plain_text(t).trim().is_empty() {
+            continue;
+        }
+        let (source, codes) = pieces(t);
+        res.push(Unit {
+            id,
+            source: normalize(source),
+            codes,
+            style: event.style.as_deref().unwrap_or("").to_owned(),
+            actor: event.actor.as_deref().unwrap_or("").to_owned(),
+            start: event.start_time,
+            end: event.end_time,
+        });
+    }
+    res
+}
+
+pub(super) struct Translation {
+    pub id: String,
+    // None if the format didn't carry the source along.
+    pub source: Option<Vec<Piece>>,
+    pub target: Vec<Piece>,
+}
+
+pub(super) fn merge<'a>(
+    track: &AssTrack<'a>,
+    translations: Vec<Translation>,
+) -> (AssTrack<'a>, Vec<Warning>) {
+    let units = units(track);
+    let mut res = track.clone();
+    let mut warnings = Vec::new();
+    for t in translations {
+        let unit =
+            t.id.trim()
+                .parse::<usize>()
+                .ok()
+                .and_then(|id| units.iter().find(|x| x.id == id));
+        let unit = match unit {
+            Some(x) => x,
+            None => {
+                warnings.push(Warning {
+                    event: None,
+                    message: format!("unit {:?} doesn't match any event, skipped", t.id),
+                });
+                continue;
+            }
+        };
+        let mut warn = |message: String| {
+            warnings.push(Warning {
+                event: Some(unit.id),
+                message,
+            })
+        };
+        if t.source.is_some_and(|x| normalize(x) != unit.source) {
+            warn("was exported from different text, skipped".to_owned());
+            continue;
+        }
+        let target = normalize(t.target);
+        if target.is_empty() {
+            continue;
+        }
+
+        let mut out = String::new();
+        let mut used = vec![false; unit.codes.len()];
+        for piece in &target {
+            match piece {
+                Piece::Text(x) => out.push_str(&x.replace('\n', "\\N")),
+                Piece::Code(n) => match unit.codes.get(n.wrapping_sub(1)) {
+                    Some(code) => {
+                        out.push_str(code);
+                        used[n - 1] = true;
+                    }
+                    None => warn(format!("placeholder {} doesn't exist, dropped", n)),
+                },
+            }
+        }
+        // anything the translator lost goes at the start, which is where
+        // most of the tags that matter (positioning, \an) live anyway.
+        let missing: Vec<&str> = unit
+            .codes
+            .iter()
+            .zip(&used)
+            .filter(|x| !x.1)
+            .map(|x| x.0.as_str())
+            .collect();
+        if !missing.is_empty() {
+            warn(format!(
+                "{} placeholder(s) missing from the translation, put at the start",
+                missing.len()
+            ));
+            out = missing.concat() + &out;
+        }
+        res.events[unit.id].text = Some(Cow::Owned(out));
+    }
+    (res, warnings)
+}
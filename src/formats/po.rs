@@ -0,0 +1,192 @@
+// gettext .po. each dialogue line is an entry with the event index as its
+// msgctxt, and the style, actor, time and what each placeholder stands for
+// as extracted comments. placeholders look like {1}.
+//
+// fuzzy entries aren't merged, same as msgfmt.
+
+use super::placeholders::{self, Piece, Translation};
+use super::FormatError::BadPoLine;
+use super::Warning;
+use crate::ass::AssTrack;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+fn to_po(pieces: &[Piece]) -> String {
+    pieces
+        .iter()
+        .map(|x| match x {
+            Piece::Text(t) => t.clone(),
+            Piece::Code(n) => format!("{{{}}}", n),
+        })
+        .collect()
+}
+
+fn from_po(s: &str) -> Vec<Piece> {
+    lazy_static! {
+        static ref CODE_RE: Regex = Regex::new(r"\{(\d+)\}").unwrap();
+    }
+    let mut res = Vec::new();
+    let mut last = 0;
+    for caps in CODE_RE.captures_iter(s) {
+        let m = caps.get(0).unwrap();
+        res.push(Piece::Text(s[last..m.start()].to_owned()));
+        res.push(Piece::Code(caps[1].parse().unwrap_or(0)));
+        last = m.end();
+    }
+    res.push(Piece::Text(s[last..].to_owned()));
+    placeholders::normalize(res)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some(c) => out.push(c),
+            None => (),
+        }
+    }
+    out
+}
+
+// multi-line strings start with an empty one and break after each \n.
+fn po_string(keyword: &str, s: &str) -> String {
+    if !s.contains('\n') || s == "\n" {
+        return format!("{} \"{}\"\n", keyword, escape(s));
+    }
+    let mut out = format!("{} \"\"\n", keyword);
+    for line in s.split_inclusive('\n') {
+        out.push_str(&format!("\"{}\"\n", escape(line)));
+    }
+    out
+}
+
+pub fn write_po(track: &AssTrack) -> String {
+    let mut out = String::from("msgid \"\"\nmsgstr \"\"\n");
+    out.push_str("\"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+    out.push_str("\"Content-Transfer-Encoding: 8bit\\n\"\n");
+    if let Some(title) = &track.header.title {
+        out.push_str(&format!("\"Project-Id-Version: {}\\n\"\n", escape(title)));
+    }
+    for unit in placeholders::units(track) {
+        out.push('\n');
+        out.push_str(&format!("#. Style: {}\n", unit.style));
+        if !unit.actor.is_empty() {
+            out.push_str(&format!("#. Actor: {}\n", unit.actor));
+        }
+        out.push_str(&format!("#. Time: {}\n", unit.time()));
+        for (n, code) in unit.codes.iter().enumerate() {
+            out.push_str(&format!("#. {{{}}} = {}\n", n + 1, code));
+        }
+        out.push_str(&po_string("msgctxt", &unit.id.to_string()));
+        out.push_str(&po_string("msgid", &to_po(&unit.source)));
+        out.push_str("msgstr \"\"\n");
+    }
+    out
+}
+
+#[derive(Default)]
+struct Entry {
+    fuzzy: bool,
+    context: Option<String>,
+    id: Option<String>,
+    string: Option<String>,
+}
+
+fn parse_entries(s: &str) -> Vec<Entry> {
+    lazy_static! {
+        static ref LINE_RE: Regex =
+            Regex::new(r#"^(msgctxt|msgid|msgid_plural|msgstr(?:\[\d+\])?)?\s*"(.*)"$"#).unwrap();
+    }
+    let mut entries = Vec::new();
+    let mut entry = Entry::default();
+    // which field continuation lines go to.
+    let mut field = "";
+    for (line_n, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("#~") {
+            continue;
+        }
+        // comments belong to the entry after them.
+        if line.starts_with('#') && entry.string.is_some() {
+            entries.push(std::mem::take(&mut entry));
+        }
+        if line.starts_with("#,") {
+            entry.fuzzy |= line.contains("fuzzy");
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        let caps = match LINE_RE.captures(line) {
+            Some(x) => x,
+            None => {
+                eprintln!("Dropped line {}: {}", line_n, BadPoLine);
+                continue;
+            }
+        };
+        let value = unescape(&caps[2]);
+        if let Some(keyword) = caps.get(1) {
+            field = keyword.as_str();
+            // a new msgctxt or msgid after a msgstr starts the next entry.
+            if matches!(field, "msgctxt" | "msgid") && entry.string.is_some() {
+                entries.push(std::mem::take(&mut entry));
+            }
+        }
+        let target = match field {
+            "msgctxt" => &mut entry.context,
+            "msgid" => &mut entry.id,
+            // plurals don't happen here, the first form will do.
+            "msgstr" | "msgstr[0]" => &mut entry.string,
+            _ => continue,
+        };
+        target.get_or_insert_with(String::new).push_str(&value);
+    }
+    if entry.string.is_some() {
+        entries.push(entry);
+    }
+    entries
+}
+
+// returns a copy of track with the translations in, and what didn't go in
+// cleanly.
+pub fn merge_po<'a>(track: &AssTrack<'a>, s: &str) -> (AssTrack<'a>, Vec<Warning>) {
+    let mut skipped = Vec::new();
+    let mut translations = Vec::new();
+    for entry in parse_entries(s) {
+        let id = match (entry.context, entry.id) {
+            (Some(ctx), Some(id)) if !id.is_empty() => (ctx, id),
+            // the header entry, or something we didn't write.
+            _ => continue,
+        };
+        if entry.fuzzy {
+            skipped.push(Warning {
+                event: id.0.parse().ok(),
+                message: "translation is fuzzy, skipped".to_owned(),
+            });
+            continue;
+        }
+        translations.push(Translation {
+            id: id.0,
+            source: Some(from_po(&id.1)),
+            target: from_po(entry.string.as_deref().unwrap_or("")),
+        });
+    }
+    let (res, mut warnings) = placeholders::merge(track, translations);
+    warnings.extend(skipped);
+    (res, warnings)
+}
@@ -0,0 +1,432 @@
+// sami (.smi): html-ish markup where each <SYNC Start=ms> block holds one <P>
+// per CLASS. classes are usually languages, declared in the <STYLE> block as
+// .KRCC { Name: Korean; lang: ko-KR; }. a class stays on screen until the
+// next sync that has a <P> for it, which is normally a &nbsp; one.
+
+use super::FormatError::{self, BadSamiDocument};
+use super::{
+    ass_to_html, css_colour, decode_entities, escape_html, html_colour, html_to_ass,
+    track_from_events, OPEN_ENDED_DURATION,
+};
+use crate::ass::{
+    common::{Alignment, Timecode},
+    event::Event,
+    style::Style,
+    text, AssTrack,
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::borrow::Cow;
+
+type Declarations = Vec<(String, String)>;
+
+struct Class {
+    // lowercased, markup doesn't care about case.
+    id: String,
+    declarations: Declarations,
+}
+impl Class {
+    fn get(&self, property: &str) -> Option<&str> {
+        self.declarations
+            .iter()
+            .rev()
+            .find(|x| x.0 == property)
+            .map(|x| x.1.as_str())
+    }
+    fn name(&self) -> &str {
+        match self.get("name") {
+            Some(x) => x,
+            None if self.id.is_empty() => "Default",
+            None => &self.id,
+        }
+    }
+}
+
+struct Document {
+    title: Option<String>,
+    // declarations from the P rule, which every class starts from.
+    base: Declarations,
+    classes: Vec<Class>,
+    // (class, start, end, text)
+    lines: Vec<(usize, u32, u32, String)>,
+}
+
+// one style per class.
+pub fn parse_track(s: &str) -> Result<AssTrack<'static>, FormatError> {
+    let doc = parse_document(s)?;
+    let styles: Vec<Style> = doc
+        .classes
+        .iter()
+        .filter(|c| doc.lines.iter().any(|x| doc.classes[x.0].id == c.id))
+        .map(|c| style_from_css(c.name(), &[&doc.base, &c.declarations]))
+        .collect();
+    let events = doc
+        .lines
+        .iter()
+        .map(|(c, start, end, text)| dialogue(doc.classes[*c].name(), *start, *end, text))
+        .collect();
+    let mut track = track_from_events(events);
+    if !styles.is_empty() {
+        track.styles = styles;
+    }
+    track.header.title = doc.title.map(Cow::Owned);
+    Ok(track)
+}
+
+// one track per class, with Language taken from the class's lang.
+pub fn parse_tracks(s: &str) -> Result<Vec<AssTrack<'static>>, FormatError> {
+    let doc = parse_document(s)?;
+    let mut res = Vec::new();
+    for (n, class) in doc.classes.iter().enumerate() {
+        let events: Vec<Event> = doc
+            .lines
+            .iter()
+            .filter(|x| x.0 == n)
+            .map(|(_, start, end, text)| dialogue(class.name(), *start, *end, text))
+            .collect();
+        if events.is_empty() {
+            continue;
+        }
+        let mut track = track_from_events(events);
+        track.styles = vec![style_from_css(
+            class.name(),
+            &[&doc.base, &class.declarations],
+        )];
+        track.header.title = doc.title.clone().map(Cow::Owned);
+        track.header.language = class.get("lang").map(|x| Cow::Owned(x.to_owned()));
+        res.push(track);
+    }
+    Ok(res)
+}
+
+fn dialogue(style: &str, start: u32, end: u32, text: &str) -> Event<'static> {
+    Event {
+        start_time: Timecode::from(start),
+        end_time: Timecode::from(end),
+        style: Some(Cow::Owned(style.to_owned())),
+        text: Some(Cow::Owned(text.to_owned())),
+        ..Event::default()
+    }
+}
+
+fn parse_document(s: &str) -> Result<Document, FormatError> {
+    lazy_static! {
+        static ref TITLE_RE: Regex = Regex::new(r"(?is)<title>(.*?)</title>").unwrap();
+        static ref STYLE_RE: Regex = Regex::new(r"(?is)<style[^>]*>(.*?)</style>").unwrap();
+        static ref RULE_RE: Regex = Regex::new(r"([^{}]+)\{([^}]*)\}").unwrap();
+        static ref SYNC_RE: Regex = Regex::new(r"(?is)<sync\b([^>]*)>").unwrap();
+        static ref START_RE: Regex = Regex::new(r#"(?i)\bstart\s*=\s*"?(\d+)"#).unwrap();
+        static ref P_RE: Regex = Regex::new(r"(?is)<p\b([^>]*)>").unwrap();
+        static ref CLASS_RE: Regex = Regex::new(r#"(?i)\bclass\s*=\s*"?([\w-]+)"#).unwrap();
+        static ref END_RE: Regex = Regex::new(r"(?is)</(p|body|sami)\s*>").unwrap();
+    }
+    let mut doc = Document {
+        title: TITLE_RE
+            .captures(s)
+            .map(|x| decode_entities(x[1].trim()))
+            .filter(|x| !x.is_empty()),
+        base: Vec::new(),
+        classes: Vec::new(),
+        lines: Vec::new(),
+    };
+
+    if let Some(caps) = STYLE_RE.captures(s) {
+        let css = caps[1].replace("<!--", "").replace("-->", "");
+        for rule in RULE_RE.captures_iter(&css) {
+            let declarations = parse_declarations(&rule[2]);
+            for selector in rule[1].split(',') {
+                let selector = selector.trim().to_ascii_lowercase();
+                if selector == "p" {
+                    doc.base.extend(declarations.iter().cloned());
+                } else if let Some(id) = selector.strip_prefix('.') {
+                    let n = class_index(&mut doc.classes, id);
+                    doc.classes[n]
+                        .declarations
+                        .extend(declarations.iter().cloned());
+                }
+            }
+        }
+    }
+
+    let mut syncs = Vec::<(u32, &str)>::new();
+    let matches: Vec<_> = SYNC_RE.captures_iter(s).collect();
+    for (n, caps) in matches.iter().enumerate() {
+        let tag = caps.get(0).unwrap();
+        let end = matches
+            .get(n + 1)
+            .map_or(s.len(), |x| x.get(0).unwrap().start());
+        let body = &s[tag.end()..end];
+        let body = END_RE
+            .find_iter(body)
+            .find(|x| !x.as_str().to_ascii_lowercase().starts_with("</p"))
+            .map_or(body, |x| &body[..x.start()]);
+        match START_RE.captures(&caps[1]) {
+            Some(start) => syncs.push((start[1].parse().or(Err(BadSamiDocument))?, body)),
+            None => eprintln!("Dropped <SYNC> without a Start time"),
+        }
+    }
+    if syncs.is_empty() {
+        return Err(BadSamiDocument);
+    }
+    syncs.sort_by_key(|x| x.0);
+
+    // whatever is showing for each class, waiting for its end time.
+    let mut showing = Vec::<Option<(u32, String)>>::new();
+    for (start, body) in syncs {
+        let mut paragraphs = Vec::<(usize, String)>::new();
+        let ps: Vec<_> = P_RE.captures_iter(body).collect();
+        if ps.is_empty() {
+            // a bare sync clears everything.
+            for n in 0..doc.classes.len() {
+                paragraphs.push((n, String::new()));
+            }
+        }
+        for (n, caps) in ps.iter().enumerate() {
+            let tag = caps.get(0).unwrap();
+            let end = ps
+                .get(n + 1)
+                .map_or(body.len(), |x| x.get(0).unwrap().start());
+            let content = &body[tag.end()..end];
+            let content = END_RE
+                .find(content)
+                .map_or(content, |x| &content[..x.start()]);
+            let id = CLASS_RE
+                .captures(&caps[1])
+                .map_or(String::new(), |x| x[1].to_ascii_lowercase());
+            paragraphs.push((class_index(&mut doc.classes, &id), html_to_ass(content)));
+        }
+        showing.resize(doc.classes.len(), None);
+        for (n, text) in paragraphs {
+            if let Some((open, text)) = showing[n].take() {
+                doc.lines.push((n, open, start, text));
+            }
+            if !text::plain_text(&text).trim().is_empty() {
+                showing[n] = Some((start, text.replace('\u{a0}', "\\h")));
+            }
+        }
+    }
+    for (n, x) in showing.into_iter().enumerate() {
+        if let Some((open, text)) = x {
+            doc.lines
+                .push((n, open, open.saturating_add(OPEN_ENDED_DURATION), text));
+        }
+    }
+    doc.lines.sort_by_key(|x| x.1);
+    Ok(doc)
+}
+
+fn class_index(classes: &mut Vec<Class>, id: &str) -> usize {
+    match classes.iter().position(|x| x.id == id) {
+        Some(n) => n,
+        None => {
+            classes.push(Class {
+                id: id.to_owned(),
+                declarations: Vec::new(),
+            });
+            classes.len() - 1
+        }
+    }
+}
+
+fn parse_declarations(s: &str) -> Declarations {
+    s.split(';')
+        .filter_map(|x| x.split_once(':'))
+        .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_owned()))
+        .collect()
+}
+
+fn style_from_css(name: &str, declarations: &[&Declarations]) -> Style<'static> {
+    let length = |x: &str| {
+        x.trim_end_matches(|c: char| c.is_ascii_alphabetic())
+            .trim()
+            .parse::<f64>()
+            .ok()
+    };
+    let mut style = Style {
+        name: Cow::Owned(name.to_owned()),
+        ..Style::default()
+    };
+    for (property, value) in declarations.iter().flat_map(|x| x.iter()) {
+        let lower = value.to_ascii_lowercase();
+        match property.as_str() {
+            "font-family" => {
+                if let Some(x) = value.split(',').next() {
+                    style.font_name =
+                        Cow::Owned(x.trim().trim_matches(|c| c == '"' || c == '\'').to_owned());
+                }
+            }
+            "font-size" => {
+                if let Some(x) = length(value) {
+                    style.font_size = x.round() as u32;
+                }
+            }
+            "color" => {
+                if let Some(x) = css_colour(value) {
+                    style.primary_colour = x;
+                }
+            }
+            "background-color" => {
+                if let Some(x) = css_colour(value) {
+                    style.back_colour = x;
+                }
+            }
+            "font-weight" => {
+                style.bold = lower == "bold" || lower.parse::<u32>().is_ok_and(|x| x >= 600)
+            }
+            "font-style" => style.italic = lower == "italic" || lower == "oblique",
+            "text-decoration" => {
+                style.underline = lower.contains("underline");
+                style.strikeout = lower.contains("line-through");
+            }
+            "text-align" => {
+                style.alignment = match lower.as_str() {
+                    "left" => Alignment::BottomLeft,
+                    "right" => Alignment::BottomRight,
+                    _ => Alignment::BottomCenter,
+                }
+            }
+            "margin-left" => style.margin_l = length(value).map_or(style.margin_l, |x| x as i32),
+            "margin-right" => style.margin_r = length(value).map_or(style.margin_r, |x| x as i32),
+            "margin-bottom" => style.margin_v = length(value).map_or(style.margin_v, |x| x as i32),
+            _ => (),
+        }
+    }
+    style
+}
+
+// css has no escaping a reader could be counted on to undo, so whatever
+// would end the declaration, the rule or the <STYLE> block goes.
+fn css_value(s: &str) -> String {
+    s.replace([';', '{', '}', '<', '>'], " ").trim().to_owned()
+}
+
+fn style_to_css(style: &Style) -> String {
+    let align = match u8::from(style.alignment) % 3 {
+        1 => "left",
+        0 => "right",
+        _ => "center",
+    };
+    let decoration = match (style.underline, style.strikeout) {
+        (true, true) => "underline line-through",
+        (true, false) => "underline",
+        (false, true) => "line-through",
+        (false, false) => "none",
+    };
+    format!(
+        "font-family: {}; font-size: {}pt; color: {}; font-weight: {}; font-style: {}; \
+         text-decoration: {}; text-align: {}; margin-left: {}pt; margin-right: {}pt; \
+         margin-bottom: {}pt;",
+        css_value(&style.font_name),
+        style.font_size,
+        html_colour(style.primary_colour),
+        if style.bold { "bold" } else { "normal" },
+        if style.italic { "italic" } else { "normal" },
+        decoration,
+        align,
+        style.margin_l,
+        style.margin_r,
+        style.margin_v
+    )
+}
+
+// each style becomes a class, all of them in the track's language.
+pub fn write_track(track: &AssTrack) -> String {
+    let default_style = [Style::default()];
+    let styles = match track.styles.is_empty() {
+        true => &default_style[..],
+        false => &track.styles[..],
+    };
+    let mut ids = Vec::<String>::new();
+    for style in styles {
+        let base: String = style
+            .name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect();
+        let base = if base.is_empty() {
+            "Style".to_owned()
+        } else {
+            base
+        };
+        let mut id = base.clone();
+        let mut n = 1;
+        while ids.iter().any(|x| x.eq_ignore_ascii_case(&id)) {
+            n += 1;
+            id = format!("{}{}", base, n);
+        }
+        ids.push(id);
+    }
+    let lang = track.header.language.as_deref().unwrap_or("en-US");
+
+    let mut out = vec!["<SAMI>".to_owned(), "<HEAD>".to_owned()];
+    if let Some(title) = &track.header.title {
+        out.push(format!("<TITLE>{}</TITLE>", escape_html(title)));
+    }
+    out.push("<STYLE TYPE=\"text/css\">".to_owned());
+    out.push("<!--".to_owned());
+    let base = styles
+        .iter()
+        .find(|x| x.name == "Default")
+        .unwrap_or(&styles[0]);
+    out.push(format!("P {{ {} }}", style_to_css(base)));
+    for (style, id) in styles.iter().zip(&ids) {
+        out.push(format!(
+            ".{} {{ Name: {}; lang: {}; SAMIType: CC; {} }}",
+            id,
+            css_value(&style.name),
+            css_value(lang),
+            style_to_css(style)
+        ));
+    }
+    out.push("-->".to_owned());
+    out.push("</STYLE>".to_owned());
+    out.push("</HEAD>".to_owned());
+    out.push("<BODY>".to_owned());
+
+    let lines: Vec<(usize, u32, u32, String)> = track
+        .events
+        .iter()
+        .filter(|x| x.descriptor != "Comment" && x.end_time.0 > x.start_time.0)
+        .map(|x| {
+            let class = styles
+                .iter()
+                .position(|s| Some(&s.name) == x.style.as_ref())
+                .unwrap_or(0);
+            let text = ass_to_html(x.text.as_deref().unwrap_or(""), true);
+            (
+                class,
+                x.start_time.as_millis(),
+                x.end_time.as_millis(),
+                text,
+            )
+        })
+        .collect();
+    let mut times: Vec<u32> = lines.iter().flat_map(|x| vec![x.1, x.2]).collect();
+    times.sort_unstable();
+    times.dedup();
+
+    let mut showing = vec![String::new(); ids.len()];
+    for t in times {
+        let mut ps = Vec::new();
+        for (n, id) in ids.iter().enumerate() {
+            let now = lines
+                .iter()
+                .filter(|x| x.0 == n && x.1 <= t && t < x.2)
+                .map(|x| x.3.as_str())
+                .collect::<Vec<&str>>()
+                .join("<br>");
+            if now != showing[n] {
+                let shown = if now.is_empty() { "&nbsp;" } else { &now };
+                ps.push(format!("<P Class={}>{}", id, shown));
+                showing[n] = now;
+            }
+        }
+        if !ps.is_empty() {
+            out.push(format!("<SYNC Start={}>", t));
+            out.append(&mut ps);
+        }
+    }
+    out.push("</BODY>".to_owned());
+    out.push("</SAMI>".to_owned());
+    out.join("\n") + "\n"
+}
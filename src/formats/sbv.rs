@@ -0,0 +1,79 @@
+// youtube .sbv: a 0:00:01.000,0:00:03.500 line, then the text, then a blank
+// line. no styling at all.
+
+use super::FormatError::{self, BadSbvBlock};
+use super::{dialogue, track_from_events};
+use crate::ass::{common::Timecode, text, AssTrack};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+// hours have no upper bound, so they can be too many for a u32 of ms.
+fn parse_time(caps: &regex::Captures, from: usize) -> Option<u32> {
+    let n = |i: usize| caps[from + i].parse::<u32>().ok();
+    n(0)?
+        .checked_mul(3_600_000)?
+        .checked_add(n(1)? * 60_000 + n(2)? * 1000 + n(3)?)
+}
+
+pub fn parse_track(s: &str) -> Result<AssTrack<'static>, FormatError> {
+    lazy_static! {
+        static ref TIME_RE: Regex =
+            Regex::new(r"^(\d+):(\d{2}):(\d{2})\.(\d{3}),(\d+):(\d{2}):(\d{2})\.(\d{3})$").unwrap();
+    }
+    let mut events = Vec::new();
+    let mut block = Vec::<(usize, &str)>::new();
+    let lines = s.lines().map(|x| x.trim()).chain(std::iter::once(""));
+    for (line_n, line) in lines.enumerate() {
+        if !line.is_empty() {
+            block.push((line_n, line));
+            continue;
+        }
+        if block.is_empty() {
+            continue;
+        }
+        let times = TIME_RE
+            .captures(block[0].1)
+            .and_then(|caps| Some((parse_time(&caps, 1)?, parse_time(&caps, 5)?)));
+        match times {
+            Some((start, end)) => {
+                let text: Vec<&str> = block[1..].iter().map(|x| x.1).collect();
+                events.push(dialogue(start, end, text.join("\\N")));
+            }
+            None => eprintln!("Dropped block at line {}: {}", block[0].0, BadSbvBlock),
+        }
+        block.clear();
+    }
+    Ok(track_from_events(events))
+}
+
+pub fn write_track(track: &AssTrack) -> String {
+    let time = |t: Timecode| {
+        let ms = t.as_millis();
+        format!(
+            "{}:{:02}:{:02}.{:03}",
+            ms / 3_600_000,
+            ms / 60_000 % 60,
+            ms / 1000 % 60,
+            ms % 1000
+        )
+    };
+    let mut out = Vec::new();
+    for event in track.events.iter().filter(|x| x.descriptor != "Comment") {
+        let text = text::plain_text(event.text.as_deref().unwrap_or("")).replace('\u{a0}', " ");
+        let lines: Vec<&str> = text
+            .lines()
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .collect();
+        if lines.is_empty() {
+            continue;
+        }
+        out.push(format!(
+            "{},{}\n{}\n",
+            time(event.start_time),
+            time(event.end_time),
+            lines.join("\n")
+        ));
+    }
+    out.join("\n")
+}
@@ -0,0 +1,459 @@
+// scenarist .scc: cea-608 byte pairs as hex, one line per burst, each line
+// stamped with the 29.97fps drop-frame timecode it starts at. every pair
+// takes a frame to send.
+//
+// captions are pop-on: loaded into the hidden buffer (RCL, ENM, then a PAC
+// per row followed by the characters) and swapped onto the screen with EOC,
+// which is timed to land on the event's start. EDM clears the screen at the
+// end unless the next caption is about to replace it anyway. control codes
+// are always sent twice, decoders drop the repeat.
+
+use super::Warning;
+use crate::ass::{
+    common::Timecode,
+    style::Style,
+    text::{self, Segment},
+    AssTrack,
+};
+
+const COLUMNS: usize = 32;
+const ROWS: usize = 15;
+// more rows than this and a lot of decoders start cutting things off.
+const MAX_CAPTION_ROWS: usize = 4;
+
+// first byte and second byte base of the preamble address code for each row.
+const PAC_ROWS: [(u8, u8); ROWS] = [
+    (0x11, 0x40),
+    (0x11, 0x60),
+    (0x12, 0x40),
+    (0x12, 0x60),
+    (0x15, 0x40),
+    (0x15, 0x60),
+    (0x16, 0x40),
+    (0x16, 0x60),
+    (0x17, 0x40),
+    (0x17, 0x60),
+    (0x10, 0x40),
+    (0x13, 0x40),
+    (0x13, 0x60),
+    (0x14, 0x40),
+    (0x14, 0x60),
+];
+
+const RCL: (u8, u8) = (0x14, 0x20);
+const ENM: (u8, u8) = (0x14, 0x2e);
+const EOC: (u8, u8) = (0x14, 0x2f);
+const EDM: (u8, u8) = (0x14, 0x2c);
+const MID_ROW_WHITE: (u8, u8) = (0x11, 0x20);
+const MID_ROW_ITALIC: (u8, u8) = (0x11, 0x2e);
+const PAC_ITALIC: u8 = 0x0e;
+const PAC_INDENT: u8 = 0x10;
+const TAB_OFFSET: u8 = 0x17;
+
+// the few places the basic set isn't ascii.
+const BASIC: [(char, u8); 10] = [
+    ('á', 0x2a),
+    ('é', 0x5c),
+    ('í', 0x5e),
+    ('ó', 0x5f),
+    ('ú', 0x60),
+    ('ç', 0x7b),
+    ('÷', 0x7c),
+    ('Ñ', 0x7d),
+    ('ñ', 0x7e),
+    ('█', 0x7f),
+];
+// 0x11 0x30 onwards. 0x39 is the transparent space, which nobody types.
+const SPECIAL: &str = "®°½¿™¢£♪à\u{0}èâêîôû";
+// 0x12 0x20 and 0x13 0x20 onwards. these replace the character before them,
+// so a decoder that doesn't know them still shows something close.
+const EXTENDED_12: &str = "ÁÉÓÚÜü‘¡*’—©℠•“”ÀÂÇÈÊËëÎÏïÔÙùÛ«»";
+const EXTENDED_12_FALLBACK: &str = "AEOUUu'!.'-cs.\"\"AACEEEeIIiOUuU\"\"";
+const EXTENDED_13: &str = "ÃãÍÌìÒòÕõ{}\\^_|~ÄäÖöß¥¤¦ÅåØø┌┐└┘";
+const EXTENDED_13_FALLBACK: &str = "AaIIiOoOo()/'-:-AaOosYo:AaOo++++";
+
+#[derive(Debug, Clone, Copy)]
+enum Cell {
+    Basic(u8),
+    Special(u8),
+    // (first byte, second byte, fallback)
+    Extended(u8, u8, u8),
+}
+
+fn map_char(c: char) -> Option<Cell> {
+    if let Some(x) = BASIC.iter().find(|x| x.0 == c) {
+        return Some(Cell::Basic(x.1));
+    }
+    if (' '..='~').contains(&c) && !BASIC.iter().any(|x| x.1 == c as u8) {
+        return Some(Cell::Basic(c as u8));
+    }
+    if let Some(n) = SPECIAL.chars().position(|x| x == c) {
+        return Some(Cell::Special(0x30 + n as u8));
+    }
+    for (first, table, fallback) in &[
+        (0x12, EXTENDED_12, EXTENDED_12_FALLBACK),
+        (0x13, EXTENDED_13, EXTENDED_13_FALLBACK),
+    ] {
+        if let Some(n) = table.chars().position(|x| x == c) {
+            let fallback = fallback.as_bytes()[n];
+            return Some(Cell::Extended(*first, 0x20 + n as u8, fallback));
+        }
+    }
+    None
+}
+
+fn substitute(c: char) -> &'static str {
+    match c {
+        '…' => "...",
+        '–' | '‐' | '‑' => "-",
+        '‚' => ",",
+        '„' => "\"",
+        '×' => "x",
+        _ => "?",
+    }
+}
+
+// one word of a row, with whether it's italic.
+type Word = (Vec<Cell>, bool);
+
+struct Caption {
+    start: Timecode,
+    end: Timecode,
+    words: Vec<[u8; 2]>,
+}
+
+// cea-608 bytes are 7 bits plus odd parity.
+fn parity(b: u8) -> u8 {
+    if b.count_ones() % 2 == 1 {
+        b
+    } else {
+        b | 0x80
+    }
+}
+
+#[derive(Default)]
+struct Encoder {
+    words: Vec<[u8; 2]>,
+    pending: Option<u8>,
+}
+impl Encoder {
+    fn control(&mut self, (a, b): (u8, u8)) {
+        self.flush();
+        let word = [parity(a), parity(b)];
+        self.words.push(word);
+        self.words.push(word);
+    }
+    fn char(&mut self, b: u8) {
+        match self.pending.take() {
+            Some(p) => self.words.push([parity(p), parity(b)]),
+            None => self.pending = Some(b),
+        }
+    }
+    fn flush(&mut self) {
+        if let Some(p) = self.pending.take() {
+            self.words.push([parity(p), parity(0)]);
+        }
+    }
+    fn cell(&mut self, cell: Cell) {
+        match cell {
+            Cell::Basic(b) => self.char(b),
+            Cell::Special(b) => self.control((0x11, b)),
+            Cell::Extended(a, b, fallback) => {
+                self.char(fallback);
+                self.control((a, b));
+            }
+        }
+    }
+}
+
+pub fn write_track(track: &AssTrack) -> (String, Vec<Warning>) {
+    let mut warnings = Vec::new();
+    let mut captions = Vec::new();
+    let default_style = Style::default();
+    for (n, event) in track.events.iter().enumerate() {
+        if event.descriptor == "Comment" || event.end_time.0 <= event.start_time.0 {
+            continue;
+        }
+        let style = track
+            .style(event.style.as_deref())
+            .unwrap_or(&default_style);
+        let mut warn = |message: String| {
+            warnings.push(Warning {
+                event: Some(n),
+                message,
+            })
+        };
+        let text = event.text.as_deref().unwrap_or("");
+        let rows = wrap(lines(text, style.italic, &mut warn));
+        if rows.is_empty() {
+            continue;
+        }
+        let rows = if rows.len() > ROWS {
+            warn(format!(
+                "{} rows don't fit on screen, dropped the last {}",
+                rows.len(),
+                rows.len() - ROWS
+            ));
+            rows[..ROWS].to_vec()
+        } else {
+            rows
+        };
+        if rows.len() > MAX_CAPTION_ROWS {
+            warn(format!(
+                "{} rows, more than the {} most decoders expect",
+                rows.len(),
+                MAX_CAPTION_ROWS
+            ));
+        }
+
+        let (res_x, res_y) = track.header.play_res();
+        let (an, pos) = text::placement(text);
+        let alignment = an.unwrap_or(style.alignment);
+        let (margin_l, margin_r, margin_v) = event.margins(style);
+        // the caption grid covers the middle 80% of the screen.
+        let (w, h) = (f64::from(res_x), f64::from(res_y));
+        let row_h = h * 0.8 / ROWS as f64;
+        let col_w = w * 0.8 / COLUMNS as f64;
+        let n_rows = rows.len() as i32;
+        let first_row = match u8::from(alignment) {
+            7..=9 => {
+                let y = pos.map_or(f64::from(margin_v), |p| p.1);
+                ((y - h * 0.1) / row_h).ceil() as i32 + 1
+            }
+            4..=6 => {
+                let y = pos.map_or(h / 2.0, |p| p.1);
+                ((y - h * 0.1) / row_h - f64::from(n_rows) / 2.0).round() as i32 + 1
+            }
+            _ => {
+                let y = pos.map_or(h - f64::from(margin_v), |p| p.1);
+                ((y - h * 0.1) / row_h).floor() as i32 - n_rows + 1
+            }
+        }
+        .clamp(1, ROWS as i32 - n_rows + 1) as usize;
+
+        let mut enc = Encoder::default();
+        enc.control(RCL);
+        enc.control(ENM);
+        for (r, row) in rows.iter().enumerate() {
+            let width = row.iter().map(|x| x.0.len()).sum::<usize>() + row.len() - 1;
+            let col = match u8::from(alignment) % 3 {
+                1 => {
+                    let x = pos.map_or(f64::from(margin_l), |p| p.0);
+                    ((x - w * 0.1) / col_w).round()
+                }
+                0 => {
+                    let x = pos.map_or(w - f64::from(margin_r), |p| p.0);
+                    ((x - w * 0.1) / col_w).round() - width as f64
+                }
+                _ => {
+                    let x = pos.map_or(w / 2.0, |p| p.0);
+                    ((x - w * 0.1) / col_w - width as f64 / 2.0).round()
+                }
+            }
+            .max(0.0)
+            .min((COLUMNS - width) as f64) as u8;
+            encode_row(&mut enc, first_row + r, col, row);
+        }
+        enc.control(EOC);
+        captions.push((
+            n,
+            Caption {
+                start: event.start_time,
+                end: event.end_time,
+                words: enc.words,
+            },
+        ));
+    }
+    captions.sort_by_key(|x| x.1.start.0);
+
+    let frame = |t: Timecode| (f64::from(t.as_millis()) * 30.0 / 1001.0).round() as u64;
+    // the frame a caption's data has to start at for EOC to land on time.
+    let load = |c: &Caption| frame(c.start).saturating_sub(c.words.len() as u64 - 2);
+    let mut lines = Vec::new();
+    let mut cursor = 0;
+    for (i, (n, caption)) in captions.iter().enumerate() {
+        let at = cursor.max(load(caption));
+        let eoc = at + caption.words.len() as u64 - 2;
+        if eoc > frame(caption.start) {
+            warnings.push(Warning {
+                event: Some(*n),
+                message: format!(
+                    "shows {} frames late, not enough time to load it",
+                    eoc - frame(caption.start)
+                ),
+            });
+        }
+        lines.push((at, caption.words.clone()));
+        cursor = at + caption.words.len() as u64;
+
+        let end = frame(caption.end);
+        let next = captions.get(i + 1).map(|x| &x.1);
+        if let Some(next) = next.filter(|x| x.start.0 < caption.end.0) {
+            warnings.push(Warning {
+                event: Some(*n),
+                message: format!(
+                    "cut short at {}, only one caption can be up at a time",
+                    next.start
+                ),
+            });
+        }
+        // no point clearing the screen if the next caption replaces it first.
+        if next.is_none_or(|x| end + 2 <= load(x)) {
+            let at = cursor.max(end);
+            let mut enc = Encoder::default();
+            enc.control(EDM);
+            lines.push((at, enc.words));
+            cursor = at + 2;
+        }
+    }
+
+    let mut out = vec!["Scenarist_SCC V1.0".to_owned()];
+    for (at, words) in lines {
+        let words: Vec<String> = words
+            .iter()
+            .map(|x| format!("{:02x}{:02x}", x[0], x[1]))
+            .collect();
+        out.push(format!("{}\t{}", drop_frame(at), words.join(" ")));
+    }
+    (out.join("\n\n") + "\n", warnings)
+}
+
+// hard line breaks split lines, spaces split words. italics only change at
+// word boundaries, where the mid-row code can stand in for the space.
+fn lines<F: FnMut(String)>(s: &str, italic: bool, warn: &mut F) -> Vec<Vec<Word>> {
+    let mut lines = vec![Vec::<Word>::new()];
+    let mut word: Option<Word> = None;
+    let mut state = italic;
+    let end_word = |lines: &mut Vec<Vec<Word>>, word: &mut Option<Word>| {
+        if let Some(w) = word.take() {
+            lines.last_mut().unwrap().push(w);
+        }
+    };
+    for segment in text::segments(s) {
+        match segment {
+            Segment::Override(block) => {
+                for tag in text::tags(block) {
+                    match tag.name {
+                        "i" => {
+                            state = match tag.arg {
+                                "" => italic,
+                                "0" => false,
+                                _ => true,
+                            }
+                        }
+                        "r" => state = italic,
+                        _ => (),
+                    }
+                }
+            }
+            Segment::Text(t) => {
+                for c in text::unescape(t).chars() {
+                    match c {
+                        '\n' => {
+                            end_word(&mut lines, &mut word);
+                            lines.push(Vec::new());
+                        }
+                        ' ' | '\u{a0}' | '\t' => end_word(&mut lines, &mut word),
+                        c => {
+                            let cells: Vec<Cell> = match map_char(c) {
+                                Some(cell) => vec![cell],
+                                None => {
+                                    let sub = substitute(c);
+                                    warn(format!("'{}' isn't in the CEA-608 character set, replaced with '{}'", c, sub));
+                                    sub.chars().filter_map(map_char).collect()
+                                }
+                            };
+                            word.get_or_insert_with(|| (Vec::new(), state))
+                                .0
+                                .extend(cells);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    end_word(&mut lines, &mut word);
+    lines
+}
+
+fn wrap(lines: Vec<Vec<Word>>) -> Vec<Vec<Word>> {
+    let mut rows = Vec::new();
+    for line in lines {
+        let mut row = Vec::<Word>::new();
+        let mut width = 0;
+        for (mut cells, italic) in line {
+            while cells.len() > COLUMNS {
+                let rest = cells.split_off(COLUMNS);
+                if !row.is_empty() {
+                    rows.push(std::mem::take(&mut row));
+                }
+                rows.push(vec![(cells, italic)]);
+                cells = rest;
+                width = 0;
+            }
+            if !row.is_empty() && width + 1 + cells.len() > COLUMNS {
+                rows.push(std::mem::take(&mut row));
+            }
+            width = match row.is_empty() {
+                true => cells.len(),
+                false => width + 1 + cells.len(),
+            };
+            row.push((cells, italic));
+        }
+        if !row.is_empty() {
+            rows.push(row);
+        }
+    }
+    rows
+}
+
+fn encode_row(enc: &mut Encoder, row: usize, col: u8, words: &[Word]) {
+    let (first, base) = PAC_ROWS[row - 1];
+    let italic = words[0].1;
+    if italic && col == 0 {
+        enc.control((first, base + PAC_ITALIC));
+    } else {
+        // an italic row needs a column for the mid-row code before it.
+        let col = if italic { col - 1 } else { col };
+        enc.control((first, base + PAC_INDENT + col / 4 * 2));
+        if col % 4 > 0 {
+            enc.control((TAB_OFFSET, 0x20 + col % 4));
+        }
+        if italic {
+            enc.control(MID_ROW_ITALIC);
+        }
+    }
+    let mut state = italic;
+    for (n, (cells, italic)) in words.iter().enumerate() {
+        if n > 0 {
+            if *italic != state {
+                enc.control(if *italic {
+                    MID_ROW_ITALIC
+                } else {
+                    MID_ROW_WHITE
+                });
+                state = *italic;
+            } else {
+                enc.char(b' ');
+            }
+        }
+        for cell in cells {
+            enc.cell(*cell);
+        }
+    }
+}
+
+// 29.97 drop-frame skips frame numbers 0 and 1 every minute except every
+// tenth, so the timecode keeps up with the clock.
+fn drop_frame(frame: u64) -> String {
+    let tens = frame / 17982;
+    let rest = frame % 17982;
+    let frame = frame + 18 * tens + 2 * ((rest.max(2) - 2) / 1798);
+    format!(
+        "{:02}:{:02}:{:02};{:02}",
+        frame / 108_000,
+        frame / 1800 % 60,
+        frame / 30 % 60,
+        frame % 30
+    )
+}
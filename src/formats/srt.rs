@@ -0,0 +1,90 @@
+// subrip (.srt): numbered blocks of a 00:00:01,000 --> 00:00:03,500 line and
+// the text, with a blank line between them. styling is the html-ish <i>, <b>,
+// <u> and <font color>, and most players also take an ass {\an8} at the
+// start for lines that go at the top.
+
+use super::FormatError::{self, BadSrtBlock};
+use super::{ass_to_html, dialogue, html_to_ass, track_from_events};
+use crate::ass::{common::Timecode, text, AssTrack};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+// . instead of , turns up often enough to take it too.
+fn parse_time(caps: &regex::Captures, from: usize) -> Option<u32> {
+    let n = |i: usize| caps[from + i].parse::<u32>().ok();
+    Some(n(0)? * 3_600_000 + n(1)? * 60_000 + n(2)? * 1000 + n(3)?)
+}
+
+pub fn parse_track(s: &str) -> Result<AssTrack<'static>, FormatError> {
+    lazy_static! {
+        static ref TIME_RE: Regex = Regex::new(
+            r"^(\d{1,3}):(\d{2}):(\d{2})[,.](\d{3})\s*-->\s*(\d{1,3}):(\d{2}):(\d{2})[,.](\d{3})"
+        )
+        .unwrap();
+    }
+    let mut events = Vec::new();
+    let mut block = Vec::<(usize, &str)>::new();
+    let lines = s.lines().map(|x| x.trim_end()).chain(std::iter::once(""));
+    for (line_n, line) in lines.enumerate() {
+        if !line.trim().is_empty() {
+            block.push((line_n, line));
+            continue;
+        }
+        if block.is_empty() {
+            continue;
+        }
+        // the number is meant to be there but nothing needs it.
+        let at = match block.len() > 1 && !block[0].1.contains("-->") {
+            true => 1,
+            false => 0,
+        };
+        let times = TIME_RE
+            .captures(block[at].1.trim())
+            .and_then(|caps| Some((parse_time(&caps, 1)?, parse_time(&caps, 5)?)));
+        match times {
+            Some((start, end)) => {
+                let text: Vec<&str> = block[at + 1..].iter().map(|x| x.1).collect();
+                events.push(dialogue(start, end, html_to_ass(&text.join("<br>"))));
+            }
+            None => eprintln!("Dropped block at line {}: {}", block[0].0, BadSrtBlock),
+        }
+        block.clear();
+    }
+    Ok(track_from_events(events))
+}
+
+pub fn write_track(track: &AssTrack) -> String {
+    let time = |t: Timecode| {
+        let ms = t.as_millis();
+        format!(
+            "{:02}:{:02}:{:02},{:03}",
+            ms / 3_600_000,
+            ms / 60_000 % 60,
+            ms / 1000 % 60,
+            ms % 1000
+        )
+    };
+    let mut out = Vec::new();
+    for event in track.events.iter().filter(|x| x.descriptor != "Comment") {
+        let text = event.text.as_deref().unwrap_or("");
+        if text::plain_text(text).trim().is_empty() {
+            continue;
+        }
+        let mut body = ass_to_html(text, false).replace('\u{a0}', " ");
+        // top of the screen is the one placement players agree on.
+        let (alignment, _) = text::placement(text);
+        let alignment =
+            alignment.or_else(|| track.style(event.style.as_deref()).map(|x| x.alignment));
+        if let Some(a) = alignment.map(u8::from).filter(|x| *x >= 7) {
+            body = format!("{{\\an{}}}{}", a, body);
+        }
+        out.push(format!(
+            "{}\n{} --> {}\n{}\n",
+            out.len() + 1,
+            time(event.start_time),
+            time(event.end_time),
+            body.trim()
+        ));
+    }
+    out.join("\n")
+}
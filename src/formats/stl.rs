@@ -0,0 +1,867 @@
+// ebu tech 3264 .stl: a 1024 byte GSI block (mostly fixed width, space
+// padded text fields) followed by 128 byte TTI blocks, one per subtitle, or
+// more when the text doesn't fit in a single block's 112 bytes.
+//
+// we write teletext level 1 (the usual broadcast delivery): rows 1 to 23,
+// 40 characters a row. the GSI text fields are in code page 850, the
+// subtitle text in whichever character code table the header names, ISO
+// 6937 for latin scripts, where accented letters are a diacritic byte
+// followed by the letter.
+
+use super::FormatError::{self, BadStlHeader};
+use super::{track_from_events, Warning};
+use crate::ass::{
+    common::{Alignment, Timecode},
+    event::Event,
+    style::Style,
+    text::{self, Segment},
+    AssTrack,
+};
+use encoding_rs::{Encoding, ISO_8859_5, ISO_8859_6, ISO_8859_7, ISO_8859_8};
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use unicode_normalization::UnicodeNormalization;
+
+const GSI_LEN: usize = 1024;
+const TTI_LEN: usize = 128;
+const TF_LEN: usize = 112;
+const ROWS: usize = 23;
+const COLUMNS: usize = 40;
+// imported tracks get a resolution so margins have something to scale to.
+const PLAY_RES: (u32, u32) = (1920, 1080);
+
+// text field control codes.
+const ITALIC_ON: u8 = 0x80;
+const ITALIC_OFF: u8 = 0x81;
+const UNDERLINE_ON: u8 = 0x82;
+const UNDERLINE_OFF: u8 = 0x83;
+const NEWLINE: u8 = 0x8a;
+const UNUSED: u8 = 0x8f;
+// extension block number of the last (or only) block of a subtitle, and of
+// blocks holding user data rather than text.
+const LAST_BLOCK: u8 = 0xff;
+const USER_DATA: u8 = 0xfe;
+
+// 0xa0 to 0xff of the latin table. 0xc1 to 0xcf are the diacritics, \0 is
+// unused.
+const LATIN_UPPER: &str = concat!(
+    "\u{a0}¡¢£$¥#§¤‘“«←↑→↓",
+    "°±²³×µ¶·÷’”»¼½¾¿",
+    "\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
+    "―¹®©™♪¬¦\0\0\0\0⅛⅜⅝⅞",
+    "ΩÆĐªĦ\0ĲĿŁØŒºÞŦŊŉ",
+    "ĸæđðħıĳŀłøœßþŧŋ\u{ad}",
+);
+
+// diacritic bytes and the combining character each one stands for.
+const DIACRITICS: &[(u8, char)] = &[
+    (0xc1, '\u{300}'),
+    (0xc2, '\u{301}'),
+    (0xc3, '\u{302}'),
+    (0xc4, '\u{303}'),
+    (0xc5, '\u{304}'),
+    (0xc6, '\u{306}'),
+    (0xc7, '\u{307}'),
+    (0xc8, '\u{308}'),
+    (0xca, '\u{30a}'),
+    (0xcb, '\u{327}'),
+    (0xcd, '\u{30b}'),
+    (0xce, '\u{328}'),
+    (0xcf, '\u{30c}'),
+];
+
+// 0x80 to 0xff of code page 850, for the GSI block.
+const CP850_UPPER: &str = concat!(
+    "ÇüéâäàåçêëèïîìÄÅ",
+    "ÉæÆôöòûùÿÖÜø£Ø×ƒ",
+    "áíóúñÑªº¿®¬½¼¡«»",
+    "░▒▓│┤ÁÂÀ©╣║╗╝¢¥┐",
+    "└┴┬├─┼ãÃ╚╔╩╦╠═╬¤",
+    "ðÐÊËÈıÍÎÏ┘┌█▄¦Ì▀",
+    "ÓßÔÒõÕµþÞÚÛÙýÝ¯´",
+    "\u{ad}±‗¾¶§÷¸°¨·¹³²■\u{a0}",
+);
+
+// the language codes from appendix 3, with the iso 639-1 code where there is
+// one.
+const LANGUAGES: &[(u8, &str, &str)] = &[
+    (0x01, "sq", "Albanian"),
+    (0x02, "br", "Breton"),
+    (0x03, "ca", "Catalan"),
+    (0x04, "hr", "Croatian"),
+    (0x05, "cy", "Welsh"),
+    (0x06, "cs", "Czech"),
+    (0x07, "da", "Danish"),
+    (0x08, "de", "German"),
+    (0x09, "en", "English"),
+    (0x0a, "es", "Spanish"),
+    (0x0b, "eo", "Esperanto"),
+    (0x0c, "et", "Estonian"),
+    (0x0d, "eu", "Basque"),
+    (0x0e, "fo", "Faroese"),
+    (0x0f, "fr", "French"),
+    (0x10, "fy", "Frisian"),
+    (0x11, "ga", "Irish"),
+    (0x12, "gd", "Gaelic"),
+    (0x13, "gl", "Galician"),
+    (0x14, "is", "Icelandic"),
+    (0x15, "it", "Italian"),
+    (0x16, "se", "Lappish"),
+    (0x17, "la", "Latin"),
+    (0x18, "lv", "Latvian"),
+    (0x19, "lb", "Luxembourgian"),
+    (0x1a, "lt", "Lithuanian"),
+    (0x1b, "hu", "Hungarian"),
+    (0x1c, "mt", "Maltese"),
+    (0x1d, "nl", "Dutch"),
+    (0x1e, "no", "Norwegian"),
+    (0x1f, "oc", "Occitan"),
+    (0x20, "pl", "Polish"),
+    (0x21, "pt", "Portuguese"),
+    (0x22, "ro", "Romanian"),
+    (0x23, "rm", "Romansh"),
+    (0x24, "sr", "Serbian"),
+    (0x25, "sk", "Slovak"),
+    (0x26, "sl", "Slovenian"),
+    (0x27, "fi", "Finnish"),
+    (0x28, "sv", "Swedish"),
+    (0x29, "tr", "Turkish"),
+    (0x2a, "", "Flemish"),
+    (0x2b, "wa", "Wallon"),
+    (0x45, "zu", "Zulu"),
+    (0x46, "vi", "Vietnamese"),
+    (0x47, "uz", "Uzbek"),
+    (0x48, "ur", "Urdu"),
+    (0x49, "uk", "Ukrainian"),
+    (0x4a, "th", "Thai"),
+    (0x4b, "te", "Telugu"),
+    (0x4c, "tt", "Tatar"),
+    (0x4d, "ta", "Tamil"),
+    (0x4e, "tg", "Tadzhik"),
+    (0x4f, "sw", "Swahili"),
+    (0x50, "", "Sranan Tongo"),
+    (0x51, "so", "Somali"),
+    (0x52, "si", "Sinhalese"),
+    (0x53, "sn", "Shona"),
+    (0x54, "sh", "Serbo-croat"),
+    (0x55, "", "Ruthenian"),
+    (0x56, "ru", "Russian"),
+    (0x57, "qu", "Quechua"),
+    (0x58, "ps", "Pushtu"),
+    (0x59, "pa", "Punjabi"),
+    (0x5a, "fa", "Persian"),
+    (0x5b, "", "Papamiento"),
+    (0x5c, "or", "Oriya"),
+    (0x5d, "ne", "Nepali"),
+    (0x5e, "nd", "Ndebele"),
+    (0x5f, "mr", "Marathi"),
+    (0x60, "mo", "Moldavian"),
+    (0x61, "ms", "Malaysian"),
+    (0x62, "mg", "Malagasay"),
+    (0x63, "mk", "Macedonian"),
+    (0x64, "lo", "Laotian"),
+    (0x65, "ko", "Korean"),
+    (0x66, "km", "Khmer"),
+    (0x67, "kk", "Kazakh"),
+    (0x68, "kn", "Kannada"),
+    (0x69, "ja", "Japanese"),
+    (0x6a, "id", "Indonesian"),
+    (0x6b, "hi", "Hindi"),
+    (0x6c, "he", "Hebrew"),
+    (0x6d, "ha", "Hausa"),
+    (0x6e, "gn", "Gurani"),
+    (0x6f, "gu", "Gujurati"),
+    (0x70, "el", "Greek"),
+    (0x71, "ka", "Georgian"),
+    (0x72, "ff", "Fulani"),
+    (0x73, "", "Dari"),
+    (0x74, "cv", "Churash"),
+    (0x75, "zh", "Chinese"),
+    (0x76, "my", "Burmese"),
+    (0x77, "bg", "Bulgarian"),
+    (0x78, "bn", "Bengali"),
+    (0x79, "be", "Bielorussian"),
+    (0x7a, "bm", "Bambora"),
+    (0x7b, "az", "Azerbaijani"),
+    (0x7c, "as", "Assamese"),
+    (0x7d, "hy", "Armenian"),
+    (0x7e, "ar", "Arabic"),
+    (0x7f, "am", "Amharic"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FrameRate {
+    #[default]
+    Fps25,
+    Fps30,
+}
+impl FrameRate {
+    fn fps(self) -> u32 {
+        match self {
+            FrameRate::Fps25 => 25,
+            FrameRate::Fps30 => 30,
+        }
+    }
+}
+
+// the character code table the subtitle text is in.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CharacterTable {
+    #[default]
+    Latin,
+    Cyrillic,
+    Arabic,
+    Greek,
+    Hebrew,
+}
+impl CharacterTable {
+    fn code(self) -> &'static str {
+        match self {
+            CharacterTable::Latin => "00",
+            CharacterTable::Cyrillic => "01",
+            CharacterTable::Arabic => "02",
+            CharacterTable::Greek => "03",
+            CharacterTable::Hebrew => "04",
+        }
+    }
+    fn from_code(s: &str) -> Option<Self> {
+        [
+            CharacterTable::Latin,
+            CharacterTable::Cyrillic,
+            CharacterTable::Arabic,
+            CharacterTable::Greek,
+            CharacterTable::Hebrew,
+        ]
+        .iter()
+        .copied()
+        .find(|x| x.code() == s)
+    }
+    // everything but latin is the top half of an iso 8859 part.
+    fn iso8859(self) -> Option<&'static Encoding> {
+        match self {
+            CharacterTable::Latin => None,
+            CharacterTable::Cyrillic => Some(ISO_8859_5),
+            CharacterTable::Arabic => Some(ISO_8859_6),
+            CharacterTable::Greek => Some(ISO_8859_7),
+            CharacterTable::Hebrew => Some(ISO_8859_8),
+        }
+    }
+
+    fn encode(self, c: char) -> Option<Vec<u8>> {
+        if (' '..='~').contains(&c) {
+            return Some(vec![c as u8]);
+        }
+        if let Some(enc) = self.iso8859() {
+            let mut buf = [0; 4];
+            let (bytes, _, bad) = enc.encode(c.encode_utf8(&mut buf));
+            return match bad {
+                true => None,
+                false => Some(bytes.into_owned()),
+            };
+        }
+        if let Some(n) = LATIN_UPPER.chars().position(|x| x == c && x != '\0') {
+            return Some(vec![0xa0 + n as u8]);
+        }
+        // anything else has to be a letter plus one diacritic.
+        match c.nfd().collect::<Vec<char>>()[..] {
+            [base, mark] if base.is_ascii_alphabetic() => DIACRITICS
+                .iter()
+                .find(|x| x.1 == mark)
+                .map(|x| vec![x.0, base as u8]),
+            _ => None,
+        }
+    }
+
+    fn decode(self, b: u8) -> Option<char> {
+        match b {
+            0x20..=0x7e => Some(b as char),
+            0xa0..=0xff => match self.iso8859() {
+                Some(enc) => enc
+                    .decode_without_bom_handling(&[b])
+                    .0
+                    .chars()
+                    .next()
+                    .filter(|x| *x != '\u{fffd}'),
+                None => LATIN_UPPER
+                    .chars()
+                    .nth(usize::from(b - 0xa0))
+                    .filter(|x| *x != '\0'),
+            },
+            _ => None,
+        }
+    }
+}
+
+//------------------------------------------------------------------------------
+// reading
+//------------------------------------------------------------------------------
+
+pub fn parse_track(b: &[u8]) -> Result<AssTrack<'static>, FormatError> {
+    if b.len() < GSI_LEN || &b[3..6] != b"STL" {
+        return Err(BadStlHeader);
+    }
+    let gsi = &b[..GSI_LEN];
+    let field = |at: usize, len: usize| cp850_decode(&gsi[at..at + len]);
+    let fps = field(6, 2).parse::<u32>().or(Err(BadStlHeader))?;
+    if fps == 0 {
+        return Err(BadStlHeader);
+    }
+    let table = CharacterTable::from_code(&field(12, 2)).unwrap_or_else(|| {
+        eprintln!(
+            "Unknown character code table {:?}, using latin",
+            field(12, 2)
+        );
+        CharacterTable::Latin
+    });
+    let rows = field(253, 2).parse::<usize>().unwrap_or(ROWS).max(1);
+    let time = |t: &[u8]| {
+        let frames = u32::from(t[0]) * 3600 * fps
+            + u32::from(t[1]) * 60 * fps
+            + u32::from(t[2]) * fps
+            + u32::from(t[3]);
+        (u64::from(frames) * 1000 / u64::from(fps)) as u32
+    };
+    // the start of programme is usually 10:00:00:00 in broadcast files, and
+    // the subtitles are timed against that.
+    let programme_start = field(256, 8);
+    let programme_start = match programme_start.as_bytes() {
+        p if p.len() == 8 && p.iter().all(u8::is_ascii_digit) => {
+            let pair = |i: usize| (p[i] - b'0') * 10 + (p[i + 1] - b'0');
+            time(&[pair(0), pair(2), pair(4), pair(6)])
+        }
+        _ => 0,
+    };
+
+    let mut events = Vec::new();
+    let mut pending: Option<(u16, Vec<u8>)> = None;
+    for block in b[GSI_LEN..].chunks_exact(TTI_LEN) {
+        let number = u16::from_le_bytes([block[1], block[2]]);
+        let extension = block[3];
+        if extension == USER_DATA {
+            continue;
+        }
+        let tf = &block[16..];
+        let tf = &tf[..tf.iter().position(|x| *x == UNUSED).unwrap_or(TF_LEN)];
+        let mut bytes = match pending.take() {
+            Some((n, bytes)) if n == number => bytes,
+            _ => Vec::new(),
+        };
+        bytes.extend_from_slice(tf);
+        if extension != LAST_BLOCK {
+            pending = Some((number, bytes));
+            continue;
+        }
+
+        let text = decode_text(&bytes, table);
+        let lines = text.matches("\\N").count() + 1;
+        let (tags, margin_v) = placement(block[13], block[14], lines, rows);
+        let start = time(&block[5..9]);
+        let end = time(&block[9..13]);
+        events.push(Event {
+            start_time: Timecode::from(start.saturating_sub(programme_start)),
+            end_time: Timecode::from(end.saturating_sub(programme_start)),
+            descriptor: Cow::Borrowed(match block[15] {
+                0 => "Dialogue",
+                _ => "Comment",
+            }),
+            margin_v,
+            text: Some(Cow::Owned(tags + &text)),
+            ..Event::default()
+        });
+    }
+
+    let mut track = track_from_events(events);
+    let header = &mut track.header;
+    header.play_res_x = Some(PLAY_RES.0);
+    header.play_res_y = Some(PLAY_RES.1);
+    let non_empty = |s: String| Some(Cow::<'static, str>::Owned(s)).filter(|x| !x.is_empty());
+    header.title = non_empty(field(16, 32)).or_else(|| non_empty(field(80, 32)));
+    header.translation = non_empty(field(144, 32));
+    header.editing = non_empty(field(309, 32));
+    header.language = u8::from_str_radix(&field(14, 2), 16)
+        .ok()
+        .and_then(|lc| LANGUAGES.iter().find(|x| x.0 == lc))
+        .map(|x| match x.1 {
+            "" => Cow::Borrowed(x.2),
+            code => Cow::Borrowed(code),
+        });
+    Ok(track)
+}
+
+// the alignment tag and vertical margin for a subtitle whose first row is vp.
+// anything in the top third is top aligned, the rest sits on the bottom.
+fn placement(vp: u8, jc: u8, lines: usize, rows: usize) -> (String, i32) {
+    let first = usize::from(vp).clamp(1, rows);
+    let res_y = f64::from(PLAY_RES.1);
+    let row_height = res_y / rows as f64;
+    let (base, margin_v) = if first <= rows / 3 {
+        (7, (first - 1) as f64 * row_height)
+    } else {
+        let last = (first + lines - 1).min(rows);
+        (1, (rows - last) as f64 * row_height)
+    };
+    // justification is 0 unchanged, 1 left, 2 centred, 3 right.
+    let alignment = base + [1, 0, 1, 2][usize::from(jc.min(3))];
+    let tags = match alignment {
+        2 => String::new(),
+        n => format!("{{\\an{}}}", n),
+    };
+    (tags, margin_v.round() as i32)
+}
+
+// teletext colour codes, as ass &HBBGGRR&.
+const COLOURS: [&str; 8] = [
+    "&H000000&",
+    "&H0000FF&",
+    "&H00FF00&",
+    "&H00FFFF&",
+    "&HFF0000&",
+    "&HFF00FF&",
+    "&HFFFF00&",
+    "&HFFFFFF&",
+];
+
+fn decode_text(bytes: &[u8], table: CharacterTable) -> String {
+    let mut rows = Vec::new();
+    let mut row = String::new();
+    // tags wait until there's text to put them in front of, so they don't
+    // end up stranded at the end of a row.
+    let mut tags = String::new();
+    let mut colour = 7;
+    // teletext control codes take up a cell, so they read as a space.
+    let mut gap = false;
+    let mut diacritic = None;
+    for &b in bytes {
+        match b {
+            NEWLINE => {
+                rows.push(row.trim_end().to_owned());
+                row.clear();
+                gap = false;
+                if colour != 7 {
+                    // teletext starts every row white.
+                    colour = 7;
+                    tags.push_str(&format!("\\c{}", COLOURS[7]));
+                }
+            }
+            ITALIC_ON => tags.push_str("\\i1"),
+            ITALIC_OFF => tags.push_str("\\i0"),
+            UNDERLINE_ON => tags.push_str("\\u1"),
+            UNDERLINE_OFF => tags.push_str("\\u0"),
+            0x00..=0x07 => {
+                if colour != b {
+                    colour = b;
+                    tags.push_str(&format!("\\c{}", COLOURS[usize::from(b)]));
+                }
+                gap = true;
+            }
+            0x08..=0x1f | 0x84 | 0x85 => gap = true,
+            _ if table == CharacterTable::Latin && DIACRITICS.iter().any(|x| x.0 == b) => {
+                diacritic = DIACRITICS.iter().find(|x| x.0 == b).map(|x| x.1);
+            }
+            _ => {
+                let c = match table.decode(b) {
+                    Some(c) => c,
+                    None => continue,
+                };
+                let c = match c {
+                    '\u{a0}' => ' ',
+                    c => c,
+                };
+                let has_text = !text::strip_tags(&row).is_empty();
+                if c == ' ' && !has_text {
+                    continue;
+                }
+                if gap && has_text && !row.ends_with(' ') && c != ' ' {
+                    row.push(' ');
+                }
+                gap = false;
+                if !tags.is_empty() {
+                    row.push_str(&format!("{{{}}}", tags));
+                    tags.clear();
+                }
+                match diacritic.take() {
+                    Some(mark) => row.extend([c, mark].iter().copied().nfc()),
+                    None => row.push(c),
+                }
+            }
+        }
+    }
+    rows.push(row.trim_end().to_owned());
+    // double height teletext leaves an empty row between every line.
+    rows.retain(|x| !x.is_empty());
+    rows.join("\\N")
+}
+
+fn cp850_decode(b: &[u8]) -> String {
+    b.iter()
+        .filter_map(|&x| match x {
+            0x20..=0x7e => Some(x as char),
+            0x80..=0xff => CP850_UPPER.chars().nth(usize::from(x - 0x80)),
+            _ => None,
+        })
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
+
+//------------------------------------------------------------------------------
+// writing
+//------------------------------------------------------------------------------
+
+pub fn write_track(
+    track: &AssTrack,
+    frame_rate: FrameRate,
+    table: CharacterTable,
+) -> (Vec<u8>, Vec<Warning>) {
+    let mut warnings = Vec::new();
+    let default_style = Style::default();
+    let fps = frame_rate.fps();
+    let mut ttis = Vec::new();
+    let mut subtitles = 0;
+    let mut first_cue = None;
+
+    for (n, event) in track.events.iter().enumerate() {
+        if event.end_time.0 <= event.start_time.0 {
+            continue;
+        }
+        let style = track
+            .style(event.style.as_deref())
+            .unwrap_or(&default_style);
+        let mut warn = |message: String| {
+            warnings.push(Warning {
+                event: Some(n),
+                message,
+            })
+        };
+        let (bytes, lines) =
+            encode_text(event.text.as_deref().unwrap_or(""), style, table, &mut warn);
+        if lines == 0 {
+            continue;
+        }
+        let (vp, jc) = vertical_position(track, event, style, lines);
+        let comment = event.descriptor == "Comment";
+        if !comment && first_cue.is_none() {
+            first_cue = Some(event.start_time);
+        }
+
+        let chunks: Vec<&[u8]> = bytes.chunks(TF_LEN).collect();
+        if chunks.len() > usize::from(USER_DATA) {
+            warn("too long, dropped".to_owned());
+            continue;
+        }
+        let number = subtitles as u16;
+        subtitles += 1;
+        for (i, chunk) in chunks.iter().enumerate() {
+            let mut block = vec![UNUSED; TTI_LEN];
+            block[0] = 0;
+            block[1..3].copy_from_slice(&number.to_le_bytes());
+            block[3] = match i + 1 == chunks.len() {
+                true => LAST_BLOCK,
+                false => i as u8,
+            };
+            block[4] = 0;
+            block[5..9].copy_from_slice(&timecode(event.start_time, fps));
+            block[9..13].copy_from_slice(&timecode(event.end_time, fps));
+            block[13] = vp;
+            block[14] = jc;
+            block[15] = u8::from(comment);
+            block[16..16 + chunk.len()].copy_from_slice(chunk);
+            ttis.push(block);
+        }
+    }
+
+    let gsi = gsi_block(
+        track,
+        frame_rate,
+        table,
+        ttis.len(),
+        subtitles,
+        first_cue.map_or([0; 4], |x| timecode(x, fps)),
+        &mut warnings,
+    );
+    let mut out = gsi;
+    for block in ttis {
+        out.extend(block);
+    }
+    (out, warnings)
+}
+
+// the text field bytes and how many rows they make.
+fn encode_text(
+    s: &str,
+    style: &Style,
+    table: CharacterTable,
+    warn: &mut impl FnMut(String),
+) -> (Vec<u8>, usize) {
+    let mut rows: Vec<Vec<u8>> = vec![Vec::new()];
+    let (mut italic, mut underline) = (false, false);
+    let (mut want_italic, mut want_underline) = (style.italic, style.underline);
+    for segment in text::segments(s) {
+        match segment {
+            Segment::Override(block) => {
+                for tag in text::tags(block) {
+                    let on = !matches!(tag.arg, "" | "0");
+                    match tag.name {
+                        "i" => want_italic = on,
+                        "u" => want_underline = on,
+                        "r" => {
+                            want_italic = style.italic;
+                            want_underline = style.underline;
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            Segment::Text(t) => {
+                for c in text::unescape(t).chars() {
+                    if c == '\n' {
+                        rows.push(Vec::new());
+                        continue;
+                    }
+                    let row = rows.last_mut().unwrap();
+                    if want_italic != italic {
+                        italic = want_italic;
+                        row.push(if italic { ITALIC_ON } else { ITALIC_OFF });
+                    }
+                    if want_underline != underline {
+                        underline = want_underline;
+                        row.push(if underline {
+                            UNDERLINE_ON
+                        } else {
+                            UNDERLINE_OFF
+                        });
+                    }
+                    let c = match c {
+                        '\u{a0}' => ' ',
+                        c => c,
+                    };
+                    match table.encode(c) {
+                        Some(b) => row.extend(b),
+                        None => {
+                            warn(format!("{:?} isn't in the character table, used ?", c));
+                            row.push(b'?');
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let rows: Vec<Vec<u8>> = rows
+        .into_iter()
+        .map(|x| trim_row(&x).to_vec())
+        .filter(|x| x.iter().any(|b| !is_control(*b)))
+        .collect();
+    for row in &rows {
+        let width = row.iter().filter(|x| !is_diacritic(**x, table)).count();
+        if width > COLUMNS {
+            warn(format!(
+                "row is {} characters, teletext only shows {}",
+                width, COLUMNS
+            ));
+        }
+    }
+    if rows.len() > ROWS {
+        warn(format!("{} rows won't fit on screen", rows.len()));
+    }
+    let lines = rows.len();
+    (rows.join(&NEWLINE), lines)
+}
+
+fn is_control(b: u8) -> bool {
+    (0x80..=0x8f).contains(&b)
+}
+
+fn is_diacritic(b: u8, table: CharacterTable) -> bool {
+    table == CharacterTable::Latin && DIACRITICS.iter().any(|x| x.0 == b)
+}
+
+// trims spaces from either end, stepping around the control codes.
+fn trim_row(row: &[u8]) -> Vec<u8> {
+    let mut res: Vec<u8> = Vec::new();
+    let mut text = false;
+    for &b in row {
+        if b == b' ' && !text {
+            continue;
+        }
+        text |= !is_control(b);
+        res.push(b);
+    }
+    while let Some(n) = res.iter().rposition(|x| !is_control(*x)) {
+        if res[n] != b' ' {
+            break;
+        }
+        res.remove(n);
+    }
+    res
+}
+
+// vertical position (first row) and justification code.
+fn vertical_position(track: &AssTrack, event: &Event, style: &Style, lines: usize) -> (u8, u8) {
+    let text = event.text.as_deref().unwrap_or("");
+    let (an, pos) = text::placement(text);
+    let alignment = an.unwrap_or(style.alignment);
+    let n = u8::from(alignment);
+    let (_, res_y) = track.header.play_res();
+    let res_y = f64::from(res_y);
+    let (_, _, margin_v) = event.margins(style);
+    let margin_v = f64::from(margin_v);
+    let y = match pos {
+        Some((_, y)) => y,
+        None => match n {
+            7..=9 => margin_v,
+            4..=6 => res_y / 2.0,
+            _ => res_y - margin_v,
+        },
+    };
+    let row = 1 + ((y / res_y).clamp(0.0, 1.0) * (ROWS - 1) as f64).round() as usize;
+    let first = match n {
+        7..=9 => row,
+        4..=6 => row.saturating_sub((lines - 1) / 2),
+        _ => row.saturating_sub(lines - 1),
+    };
+    let first = first.clamp(1, ROWS.saturating_sub(lines - 1).max(1));
+    let jc = match Alignment::try_from(n).unwrap_or_default() {
+        Alignment::BottomLeft | Alignment::Left | Alignment::TopLeft => 1,
+        Alignment::BottomRight | Alignment::Right | Alignment::TopRight => 3,
+        _ => 2,
+    };
+    (first as u8, jc)
+}
+
+fn timecode(t: Timecode, fps: u32) -> [u8; 4] {
+    let frames = (u64::from(t.as_millis()) * u64::from(fps) + 500) / 1000;
+    let fps = u64::from(fps);
+    let seconds = frames / fps;
+    [
+        (seconds / 3600 % 24) as u8,
+        (seconds / 60 % 60) as u8,
+        (seconds % 60) as u8,
+        (frames % fps) as u8,
+    ]
+}
+
+fn gsi_block(
+    track: &AssTrack,
+    frame_rate: FrameRate,
+    table: CharacterTable,
+    blocks: usize,
+    subtitles: usize,
+    first_cue: [u8; 4],
+    warnings: &mut Vec<Warning>,
+) -> Vec<u8> {
+    let header = &track.header;
+    let mut gsi = vec![b' '; GSI_LEN];
+    let mut messages = Vec::new();
+    let lc = language_code(header.language.as_deref(), &mut messages);
+    let mut put = |at: usize, len: usize, s: &str, what: &str| {
+        let mut bytes = Vec::new();
+        for c in s.chars() {
+            match cp850_encode(c) {
+                Some(b) => bytes.push(b),
+                None => {
+                    messages.push(format!(
+                        "{:?} in the {} isn't in code page 850, used ?",
+                        c, what
+                    ));
+                    bytes.push(b'?');
+                }
+            }
+        }
+        if bytes.len() > len {
+            messages.push(format!("{} cut to {} characters", what, len));
+            bytes.truncate(len);
+        }
+        gsi[at..at + bytes.len()].copy_from_slice(&bytes);
+    };
+    let tc = |t: [u8; 4]| format!("{:02}{:02}{:02}{:02}", t[0], t[1], t[2], t[3]);
+    let date = today();
+
+    put(0, 3, "850", "code page");
+    put(3, 8, &format!("STL{}.01", frame_rate.fps()), "disk format");
+    put(11, 1, "1", "display standard");
+    put(12, 2, table.code(), "character code table");
+    put(14, 2, &format!("{:02X}", lc), "language");
+    put(16, 32, header.title.as_deref().unwrap_or(""), "title");
+    put(
+        144,
+        32,
+        header.translation.as_deref().unwrap_or(""),
+        "translator",
+    );
+    put(224, 6, &date, "creation date");
+    put(230, 6, &date, "revision date");
+    put(236, 2, "00", "revision number");
+    put(238, 5, &format!("{:05}", blocks.min(99_999)), "block count");
+    put(
+        243,
+        5,
+        &format!("{:05}", subtitles.min(99_999)),
+        "subtitle count",
+    );
+    put(248, 3, "001", "group count");
+    put(251, 2, &format!("{:02}", COLUMNS), "row length");
+    put(253, 2, &format!("{:02}", ROWS), "row count");
+    put(255, 1, "1", "time code status");
+    put(256, 8, &tc([0; 4]), "programme start");
+    put(264, 8, &tc(first_cue), "first cue");
+    put(272, 1, "1", "disk count");
+    put(273, 1, "1", "disk number");
+    put(309, 32, header.editing.as_deref().unwrap_or(""), "editor");
+
+    warnings.extend(messages.into_iter().map(|message| Warning {
+        event: None,
+        message,
+    }));
+    gsi
+}
+
+// takes an iso 639-1 code (with or without a region) or an english name.
+fn language_code(language: Option<&str>, messages: &mut Vec<String>) -> u8 {
+    let language = match language.map(|x| x.trim()).filter(|x| !x.is_empty()) {
+        Some(x) => x,
+        None => return 0,
+    };
+    let code = language.split(['-', '_']).next().unwrap_or(language);
+    let found = LANGUAGES.iter().find(|x| {
+        (!x.1.is_empty() && x.1.eq_ignore_ascii_case(code)) || x.2.eq_ignore_ascii_case(language)
+    });
+    match found {
+        Some(x) => x.0,
+        None => {
+            messages.push(format!("no EBU language code for {:?}", language));
+            0
+        }
+    }
+}
+
+fn cp850_encode(c: char) -> Option<u8> {
+    match c {
+        ' '..='~' => Some(c as u8),
+        _ => CP850_UPPER
+            .chars()
+            .position(|x| x == c)
+            .map(|n| 0x80 + n as u8),
+    }
+}
+
+// YYMMDD, utc.
+fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs());
+    // days since the epoch to a civil date, from howard hinnant's algorithms.
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:02}{:02}{:02}", year % 100, month, day)
+}
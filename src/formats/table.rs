@@ -0,0 +1,247 @@
+// csv/tsv for translating in a spreadsheet. one row per dialogue line:
+//
+//   id, start, end, style, actor, plain, text
+//
+// id is the event's index in the track, so the sheet has to be merged back
+// into the same script it came from. plain is the readable text (real line
+// breaks, no tags) for reference, text is the same thing with the ass
+// escapes kept, and is the column to translate.
+//
+// merging puts the override blocks back: the ones at the start and end stay
+// there, the ones in the middle go to the same relative spot in the
+// translation, at a word boundary. a translation that has its own {} blocks
+// is used as is. columns are found by name, so people can add their own.
+
+use super::FormatError::{self, BadTableHeader};
+use super::Warning;
+use crate::ass::{
+    common::Timecode,
+    text::{self, Segment},
+    AssTrack,
+};
+use std::borrow::Cow;
+
+const COLUMNS: [&str; 7] = ["id", "start", "end", "style", "actor", "plain", "text"];
+
+fn quote(field: &str, delimiter: char) -> String {
+    match field.contains([delimiter, '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_owned(),
+    }
+}
+
+pub fn write_table(track: &AssTrack, delimiter: char) -> String {
+    let mut rows = vec![COLUMNS.iter().map(|x| x.to_string()).collect::<Vec<_>>()];
+    for (n, event) in track.events.iter().enumerate() {
+        let t = event.text.as_deref().unwrap_or("");
+        if event.descriptor == "Comment" || text::plain_text(t).trim().is_empty() {
+            continue;
+        }
+        rows.push(vec![
+            n.to_string(),
+            event.start_time.to_string(),
+            event.end_time.to_string(),
+            event.style.as_deref().unwrap_or("").to_owned(),
+            event.actor.as_deref().unwrap_or("").to_owned(),
+            plain(t),
+            text::strip_tags(t),
+        ]);
+    }
+    let mut out = String::new();
+    for row in rows {
+        let row: Vec<String> = row.iter().map(|x| quote(x, delimiter)).collect();
+        out.push_str(&row.join(&delimiter.to_string()));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn plain(s: &str) -> String {
+    text::plain_text(s).replace('\u{a0}', " ")
+}
+
+// rfc 4180, which is also what spreadsheets do for tsv.
+fn parse_rows(s: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            _ if quoted => field.push(c),
+            '\r' => (),
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ if c == delimiter => row.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|x| x.iter().any(|f| !f.is_empty()));
+    rows
+}
+
+// returns the merged track, and what didn't go in cleanly. warnings for rows
+// that don't point at an event say which row in the message instead.
+pub fn merge_table<'a>(
+    track: &AssTrack<'a>,
+    s: &str,
+    delimiter: char,
+) -> Result<(AssTrack<'a>, Vec<Warning>), FormatError> {
+    let s = s.strip_prefix('\u{feff}').unwrap_or(s);
+    let rows = parse_rows(s, delimiter);
+    let (header, rows) = rows.split_first().ok_or(BadTableHeader)?;
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|x| x.trim().eq_ignore_ascii_case(name))
+    };
+    let (id_col, text_col) = match (column("id"), column("text")) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Err(BadTableHeader),
+    };
+    let (start_col, end_col, plain_col) = (column("start"), column("end"), column("plain"));
+
+    let mut res = track.clone();
+    let mut warnings = Vec::new();
+    for (row_n, row) in rows.iter().enumerate() {
+        // the header is row 1.
+        let row_n = row_n + 2;
+        let get = |col: Option<usize>| col.and_then(|x| row.get(x)).map(|x| x.as_str());
+        let event = get(Some(id_col))
+            .and_then(|x| x.trim().parse::<usize>().ok())
+            .filter(|x| *x < res.events.len());
+        let n = match event {
+            Some(n) => n,
+            None => {
+                warnings.push(Warning {
+                    event: None,
+                    message: format!("row {} doesn't match any event, skipped", row_n),
+                });
+                continue;
+            }
+        };
+        let mut warn = |message: String| {
+            warnings.push(Warning {
+                event: Some(n),
+                message,
+            })
+        };
+        let event = &mut res.events[n];
+        let original = event.text.as_deref().unwrap_or("").to_owned();
+
+        if let Some(p) = get(plain_col) {
+            if p.trim() != plain(&original).trim() {
+                warn(format!(
+                    "row {} was exported from different text, skipped",
+                    row_n
+                ));
+                continue;
+            }
+        }
+        for (col, time, what) in &[
+            (start_col, event.start_time, "start"),
+            (end_col, event.end_time, "end"),
+        ] {
+            let changed = get(*col)
+                .map(|x| x.trim().parse::<Timecode>())
+                .is_some_and(|x| x.map_or(true, |t| t.0 != time.0));
+            if changed {
+                warn(format!(
+                    "row {} has a different {} time, kept {}",
+                    row_n, what, time
+                ));
+            }
+        }
+
+        let translation = get(Some(text_col)).unwrap_or("").trim();
+        if translation.is_empty() || translation == text::strip_tags(&original) {
+            continue;
+        }
+        event.text = Some(Cow::Owned(reinsert_tags(&original, translation)));
+    }
+    Ok((res, warnings))
+}
+
+fn reinsert_tags(original: &str, translation: &str) -> String {
+    if text::segments(translation)
+        .iter()
+        .any(|x| matches!(x, Segment::Override(_)))
+    {
+        return translation.to_owned();
+    }
+    let segments = text::segments(original);
+    let text_len: usize = segments
+        .iter()
+        .map(|x| match x {
+            Segment::Text(t) => t.chars().count(),
+            Segment::Override(_) => 0,
+        })
+        .sum();
+    let first_text = segments.iter().position(|x| matches!(x, Segment::Text(_)));
+    let last_text = segments.iter().rposition(|x| matches!(x, Segment::Text(_)));
+
+    let mut lead = String::new();
+    let mut trail = String::new();
+    // (position in the translation, block)
+    let mut inner = Vec::new();
+    let mut at = 0;
+    for (i, segment) in segments.iter().enumerate() {
+        match segment {
+            Segment::Text(t) => at += t.chars().count(),
+            Segment::Override(block) => {
+                let block = format!("{{{}}}", block);
+                if first_text.is_none_or(|x| i < x) {
+                    lead.push_str(&block);
+                } else if last_text.is_some_and(|x| i > x) {
+                    trail.push_str(&block);
+                } else {
+                    inner.push((at as f64 / text_len.max(1) as f64, block));
+                }
+            }
+        }
+    }
+
+    let chars: Vec<char> = translation.chars().collect();
+    // word boundaries, as the char index of each word's start.
+    let mut bounds: Vec<usize> = (1..chars.len())
+        .filter(|x| chars[x - 1] == ' ' && chars[*x] != ' ')
+        .collect();
+    bounds.insert(0, 0);
+    bounds.push(chars.len());
+    let mut out = lead;
+    let mut taken = 0;
+    for (fraction, block) in inner {
+        let target = fraction * chars.len() as f64;
+        let pos = bounds
+            .iter()
+            .copied()
+            .filter(|x| *x >= taken)
+            .min_by(|a, b| {
+                (*a as f64 - target)
+                    .abs()
+                    .total_cmp(&(*b as f64 - target).abs())
+            })
+            .unwrap_or(taken);
+        out.extend(&chars[taken..pos]);
+        out.push_str(&block);
+        taken = pos;
+    }
+    out.extend(&chars[taken..]);
+    out + &trail
+}
@@ -0,0 +1,53 @@
+// tmplayer: hh:mm:ss:text (or = instead of the last :), lines split with |.
+// there are no end times, a line stays up until the next one. an empty line
+// just clears the screen. tmplayer+ files repeat the time with ,1 ,2 ... per
+// line of a multi-line subtitle.
+
+use super::FormatError::{self, BadTmPlayerLine};
+use super::{close_open_ends, join_pipe_lines, parse_lines, track_from_events};
+use crate::ass::AssTrack;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+pub fn parse_track(s: &str) -> Result<AssTrack<'static>, FormatError> {
+    lazy_static! {
+        static ref LINE_RE: Regex =
+            Regex::new(r"^(\d{1,2}):(\d{2}):(\d{2})(?:\.(\d))?(?:,(\d+))?[:=](.*)$").unwrap();
+    }
+    let parsed = parse_lines(s, |line| {
+        let caps = LINE_RE.captures(line).ok_or(BadTmPlayerLine)?;
+        // the part number has no upper bound, so it can be too big for a u32.
+        let n = |i: usize| {
+            caps.get(i)
+                .map_or(Some(0), |x| x.as_str().parse::<u32>().ok())
+        };
+        let n = (1..=5)
+            .map(n)
+            .collect::<Option<Vec<u32>>>()
+            .ok_or(BadTmPlayerLine)?;
+        let start = n[0] * 3_600_000 + n[1] * 60_000 + n[2] * 1000 + n[3] * 100;
+        let continued = n[4] > 1;
+        Ok(Some((start, continued, caps[6].to_owned())))
+    });
+
+    let mut lines = Vec::<(u32, Option<u32>, String)>::new();
+    for (start, continued, text) in parsed {
+        match lines.last_mut() {
+            Some(last) if continued && last.0 == start => {
+                last.2.push('|');
+                last.2.push_str(&text);
+            }
+            _ => lines.push((start, None, text)),
+        }
+    }
+    // blank lines only mark where the one before them ends.
+    let events = close_open_ends(lines)
+        .into_iter()
+        .filter(|x| x.text.as_deref().is_some_and(|t| !t.trim().is_empty()))
+        .map(|mut x| {
+            x.text = x.text.map(|t| join_pipe_lines(&t).into());
+            x
+        })
+        .collect();
+    Ok(track_from_events(events))
+}
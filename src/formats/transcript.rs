@@ -0,0 +1,165 @@
+// readable transcripts for editors: no tags, no line breaks, no comments and
+// no signs, with consecutive lines from the same actor run together into one
+// speech. plain text, markdown, or html laid out like a screenplay.
+//
+// there's no real sign flag in ass, so a line is a sign if its style or
+// effect says "sign", or it's positioned or drawn with \pos, \move or \p.
+
+use super::escape_html;
+use crate::ass::{
+    common::Timecode,
+    event::Event,
+    text::{self, Segment},
+    AssTrack,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    Text,
+    Markdown,
+    Html,
+}
+
+struct Speech {
+    actor: String,
+    start: Timecode,
+    end: Timecode,
+    text: String,
+}
+
+fn is_sign(event: &Event) -> bool {
+    let named = |x: &Option<_>| {
+        x.as_deref()
+            .is_some_and(|x: &str| x.to_ascii_lowercase().contains("sign"))
+    };
+    if named(&event.style) || named(&event.effect) {
+        return true;
+    }
+    text::segments(event.text.as_deref().unwrap_or(""))
+        .into_iter()
+        .any(|x| match x {
+            Segment::Override(block) => text::tags(block).iter().any(|t| match t.name {
+                "pos" | "move" => true,
+                "p" => t.arg.parse::<u32>().is_ok_and(|x| x > 0),
+                _ => false,
+            }),
+            Segment::Text(_) => false,
+        })
+}
+
+// \N, \n and \h all become plain spaces, and runs of them collapse.
+fn flatten(s: &str) -> String {
+    text::plain_text(s)
+        .split(|c: char| c.is_whitespace() || c == '\u{a0}')
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn speeches(track: &AssTrack) -> Vec<Speech> {
+    let mut res: Vec<Speech> = Vec::new();
+    for event in &track.events {
+        if event.descriptor == "Comment" || is_sign(event) {
+            continue;
+        }
+        let line = flatten(event.text.as_deref().unwrap_or(""));
+        if line.is_empty() {
+            continue;
+        }
+        let actor = event.actor.as_deref().unwrap_or("").trim();
+        match res.last_mut() {
+            Some(last) if last.actor == actor => {
+                last.text.push(' ');
+                last.text.push_str(&line);
+                last.end = Timecode(last.end.0.max(event.end_time.0));
+            }
+            _ => res.push(Speech {
+                actor: actor.to_owned(),
+                start: event.start_time,
+                end: event.end_time,
+                text: line,
+            }),
+        }
+    }
+    res
+}
+
+fn escape_markdown(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        if "\\`*_[]<>#|~".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+pub fn write_transcript(track: &AssTrack, layout: Layout) -> String {
+    let title = track.header.title.as_deref().unwrap_or("").trim();
+    let speeches = speeches(track);
+    let mut out = String::new();
+    match layout {
+        Layout::Text => {
+            if !title.is_empty() {
+                out.push_str(&format!("{}\n\n", title));
+            }
+            for x in speeches {
+                match x.actor.is_empty() {
+                    true => out.push_str(&format!("[{} - {}]\n", x.start, x.end)),
+                    false => out.push_str(&format!("[{} - {}] {}\n", x.start, x.end, x.actor)),
+                }
+                out.push_str(&format!("{}\n\n", x.text));
+            }
+        }
+        Layout::Markdown => {
+            if !title.is_empty() {
+                out.push_str(&format!("# {}\n\n", escape_markdown(title)));
+            }
+            for x in speeches {
+                out.push_str(&format!("`{} - {}`", x.start, x.end));
+                if !x.actor.is_empty() {
+                    out.push_str(&format!(" **{}**", escape_markdown(&x.actor)));
+                }
+                out.push_str(&format!("  \n{}\n\n", escape_markdown(&x.text)));
+            }
+        }
+        Layout::Html => {
+            out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+            if !title.is_empty() {
+                out.push_str(&format!("<title>{}</title>\n", escape_html(title)));
+            }
+            out.push_str(concat!(
+                "<style>\n",
+                "body { font-family: \"Courier New\", monospace; max-width: 40em; margin: 2em auto; }\n",
+                ".speech { margin: 1.5em 0; }\n",
+                ".time { color: #888; font-size: 80%; }\n",
+                ".character { margin-left: 20ch; text-transform: uppercase; }\n",
+                ".dialogue { margin-left: 10ch; margin-right: 10ch; }\n",
+                "</style>\n</head>\n<body>\n",
+            ));
+            if !title.is_empty() {
+                out.push_str(&format!("<h1>{}</h1>\n", escape_html(title)));
+            }
+            for x in speeches {
+                out.push_str("<div class=\"speech\">\n");
+                out.push_str(&format!(
+                    "<div class=\"time\">{} - {}</div>\n",
+                    x.start, x.end
+                ));
+                if !x.actor.is_empty() {
+                    out.push_str(&format!(
+                        "<div class=\"character\">{}</div>\n",
+                        escape_html(&x.actor)
+                    ));
+                }
+                out.push_str(&format!(
+                    "<div class=\"dialogue\">{}</div>\n</div>\n",
+                    escape_html(&x.text)
+                ));
+            }
+            out.push_str("</body>\n</html>\n");
+        }
+    }
+    out
+}
@@ -0,0 +1,149 @@
+// webvtt (.vtt): a WEBVTT line, then cues of an optional id, a
+// 00:01.000 --> 00:03.500 line with settings after it, and the text. NOTE,
+// STYLE and REGION blocks are skipped. the markup is <i>, <b>, <u>, <c> and
+// <v Speaker>, which becomes the actor. timestamps inside the text are for
+// karaoke-style reveals and get dropped.
+//
+// of the cue settings only line is looked at, to tell top from bottom.
+
+use super::FormatError::{self, BadVttCue, BadVttDocument};
+use super::{ass_to_html, decode_entities, dialogue, escape_html, html_to_ass, track_from_events};
+use crate::ass::{common::Timecode, text, AssTrack};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::borrow::Cow;
+
+// hours are optional.
+fn parse_time(s: &str) -> Option<u32> {
+    let (rest, ms) = s.split_once('.')?;
+    if ms.len() != 3 {
+        return None;
+    }
+    let parts: Vec<&str> = rest.split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+    let ms = ms.parse::<u32>().ok()?;
+    parts
+        .iter()
+        .try_fold(0u32, |acc, x| {
+            acc.checked_mul(60)?.checked_add(x.parse().ok()?)
+        })?
+        .checked_mul(1000)?
+        .checked_add(ms)
+}
+
+// line:0, line:10% and the like are near the top. negative line numbers
+// count up from the bottom.
+fn near_top(settings: &str) -> bool {
+    settings
+        .split_whitespace()
+        .filter_map(|x| x.strip_prefix("line:"))
+        .map(|x| x.split(',').next().unwrap_or(""))
+        .any(|x| match x.strip_suffix('%') {
+            Some(p) => p.parse::<f64>().is_ok_and(|p| p < 50.0),
+            None => x.parse::<i32>().is_ok_and(|n| n >= 0),
+        })
+}
+
+pub fn parse_track(s: &str) -> Result<AssTrack<'static>, FormatError> {
+    lazy_static! {
+        static ref VOICE_RE: Regex = Regex::new(r"<v(?:\.[^\s>]*)?\s+([^>]*)>").unwrap();
+        static ref STAMP_RE: Regex = Regex::new(r"<\d[\d:.]*>").unwrap();
+    }
+    let s = s.replace("\r\n", "\n");
+    if !s.trim_start_matches('\u{feff}').starts_with("WEBVTT") {
+        return Err(BadVttDocument);
+    }
+    let mut events = Vec::new();
+    for (n, block) in s.split("\n\n").enumerate().skip(1) {
+        let lines: Vec<&str> = block.lines().map(|x| x.trim_end()).collect();
+        if lines.is_empty() {
+            continue;
+        }
+        let at = match lines.iter().position(|x| x.contains("-->")) {
+            Some(x) if x <= 1 => x,
+            // notes, styles, regions, and blank runs between cues.
+            _ if lines.iter().all(|x| x.is_empty())
+                || ["NOTE", "STYLE", "REGION"]
+                    .iter()
+                    .any(|x| lines[0].starts_with(x)) =>
+            {
+                continue
+            }
+            _ => {
+                eprintln!("Dropped cue {}: {}", n, BadVttCue);
+                continue;
+            }
+        };
+        let (start, rest) = lines[at].split_once("-->").unwrap_or_default();
+        let mut rest = rest.trim().splitn(2, char::is_whitespace);
+        let end = rest.next().unwrap_or("");
+        let settings = rest.next().unwrap_or("");
+        let (start, end) = match (parse_time(start.trim()), parse_time(end)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => {
+                eprintln!("Dropped cue {}: {}", n, BadVttCue);
+                continue;
+            }
+        };
+        let body = lines[at + 1..].join("<br>");
+        let actor = VOICE_RE
+            .captures(&body)
+            .map(|x| decode_entities(x[1].trim()));
+        let body = STAMP_RE.replace_all(&body, "");
+        let mut text = html_to_ass(&body);
+        if near_top(settings) {
+            text = format!("{{\\an8}}{}", text);
+        }
+        let mut event = dialogue(start, end, text);
+        event.actor = actor.filter(|x| !x.is_empty()).map(Cow::Owned);
+        events.push(event);
+    }
+    Ok(track_from_events(events))
+}
+
+pub fn write_track(track: &AssTrack) -> String {
+    lazy_static! {
+        static ref FONT_RE: Regex = Regex::new(r"</?font[^>]*>").unwrap();
+    }
+    let time = |t: Timecode| {
+        let ms = t.as_millis();
+        format!(
+            "{:02}:{:02}:{:02}.{:03}",
+            ms / 3_600_000,
+            ms / 60_000 % 60,
+            ms / 1000 % 60,
+            ms % 1000
+        )
+    };
+    let mut out = vec!["WEBVTT\n".to_owned()];
+    for event in track.events.iter().filter(|x| x.descriptor != "Comment") {
+        let text = event.text.as_deref().unwrap_or("");
+        // webvtt colours go through classes and a STYLE block, which isn't
+        // worth it for what <font> would have said.
+        let body = ass_to_html(text, true).replace("<br>", "\n");
+        let mut body = FONT_RE.replace_all(&body, "").into_owned();
+        if text::plain_text(text).trim().is_empty() {
+            continue;
+        }
+        if let Some(actor) = event.actor.as_deref().filter(|x| !x.is_empty()) {
+            body = format!("<v {}>{}", escape_html(actor), body);
+        }
+        let (alignment, _) = text::placement(text);
+        let alignment =
+            alignment.or_else(|| track.style(event.style.as_deref()).map(|x| x.alignment));
+        let settings = match alignment.map(u8::from) {
+            Some(7..=9) => " line:0",
+            _ => "",
+        };
+        out.push(format!(
+            "{} --> {}{}\n{}\n",
+            time(event.start_time),
+            time(event.end_time),
+            settings,
+            body.trim()
+        ));
+    }
+    out.join("\n")
+}
@@ -0,0 +1,149 @@
+// word timings from speech recognition, grouped into lines. the schema is
+// a list of words, each an object with
+//
+//   word     the word, with any punctuation attached ("text" works too)
+//   start    seconds, as a number
+//   end      seconds, as a number
+//   speaker  optional, any string or number
+//
+// the list can be the whole document, under "words", or split over
+// "segments" that each have their own "words", which is what whisper and
+// most tools built on it write. a speaker on a segment goes for all of its
+// words.
+//
+// a line ends at a change of speaker, a pause, or when it would get too long
+// or too many characters. with karaoke on, every word gets a \k for its own
+// length plus the gap after it, so the syllable timing adds up to the line.
+
+use super::track_from_events;
+use super::FormatError::{self, BadWord, BadWordsDocument};
+use crate::ass::{common::Timecode, event::Event, AssTrack};
+use serde_json::Value;
+use smart_default::SmartDefault;
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, Copy, PartialEq, SmartDefault)]
+pub struct Grouping {
+    // a gap longer than this between words starts a new line, in ms.
+    #[default(700)]
+    pub max_pause: u32,
+    // in ms, from the start of the first word to the end of the last.
+    #[default(6_000)]
+    pub max_duration: u32,
+    #[default(42)]
+    pub max_chars: usize,
+    pub karaoke: bool,
+}
+
+struct Word {
+    text: String,
+    start: u32,
+    end: u32,
+    speaker: Option<String>,
+}
+
+fn speaker(v: &Value) -> Option<String> {
+    match v {
+        Value::String(x) if !x.trim().is_empty() => Some(x.trim().to_owned()),
+        Value::Number(x) => Some(x.to_string()),
+        _ => None,
+    }
+}
+
+fn word(v: &Value, segment_speaker: Option<&String>) -> Option<Word> {
+    let text = v["word"].as_str().or_else(|| v["text"].as_str())?.trim();
+    let ms = |key: &str| {
+        v[key]
+            .as_f64()
+            .filter(|x| *x >= 0.0)
+            .map(|x| (x * 1000.0).round() as u32)
+    };
+    let start = ms("start")?;
+    let end = ms("end")?.max(start);
+    if text.is_empty() {
+        return None;
+    }
+    Some(Word {
+        text: text.to_owned(),
+        start,
+        end,
+        speaker: speaker(&v["speaker"]).or_else(|| segment_speaker.cloned()),
+    })
+}
+
+fn words(root: &Value) -> Option<Vec<Word>> {
+    let mut res = Vec::new();
+    let mut add = |list: &Vec<Value>, segment_speaker: Option<String>| {
+        for (n, v) in list.iter().enumerate() {
+            match word(v, segment_speaker.as_ref()) {
+                Some(x) => res.push(x),
+                None => eprintln!("Dropped word {}: {}", n, BadWord),
+            }
+        }
+    };
+    if let Some(list) = root.as_array() {
+        add(list, None);
+    } else if let Some(list) = root["words"].as_array() {
+        add(list, None);
+    } else {
+        for segment in root["segments"].as_array()? {
+            if let Some(list) = segment["words"].as_array() {
+                add(list, speaker(&segment["speaker"]));
+            }
+        }
+    }
+    Some(res)
+}
+
+fn line(words: &[Word], karaoke: bool) -> Event<'static> {
+    let start = words[0].start;
+    let end = words.iter().map(|x| x.end).max().unwrap_or(start);
+    let mut text = String::new();
+    for (n, word) in words.iter().enumerate() {
+        if n > 0 {
+            text.push(' ');
+        }
+        if karaoke {
+            // centiseconds, measured from the line start so rounding
+            // doesn't pile up along the line.
+            let until = words.get(n + 1).map_or(end, |x| x.start).max(word.start);
+            let cs = |ms: u32| (ms - start + 5) / 10;
+            text.push_str(&format!("{{\\k{}}}", cs(until) - cs(word.start)));
+        }
+        text.push_str(&word.text.replace(['{', '}'], ""));
+    }
+    Event {
+        start_time: Timecode::from(start),
+        end_time: Timecode::from(end),
+        actor: words[0].speaker.clone().map(Cow::Owned),
+        text: Some(Cow::Owned(text)),
+        ..Event::default()
+    }
+}
+
+pub fn parse_track(s: &str, grouping: Grouping) -> Result<AssTrack<'static>, FormatError> {
+    let root: Value = serde_json::from_str(s).or(Err(BadWordsDocument))?;
+    let mut words = words(&root).ok_or(BadWordsDocument)?;
+    words.sort_by_key(|x| x.start);
+
+    let mut events = Vec::new();
+    let mut current: Vec<Word> = Vec::new();
+    for word in words {
+        if let (Some(first), Some(last)) = (current.first(), current.last()) {
+            let chars: usize = current.iter().map(|x| x.text.chars().count() + 1).sum();
+            let split = word.speaker != first.speaker
+                || word.start.saturating_sub(last.end) > grouping.max_pause
+                || word.end.saturating_sub(first.start) > grouping.max_duration
+                || chars + word.text.chars().count() > grouping.max_chars;
+            if split {
+                events.push(line(&current, grouping.karaoke));
+                current.clear();
+            }
+        }
+        current.push(word);
+    }
+    if !current.is_empty() {
+        events.push(line(&current, grouping.karaoke));
+    }
+    Ok(track_from_events(events))
+}
@@ -0,0 +1,272 @@
+// xliff 1.2 and 2.0. each dialogue line is a unit with the event index as
+// its id, the style, actor and time as notes, and the override blocks as
+// <ph> placeholders that carry the original tags (as content in 1.2, as
+// originalData in 2.0).
+//
+// reading only looks at units, sources, targets and the inline elements
+// that can stand for a placeholder, so a small tokenizer is enough. tools
+// that swap <ph> for <x/> or wrap things in <g>/<pc>/<mrk> are fine.
+
+use super::placeholders::{self, Piece, Translation};
+use super::FormatError::{self, BadXliffDocument};
+use super::{decode_entities, escape_html, Warning};
+use crate::ass::AssTrack;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Version {
+    V1_2,
+    V2_0,
+}
+
+fn source_language(track: &AssTrack) -> String {
+    track
+        .header
+        .language
+        .as_deref()
+        .map_or("und".to_owned(), |x| escape_html(x.trim()))
+}
+
+fn inline(pieces: &[Piece], codes: &[String], version: Version) -> String {
+    pieces
+        .iter()
+        .map(|x| match (x, version) {
+            (Piece::Text(t), _) => escape_html(t),
+            (Piece::Code(n), Version::V1_2) => {
+                format!("<ph id=\"{}\">{}</ph>", n, escape_html(&codes[n - 1]))
+            }
+            (Piece::Code(n), Version::V2_0) => format!("<ph id=\"{}\" dataRef=\"d{}\"/>", n, n),
+        })
+        .collect()
+}
+
+pub fn write_xliff(track: &AssTrack, version: Version) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let lang = source_language(track);
+    let original = escape_html(track.header.title.as_deref().unwrap_or("script.ass"));
+    match version {
+        Version::V1_2 => {
+            out.push_str(
+                "<xliff version=\"1.2\" xmlns=\"urn:oasis:names:tc:xliff:document:1.2\">\n",
+            );
+            out.push_str(&format!(
+                "  <file original=\"{}\" source-language=\"{}\" datatype=\"plaintext\">\n    <body>\n",
+                original, lang
+            ));
+        }
+        Version::V2_0 => {
+            out.push_str(&format!(
+                "<xliff version=\"2.0\" xmlns=\"urn:oasis:names:tc:xliff:document:2.0\" srcLang=\"{}\">\n",
+                lang
+            ));
+            out.push_str(&format!("  <file id=\"f1\" original=\"{}\">\n", original));
+        }
+    }
+
+    for unit in placeholders::units(track) {
+        let mut notes = vec![("style", unit.style.clone())];
+        if !unit.actor.is_empty() {
+            notes.push(("actor", unit.actor.clone()));
+        }
+        notes.push(("time", unit.time()));
+        let source = inline(&unit.source, &unit.codes, version);
+        match version {
+            Version::V1_2 => {
+                out.push_str(&format!(
+                    "      <trans-unit id=\"{}\" xml:space=\"preserve\">\n",
+                    unit.id
+                ));
+                out.push_str(&format!("        <source>{}</source>\n", source));
+                for (category, note) in notes {
+                    out.push_str(&format!(
+                        "        <note from=\"{}\">{}</note>\n",
+                        category,
+                        escape_html(&note)
+                    ));
+                }
+                out.push_str("      </trans-unit>\n");
+            }
+            Version::V2_0 => {
+                out.push_str(&format!("    <unit id=\"{}\">\n      <notes>\n", unit.id));
+                for (category, note) in notes {
+                    out.push_str(&format!(
+                        "        <note category=\"{}\">{}</note>\n",
+                        category,
+                        escape_html(&note)
+                    ));
+                }
+                out.push_str("      </notes>\n");
+                if !unit.codes.is_empty() {
+                    out.push_str("      <originalData>\n");
+                    for (n, code) in unit.codes.iter().enumerate() {
+                        out.push_str(&format!(
+                            "        <data id=\"d{}\">{}</data>\n",
+                            n + 1,
+                            escape_html(code)
+                        ));
+                    }
+                    out.push_str("      </originalData>\n");
+                }
+                out.push_str(&format!(
+                    "      <segment>\n        <source xml:space=\"preserve\">{}</source>\n      </segment>\n",
+                    source
+                ));
+                out.push_str("    </unit>\n");
+            }
+        }
+    }
+
+    match version {
+        Version::V1_2 => out.push_str("    </body>\n  </file>\n</xliff>\n"),
+        Version::V2_0 => out.push_str("  </file>\n</xliff>\n"),
+    }
+    out
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    // local name (no namespace prefix), and the attributes.
+    Start(String, Vec<(String, String)>),
+    End(String),
+    Empty(String, Vec<(String, String)>),
+    Text(String),
+}
+
+fn local_name(s: &str) -> String {
+    s.rsplit(':').next().unwrap_or(s).to_owned()
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, FormatError> {
+    lazy_static! {
+        static ref ATTR_RE: Regex =
+            Regex::new(r#"([\w:.-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+    }
+    let mut res = Vec::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let skip_to = |rest: &str, end: &str| rest.find(end).map(|x| x + end.len());
+        if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after.find("]]>").ok_or(BadXliffDocument)?;
+            res.push(Token::Text(after[..end].to_owned()));
+            rest = &after[end + 3..];
+        } else if rest.starts_with("<!--") {
+            rest = &rest[skip_to(rest, "-->").ok_or(BadXliffDocument)?..];
+        } else if rest.starts_with("<?") {
+            rest = &rest[skip_to(rest, "?>").ok_or(BadXliffDocument)?..];
+        } else if rest.starts_with("<!") {
+            rest = &rest[skip_to(rest, ">").ok_or(BadXliffDocument)?..];
+        } else if let Some(after) = rest.strip_prefix("</") {
+            let end = after.find('>').ok_or(BadXliffDocument)?;
+            res.push(Token::End(local_name(after[..end].trim())));
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('<') {
+            let end = after.find('>').ok_or(BadXliffDocument)?;
+            let tag = &after[..end];
+            let (tag, empty) = match tag.strip_suffix('/') {
+                Some(x) => (x, true),
+                None => (tag, false),
+            };
+            let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+            let name = local_name(&tag[..name_end]);
+            let attrs = ATTR_RE
+                .captures_iter(&tag[name_end..])
+                .map(|c| {
+                    let value = c.get(2).or_else(|| c.get(3)).map_or("", |x| x.as_str());
+                    (local_name(&c[1]), decode_entities(value))
+                })
+                .collect();
+            res.push(match empty {
+                true => Token::Empty(name, attrs),
+                false => Token::Start(name, attrs),
+            });
+            rest = &after[end + 1..];
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            res.push(Token::Text(decode_entities(&rest[..end])));
+            rest = &rest[end..];
+        }
+    }
+    Ok(res)
+}
+
+fn attr<'a>(attrs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attrs.iter().find(|x| x.0 == name).map(|x| x.1.as_str())
+}
+
+// elements whose content is the placeholder's original code, not text.
+const CODE_ELEMENTS: &[&str] = &["ph", "bpt", "ept", "it", "data"];
+
+pub fn merge_xliff<'a>(
+    track: &AssTrack<'a>,
+    s: &str,
+) -> Result<(AssTrack<'a>, Vec<Warning>), FormatError> {
+    let tokens = tokenize(s)?;
+    if !tokens
+        .iter()
+        .any(|x| matches!(x, Token::Start(name, _) if name == "xliff"))
+    {
+        return Err(BadXliffDocument);
+    }
+
+    let mut translations = Vec::new();
+    let mut current: Option<Translation> = None;
+    // where text is going: "source", "target", or nowhere.
+    let mut into = "";
+    // inside a placeholder's own content, which isn't translatable text.
+    let mut code_depth = 0usize;
+    for token in tokens {
+        let is_start = matches!(token, Token::Start(..));
+        match token {
+            Token::Start(name, attrs) | Token::Empty(name, attrs)
+                if name == "trans-unit" || name == "unit" =>
+            {
+                current = Some(Translation {
+                    id: attr(&attrs, "id").unwrap_or("").to_owned(),
+                    source: Some(Vec::new()),
+                    target: Vec::new(),
+                });
+            }
+            Token::End(name) if name == "trans-unit" || name == "unit" => {
+                translations.extend(current.take());
+            }
+            Token::Start(name, _) if name == "source" || name == "target" => {
+                into = if name == "source" { "source" } else { "target" };
+            }
+            Token::End(name) if name == "source" || name == "target" => into = "",
+            Token::Start(name, attrs) | Token::Empty(name, attrs)
+                if CODE_ELEMENTS.contains(&name.as_str()) || name == "x" =>
+            {
+                // <ph>code</ph> has content to skip, <ph/> doesn't.
+                let has_content = is_start && name != "x";
+                if !into.is_empty() && code_depth == 0 {
+                    let n = attr(&attrs, "id").and_then(|x| x.trim().parse::<usize>().ok());
+                    if let (Some(n), Some(t)) = (n, current.as_mut()) {
+                        let pieces = match into {
+                            "source" => t.source.get_or_insert_with(Vec::new),
+                            _ => &mut t.target,
+                        };
+                        pieces.push(Piece::Code(n));
+                    }
+                }
+                if has_content {
+                    code_depth += 1;
+                }
+            }
+            Token::End(name) if CODE_ELEMENTS.contains(&name.as_str()) => {
+                code_depth = code_depth.saturating_sub(1);
+            }
+            Token::Text(text) if !into.is_empty() && code_depth == 0 => {
+                if let Some(t) = current.as_mut() {
+                    let pieces = match into {
+                        "source" => t.source.get_or_insert_with(Vec::new),
+                        _ => &mut t.target,
+                    };
+                    pieces.push(Piece::Text(text));
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(placeholders::merge(track, translations))
+}
@@ -1,48 +1,77 @@
 use std::error::Error;
-use std::fs;
 
-mod ass;
-use ass::AssTrack;
+pub mod ass;
+pub mod batch;
+pub mod check;
+pub mod cli;
+pub mod diagnostics;
+pub mod diff;
+pub mod edit;
+pub mod files;
+pub mod formats;
+pub mod project;
+pub mod registry;
+pub mod resample;
+pub mod summary;
+pub mod threeway;
+pub mod watch;
+use project::Settings;
+use registry::Registry;
 
 pub struct Config {
     pub input: String,
     pub output: String,
+    // format names that win over the extensions, for when they say wrong.
+    pub from: Option<String>,
+    pub to: Option<String>,
+    // .ass2ass.toml, whose from and to are only for extensions that don't
+    // say anything.
+    pub settings: Settings,
 }
 
 impl Config {
-    pub fn new(args: &[String]) -> Result<Self, String> {
-        if args.len() != 3 {
-            return Err("not enough arguments".to_owned());
-        }
-
-        let input = &args[1];
-        if !input.ends_with(".ass") {
-            return Err(format!("file extension must be .ass : {}", input));
-        }
-
-        let output = &args[2];
-        if !output.ends_with(".ass") {
-            return Err(format!("file extension must be .ass : {}", output));
-        }
-
-        let (input, output) = (input.into(), output.into());
-        Ok(Self { input, output })
+    // with whatever project file is above the input.
+    pub fn discover(input: &str, output: &str) -> Result<Config, Box<dyn Error>> {
+        let start = match input {
+            files::STDIO => ".",
+            x => x,
+        };
+        let (_, settings) = Settings::discover(std::path::Path::new(start))?;
+        Ok(Config {
+            input: input.to_owned(),
+            output: output.to_owned(),
+            from: None,
+            to: None,
+            settings,
+        })
     }
 }
 
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let instring = fs::read_to_string(config.input)?;
-    let instring = strip_bom(instring.as_str());
+    run_with(config, &Registry::builtin())
+}
 
-    let track = AssTrack::parse_track(&instring)?;
+// for other crates that add formats of their own to the registry.
+pub fn run_with(config: Config, registry: &Registry) -> Result<(), Box<dyn Error>> {
+    let settings = &config.settings;
+    let options = settings.options();
+    let from = registry.name_for(
+        config.from.as_deref(),
+        settings.from.as_deref(),
+        &config.input,
+    );
+    let to = registry.name_for(config.to.as_deref(), settings.to.as_deref(), &config.output);
+    let reader = registry.reader(from, &config.input)?;
+    let writer = registry.writer(to, &config.output)?;
 
-    let outstring = track.to_string();
+    let mut track = reader.read(&mut *files::open(&config.input)?, &options)?;
+    settings.apply(&mut track);
+    let (out, warnings) = writer.write(&track, &options)?;
+    for warning in warnings {
+        eprintln!("{}", warning);
+    }
 
-    fs::write(config.output, outstring)?;
+    files::write(&config.output, &out)?;
 
     Ok(())
 }
-
-fn strip_bom(s: &str) -> &str {
-    s.strip_prefix("\u{feff}").unwrap_or(s)
-}
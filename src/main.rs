@@ -1,19 +1,19 @@
 use std::env;
 use std::process;
 
-use ass2ass::Config;
+use ass2ass::{cli, registry::Registry};
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let cli = cli::parse(env::args_os());
 
-    let config = Config::new(&args).unwrap_or_else(|err| {
-        eprintln!("Problem parsing arguments: {}", err);
-        process::exit(1);
-    });
-
-    if let Err(e) = ass2ass::run(config) {
+    if let Err(e) = cli.run(&Registry::builtin()) {
+        let code = match e.downcast_ref::<cli::Exit>() {
+            Some(x) if x.message.is_empty() => process::exit(x.code),
+            Some(x) => x.code,
+            None => 1,
+        };
         eprintln!("Application error: {}", e);
 
-        process::exit(1);
+        process::exit(code);
     }
 }
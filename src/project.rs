@@ -0,0 +1,157 @@
+// project.rs
+// .ass2ass.toml, for the flags a whole team would otherwise pass every time.
+// the first one found walking up from the input is used, and anything given
+// on the command line wins over it. looks like:
+//
+//     to = "ass"
+//     fps = 23.976
+//     styles = ["Default,Open Sans,52,&H00FFFFFF,&H000000FF,&H00000000,..."]
+//
+//     [normalize]
+//     unicode = "nfc"
+//     trim = true
+//
+//     [lint]
+//     max_cps = 20
+//     max_line_length = 42
+//
+// style presets are style lines in the default v4+ format, and replace a
+// script's style of the same name or get added if there isn't one.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::ass::{style::Style, AssTrack};
+use crate::check::Thresholds;
+use crate::edit::{self, Normalize};
+use crate::registry::Options;
+
+pub const FILE_NAME: &str = ".ass2ass.toml";
+
+//------------------------------------------------------------------------------
+// errors
+//------------------------------------------------------------------------------
+
+#[derive(Error, Debug)]
+pub enum ProjectError {
+    #[error("{0}: style preset {1:?} isn't a valid style line.")]
+    BadStylePreset(PathBuf, String),
+    #[error("{0}: {1}")]
+    BadToml(PathBuf, toml::de::Error),
+    #[error("{0}: {1}")]
+    Io(PathBuf, std::io::Error),
+}
+
+//------------------------------------------------------------------------------
+// settings
+//------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub styles: Vec<String>,
+    pub normalize: Normalize,
+    pub lint: Thresholds,
+}
+
+impl Settings {
+    pub fn load(path: &Path) -> Result<Settings, ProjectError> {
+        let s = fs::read_to_string(path).map_err(|e| ProjectError::Io(path.to_owned(), e))?;
+        let res: Settings =
+            toml::from_str(&s).map_err(|e| ProjectError::BadToml(path.to_owned(), e))?;
+        // better to hear about a bad preset now than halfway through a batch.
+        for x in &res.styles {
+            if Style::parse(x, None).is_err() {
+                return Err(ProjectError::BadStylePreset(path.to_owned(), x.clone()));
+            }
+        }
+        Ok(res)
+    }
+
+    // the nearest project file in start or above it, and what it says. no
+    // file is just the defaults.
+    pub fn discover(start: &Path) -> Result<(Option<PathBuf>, Settings), ProjectError> {
+        match find(start) {
+            Some(x) => {
+                let res = Self::load(&x)?;
+                Ok((Some(x), res))
+            }
+            None => Ok((None, Settings::default())),
+        }
+    }
+
+    // anything set in over replaces what's here. the tables go as a whole,
+    // since half of one set of house rules is nobody's house rules.
+    pub fn merge(self, over: Settings) -> Settings {
+        let default = Settings::default();
+        Settings {
+            from: over.from.or(self.from),
+            to: over.to.or(self.to),
+            fps: over.fps.or(self.fps),
+            styles: match over.styles.is_empty() {
+                true => self.styles,
+                false => over.styles,
+            },
+            normalize: match over.normalize == default.normalize {
+                true => self.normalize,
+                false => over.normalize,
+            },
+            lint: match over.lint == default.lint {
+                true => self.lint,
+                false => over.lint,
+            },
+        }
+    }
+
+    pub fn options(&self) -> Options {
+        let mut res = Options::default();
+        if let Some(x) = self.fps {
+            res.fps = x;
+        }
+        res
+    }
+
+    // the presets and the normalization, for a track that's about to be
+    // written.
+    pub fn apply(&self, track: &mut AssTrack) {
+        for preset in &self.styles {
+            // checked when loaded, so only ones made in code can be bad.
+            let style = match Style::parse(preset, None) {
+                Ok(x) => x.into_owned(),
+                Err(_) => continue,
+            };
+            match track.styles.iter_mut().find(|x| x.name == style.name) {
+                Some(x) => *x = style,
+                None => track.styles.push(style),
+            }
+        }
+        edit::normalize(track, &self.normalize);
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).unwrap_or_default()
+    }
+}
+
+fn find(start: &Path) -> Option<PathBuf> {
+    let start = fs::canonicalize(start).unwrap_or_else(|_| start.to_owned());
+    let dir = match start.is_dir() {
+        true => start.as_path(),
+        false => start.parent()?,
+    };
+    dir.ancestors()
+        .map(|x| x.join(FILE_NAME))
+        .find(|x| x.is_file())
+}
@@ -0,0 +1,415 @@
+// registry.rs
+// the formats the cli knows about, looked up by name (--from/--to) or by
+// file extension. every format has a reader, a writer, or both, and other
+// crates can add their own to a registry before handing it to run.
+//
+// extensions only go to formats that can be told apart by them. .txt could
+// be half a dozen things, so those formats have to be asked for by name.
+
+use std::{
+    borrow::Cow,
+    error::Error,
+    io::{Read, Seek},
+    path::Path,
+};
+
+use smart_default::SmartDefault;
+use thiserror::Error;
+
+use crate::ass::AssTrack;
+use crate::formats::{
+    audacity, fountain, json3, lrc, matroska, microdvd, mpl2, po, sami, sbv, scc, srt, stl, table,
+    tmplayer, transcript, vtt, words, xliff, FormatError, Warning,
+};
+
+//------------------------------------------------------------------------------
+// errors
+//------------------------------------------------------------------------------
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RegistryError {
+    #[error("No format can read {0}.")]
+    CannotRead(String),
+    #[error("No format can write {0}.")]
+    CannotWrite(String),
+    #[error("No format for the extension of {0}, use --from or --to.")]
+    UnknownExtension(String),
+    #[error("Unknown format: {0}")]
+    UnknownFormat(String),
+}
+
+//------------------------------------------------------------------------------
+// traits
+//------------------------------------------------------------------------------
+
+// settings that some formats need and can't work out from the file.
+#[derive(Debug, Clone, PartialEq, SmartDefault)]
+pub struct Options {
+    // for frame-based formats that don't say.
+    #[default(23.976)]
+    pub fps: f64,
+}
+
+// where a reader gets its input. seekable, so container formats only read
+// the parts they need instead of the whole file.
+pub trait Source: Read + Seek {}
+impl<T: Read + Seek> Source for T {}
+
+// Send and Sync so batches can share a registry across threads.
+pub trait Reader: Send + Sync {
+    fn read(
+        &self,
+        source: &mut dyn Source,
+        options: &Options,
+    ) -> Result<AssTrack<'static>, Box<dyn Error>>;
+}
+
+// writers that have to leave things out say so in the warnings.
+pub trait Writer: Send + Sync {
+    fn write(
+        &self,
+        track: &AssTrack,
+        options: &Options,
+    ) -> Result<(Vec<u8>, Vec<Warning>), Box<dyn Error>>;
+}
+
+impl<F> Reader for F
+where
+    F: Fn(&mut dyn Source, &Options) -> Result<AssTrack<'static>, Box<dyn Error>> + Send + Sync,
+{
+    fn read(
+        &self,
+        source: &mut dyn Source,
+        options: &Options,
+    ) -> Result<AssTrack<'static>, Box<dyn Error>> {
+        self(source, options)
+    }
+}
+
+impl<F> Writer for F
+where
+    F: Fn(&AssTrack, &Options) -> Result<(Vec<u8>, Vec<Warning>), Box<dyn Error>> + Send + Sync,
+{
+    fn write(
+        &self,
+        track: &AssTrack,
+        options: &Options,
+    ) -> Result<(Vec<u8>, Vec<Warning>), Box<dyn Error>> {
+        self(track, options)
+    }
+}
+
+//------------------------------------------------------------------------------
+// registry
+//------------------------------------------------------------------------------
+
+pub struct Format {
+    pub name: String,
+    // lowercase, without the dot.
+    pub extensions: Vec<String>,
+    pub reader: Option<Box<dyn Reader>>,
+    pub writer: Option<Box<dyn Writer>>,
+}
+
+#[derive(Default)]
+pub struct Registry {
+    formats: Vec<Format>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // a format registered under a name that's already taken replaces it.
+    pub fn register(&mut self, format: Format) {
+        self.formats
+            .retain(|x| !x.name.eq_ignore_ascii_case(&format.name));
+        self.formats.push(format);
+    }
+
+    pub fn formats(&self) -> &[Format] {
+        &self.formats
+    }
+
+    pub fn by_name(&self, name: &str) -> Result<&Format, RegistryError> {
+        self.formats
+            .iter()
+            .find(|x| x.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| RegistryError::UnknownFormat(name.to_owned()))
+    }
+
+    // the last format registered for an extension wins, so added formats
+    // can take one over from the built in ones.
+    pub fn by_path(&self, path: &str) -> Result<&Format, RegistryError> {
+        let ext = Path::new(path)
+            .extension()
+            .map(|x| x.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        self.formats
+            .iter()
+            .rev()
+            .find(|x| x.extensions.contains(&ext))
+            .ok_or_else(|| RegistryError::UnknownExtension(path.to_owned()))
+    }
+
+    // the name to pass to reader or writer: one given outright wins, then
+    // the path's extension, and the fallback only if that says nothing.
+    pub fn name_for<'a>(
+        &self,
+        name: Option<&'a str>,
+        fallback: Option<&'a str>,
+        path: &str,
+    ) -> Option<&'a str> {
+        match name {
+            Some(x) => Some(x),
+            None if self.by_path(path).is_ok() => None,
+            None => fallback,
+        }
+    }
+
+    // by name if there is one, by the path's extension if not.
+    pub fn reader(&self, name: Option<&str>, path: &str) -> Result<&dyn Reader, RegistryError> {
+        let format = match name {
+            Some(x) => self.by_name(x)?,
+            None => self.by_path(path)?,
+        };
+        format
+            .reader
+            .as_deref()
+            .ok_or_else(|| RegistryError::CannotRead(format.name.clone()))
+    }
+
+    pub fn writer(&self, name: Option<&str>, path: &str) -> Result<&dyn Writer, RegistryError> {
+        let format = match name {
+            Some(x) => self.by_name(x)?,
+            None => self.by_path(path)?,
+        };
+        format
+            .writer
+            .as_deref()
+            .ok_or_else(|| RegistryError::CannotWrite(format.name.clone()))
+    }
+
+    // everything in formats, plus ass itself.
+    pub fn builtin() -> Self {
+        let mut res = Self::new();
+        let mut add = |name: &str,
+                       extensions: &[&str],
+                       reader: Option<Box<dyn Reader>>,
+                       writer: Option<Box<dyn Writer>>| {
+            res.register(Format {
+                name: name.to_owned(),
+                extensions: extensions.iter().map(|x| x.to_string()).collect(),
+                reader,
+                writer,
+            })
+        };
+        add(
+            "ass",
+            &["ass", "ssa"],
+            Some(text_reader(|s, _| {
+                Ok(AssTrack::parse_track(s)?.into_owned())
+            })),
+            Some(text_writer(|t, _| Ok((t.to_string(), vec![])))),
+        );
+        add(
+            "audacity",
+            &[],
+            Some(text_reader(|s, _| Ok(audacity::parse_track(s, "Default")?))),
+            Some(text_writer(|t, _| Ok((audacity::write_track(t), vec![])))),
+        );
+        add(
+            "fountain",
+            &["fountain", "spmd"],
+            Some(text_reader(|s, _| Ok(fountain::parse_track(s, 0)?))),
+            None,
+        );
+        add(
+            "json3",
+            &["json3"],
+            Some(text_reader(|s, _| Ok(json3::parse_track(s)?))),
+            Some(text_writer(|t, _| Ok((json3::write_track(t), vec![])))),
+        );
+        add(
+            "lrc",
+            &["lrc"],
+            Some(text_reader(|s, _| Ok(lrc::parse_track(s)?))),
+            Some(text_writer(|t, _| Ok((lrc::write_track(t), vec![])))),
+        );
+        add(
+            "matroska",
+            &["mkv", "mka", "mks"],
+            Some(Box::new(read_matroska)),
+            None,
+        );
+        add(
+            "microdvd",
+            &["sub"],
+            Some(text_reader(|s, o| Ok(microdvd::parse_track(s, o.fps)?))),
+            None,
+        );
+        add(
+            "mpl2",
+            &[],
+            Some(text_reader(|s, _| Ok(mpl2::parse_track(s)?))),
+            None,
+        );
+        add(
+            "sami",
+            &["smi", "sami"],
+            Some(text_reader(|s, _| Ok(sami::parse_track(s)?))),
+            Some(text_writer(|t, _| Ok((sami::write_track(t), vec![])))),
+        );
+        add(
+            "sbv",
+            &["sbv"],
+            Some(text_reader(|s, _| Ok(sbv::parse_track(s)?))),
+            Some(text_writer(|t, _| Ok((sbv::write_track(t), vec![])))),
+        );
+        add(
+            "scc",
+            &["scc"],
+            None,
+            Some(text_writer(|t, _| Ok(scc::write_track(t)))),
+        );
+        add(
+            "srt",
+            &["srt"],
+            Some(text_reader(|s, _| Ok(srt::parse_track(s)?))),
+            Some(text_writer(|t, _| Ok((srt::write_track(t), vec![])))),
+        );
+        add(
+            "stl",
+            &["stl"],
+            Some(Box::new(
+                |r: &mut dyn Source, _: &Options| -> Result<_, Box<dyn Error>> {
+                    Ok(stl::parse_track(&read_all(r)?)?)
+                },
+            )),
+            Some(Box::new(
+                |t: &AssTrack, _: &Options| -> Result<_, Box<dyn Error>> {
+                    Ok(stl::write_track(t, Default::default(), Default::default()))
+                },
+            )),
+        );
+        add(
+            "tmplayer",
+            &[],
+            Some(text_reader(|s, _| Ok(tmplayer::parse_track(s)?))),
+            None,
+        );
+        add(
+            "vtt",
+            &["vtt"],
+            Some(text_reader(|s, _| Ok(vtt::parse_track(s)?))),
+            Some(text_writer(|t, _| Ok((vtt::write_track(t), vec![])))),
+        );
+        add(
+            "words",
+            &[],
+            Some(text_reader(|s, _| {
+                Ok(words::parse_track(s, Default::default())?)
+            })),
+            None,
+        );
+
+        // exports for translation, which get merged back rather than read.
+        add(
+            "csv",
+            &["csv"],
+            None,
+            Some(text_writer(|t, _| Ok((table::write_table(t, ','), vec![])))),
+        );
+        add(
+            "tsv",
+            &["tsv"],
+            None,
+            Some(text_writer(|t, _| {
+                Ok((table::write_table(t, '\t'), vec![]))
+            })),
+        );
+        add(
+            "po",
+            &["po", "pot"],
+            None,
+            Some(text_writer(|t, _| Ok((po::write_po(t), vec![])))),
+        );
+        add(
+            "xliff",
+            &["xlf", "xliff"],
+            None,
+            Some(text_writer(|t, _| {
+                Ok((xliff::write_xliff(t, xliff::Version::V1_2), vec![]))
+            })),
+        );
+        add(
+            "xliff2",
+            &[],
+            None,
+            Some(text_writer(|t, _| {
+                Ok((xliff::write_xliff(t, xliff::Version::V2_0), vec![]))
+            })),
+        );
+
+        // transcripts.
+        for (name, extensions, layout) in [
+            ("text", &["txt"][..], transcript::Layout::Text),
+            ("markdown", &["md"][..], transcript::Layout::Markdown),
+            ("html", &["html", "htm"][..], transcript::Layout::Html),
+        ] {
+            add(
+                name,
+                extensions,
+                None,
+                Some(text_writer(move |t, _| {
+                    Ok((transcript::write_transcript(t, layout), vec![]))
+                })),
+            );
+        }
+        res
+    }
+}
+
+// formats that work on text get utf-8 in and out.
+pub fn text_reader<F>(f: F) -> Box<dyn Reader>
+where
+    F: Fn(&str, &Options) -> Result<AssTrack<'static>, Box<dyn Error>> + Send + Sync + 'static,
+{
+    Box::new(move |r: &mut dyn Source, o: &Options| f(&decode(&read_all(r)?), o))
+}
+
+pub fn read_all(r: &mut dyn Source) -> std::io::Result<Vec<u8>> {
+    let mut res = Vec::new();
+    r.read_to_end(&mut res)?;
+    Ok(res)
+}
+
+pub fn text_writer<F>(f: F) -> Box<dyn Writer>
+where
+    F: Fn(&AssTrack, &Options) -> Result<(String, Vec<Warning>), Box<dyn Error>>
+        + Send
+        + Sync
+        + 'static,
+{
+    Box::new(move |t: &AssTrack, o: &Options| {
+        f(t, o).map(|(s, warnings)| (s.into_bytes(), warnings))
+    })
+}
+
+// not valid utf-8 is most likely some old codepage, which we can't guess,
+// so keep what we can rather than give up.
+pub(crate) fn decode(b: &[u8]) -> Cow<'_, str> {
+    let b = b.strip_prefix(b"\xef\xbb\xbf").unwrap_or(b);
+    String::from_utf8_lossy(b)
+}
+
+// the first ass/ssa track, with the file's fonts embedded.
+fn read_matroska(r: &mut dyn Source, _: &Options) -> Result<AssTrack<'static>, Box<dyn Error>> {
+    let mut mkv = matroska::read(r)?;
+    mkv.embed_fonts();
+    match mkv.tracks.into_iter().next() {
+        Some(x) => Ok(x.track),
+        None => Err(FormatError::NoMatroskaTrack.into()),
+    }
+}
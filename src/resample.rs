@@ -0,0 +1,163 @@
+// resample.rs
+// moving a script to another PlayResX/PlayResY, so it looks the same on a
+// different canvas: style sizes and margins, event margins, and everything
+// in the override tags that's measured in pixels, drawings included.
+//
+// x and y are scaled separately, so a change of aspect ratio stretches
+// things rather than leaving them where they were.
+
+use std::borrow::Cow;
+
+use crate::ass::{
+    text::{self, Segment},
+    AssTrack,
+};
+
+#[derive(Debug, Clone, Copy)]
+struct Scale {
+    x: f64,
+    y: f64,
+}
+
+// two decimals is finer than any renderer cares about.
+fn num(x: f64) -> String {
+    let x = (x * 100.0).round() / 100.0;
+    format!("{}", x)
+}
+
+// the number at the start of s, scaled, and whatever came after it as is.
+fn scale_num(s: &str, by: f64) -> String {
+    let end = s
+        .find(|c: char| !(c.is_ascii_digit() || "+-.".contains(c)))
+        .unwrap_or(s.len());
+    match s[..end].parse::<f64>() {
+        Ok(x) => num(x * by) + &s[end..],
+        Err(_) => s.to_owned(),
+    }
+}
+
+// "(a,b,...)" with the first few arguments scaled, the rest left alone.
+fn scale_args(arg: &str, by: &[f64]) -> String {
+    let args = text::tag_args(arg);
+    let scaled: Vec<String> = args
+        .iter()
+        .enumerate()
+        .map(|(n, x)| match by.get(n) {
+            Some(f) => scale_num(x.trim(), *f),
+            None => x.to_string(),
+        })
+        .collect();
+    format!("({})", scaled.join(","))
+}
+
+// drawing commands are letters, and the numbers after them go x, y, x, y.
+fn scale_drawing(s: &str, scale: Scale) -> String {
+    let mut n = 0;
+    s.split_whitespace()
+        .map(|x| match x.parse::<f64>() {
+            Ok(v) => {
+                n += 1;
+                num(v * match n % 2 {
+                    1 => scale.x,
+                    _ => scale.y,
+                })
+            }
+            Err(_) => x.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn scale_clip(arg: &str, scale: Scale) -> String {
+    let args = text::tag_args(arg);
+    if args.len() == 4 {
+        return scale_args(arg, &[scale.x, scale.y, scale.x, scale.y]);
+    }
+    // a drawing, maybe after a scale argument.
+    match args.as_slice() {
+        [s, drawing] => format!("({},{})", s, scale_drawing(drawing, scale)),
+        [drawing] => format!("({})", scale_drawing(drawing, scale)),
+        _ => arg.to_owned(),
+    }
+}
+
+// the arguments of the tags in one override block, rewritten in place.
+fn scale_block(block: &str, scale: Scale) -> String {
+    let mut res = String::new();
+    let mut last = 0;
+    for tag in text::tags(block) {
+        let arg = match tag.name {
+            "pos" | "org" => scale_args(tag.arg, &[scale.x, scale.y]),
+            "move" => scale_args(tag.arg, &[scale.x, scale.y, scale.x, scale.y]),
+            "clip" | "iclip" => scale_clip(tag.arg, scale),
+            "fs" | "bord" | "shad" | "blur" | "ybord" | "yshad" => scale_num(tag.arg, scale.y),
+            "fsp" | "xbord" | "xshad" => scale_num(tag.arg, scale.x),
+            // animated tags are in its parens.
+            "t" => scale_block(tag.arg, scale),
+            _ => continue,
+        };
+        // the args are slices of the block, so that's where they are.
+        let start = tag.arg.as_ptr() as usize - block.as_ptr() as usize;
+        res.push_str(&block[last..start]);
+        res.push_str(&arg);
+        last = start + tag.arg.len();
+    }
+    res.push_str(&block[last..]);
+    res
+}
+
+fn scale_text(s: &str, scale: Scale) -> String {
+    let mut res = String::new();
+    let mut drawing = false;
+    for segment in text::segments(s) {
+        match segment {
+            Segment::Override(block) => {
+                for tag in text::tags(block) {
+                    if tag.name == "p" {
+                        drawing = tag.arg.parse::<u32>().is_ok_and(|x| x > 0);
+                    }
+                }
+                res.push('{');
+                res.push_str(&scale_block(block, scale));
+                res.push('}');
+            }
+            Segment::Text(t) if drawing => res.push_str(&scale_drawing(t, scale)),
+            Segment::Text(t) => res.push_str(t),
+        }
+    }
+    res
+}
+
+pub fn resample(track: &mut AssTrack, to: (u32, u32)) {
+    let from = track.header.play_res();
+    if from == to {
+        return;
+    }
+    let scale = Scale {
+        x: f64::from(to.0) / f64::from(from.0),
+        y: f64::from(to.1) / f64::from(from.1),
+    };
+    let round = |x: i32, by: f64| (f64::from(x) * by).round() as i32;
+
+    track.header.play_res_x = Some(to.0);
+    track.header.play_res_y = Some(to.1);
+    for style in track.styles.iter_mut() {
+        style.font_size = (f64::from(style.font_size) * scale.y).round() as u32;
+        style.outline *= scale.y;
+        style.shadow *= scale.y;
+        style.spacing *= scale.x;
+        style.margin_l = round(style.margin_l, scale.x);
+        style.margin_r = round(style.margin_r, scale.x);
+        style.margin_v = round(style.margin_v, scale.y);
+    }
+    for event in track.events.iter_mut() {
+        event.margin_l = round(event.margin_l, scale.x);
+        event.margin_r = round(event.margin_r, scale.x);
+        event.margin_v = round(event.margin_v, scale.y);
+        if let Some(t) = &event.text {
+            if t.contains('{') {
+                event.text = Some(Cow::Owned(scale_text(t, scale)));
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+// summary.rs
+// what's in a script at a glance, for the info command.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use serde_json::{json, Value};
+
+use crate::ass::{common::Timecode, event::EventKind, text, AssTrack, SourceMap};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub header: Vec<(&'static str, String)>,
+    pub resolution: (u32, u32),
+    // dialogue lines per style, every defined style included. styles that
+    // are used but not defined are in here too.
+    pub styles: BTreeMap<String, usize>,
+    pub style_count: usize,
+    // dialogue lines per actor, leaving out lines without one.
+    pub actors: BTreeMap<String, usize>,
+    pub kinds: BTreeMap<EventKind, usize>,
+    pub events: usize,
+    pub first: Option<u32>,
+    pub last: Option<u32>,
+    // time with at least one line of dialogue up, overlaps counted once.
+    pub on_screen: u32,
+    // from the styles and from \fn in the lines.
+    pub fonts: BTreeSet<String>,
+    // name and size in bytes.
+    pub attached_fonts: Vec<(String, usize)>,
+    pub attached_graphics: Vec<(String, usize)>,
+    pub unknown_sections: Vec<String>,
+}
+
+// the source map is only there for ass, and is where unknown sections come
+// from.
+pub fn summarize(track: &AssTrack, map: Option<&SourceMap>) -> Summary {
+    let mut res = Summary {
+        header: track.header.fields(),
+        resolution: track.header.play_res(),
+        style_count: track.styles.len(),
+        events: track.events.len(),
+        ..Default::default()
+    };
+    for style in &track.styles {
+        res.styles.entry(style.name.to_string()).or_insert(0);
+        res.fonts.insert(style.font_name.to_string());
+    }
+
+    let mut spans = Vec::new();
+    for event in &track.events {
+        *res.kinds.entry(event.kind()).or_insert(0) += 1;
+        let t = event.text.as_deref().unwrap_or("");
+        for segment in text::segments(t) {
+            if let text::Segment::Override(block) = segment {
+                for tag in text::tags(block) {
+                    // an empty \fn goes back to the style's font.
+                    if tag.name == "fn" && !tag.arg.is_empty() {
+                        res.fonts.insert(tag.arg.to_owned());
+                    }
+                }
+            }
+        }
+        if event.kind() != EventKind::Dialogue {
+            continue;
+        }
+        let style = event.style.as_deref().unwrap_or("Default");
+        *res.styles.entry(style.to_owned()).or_insert(0) += 1;
+        if let Some(actor) = event.actor.as_deref().filter(|x| !x.is_empty()) {
+            *res.actors.entry(actor.to_owned()).or_insert(0) += 1;
+        }
+        let (start, end) = (event.start_time.as_millis(), event.end_time.as_millis());
+        res.first = Some(res.first.map_or(start, |x| x.min(start)));
+        res.last = Some(res.last.map_or(end, |x| x.max(end)));
+        if end > start {
+            spans.push((start, end));
+        }
+    }
+
+    spans.sort_unstable();
+    let mut covered_to = 0;
+    for (start, end) in spans {
+        let start = start.max(covered_to);
+        if end > start {
+            res.on_screen += end - start;
+            covered_to = end;
+        }
+    }
+
+    let attached = |files: &[crate::ass::attachment::Attachment]| {
+        files
+            .iter()
+            .map(|x| (x.name().to_owned(), x.data().len()))
+            .collect()
+    };
+    res.attached_fonts = attached(&track.fonts);
+    res.attached_graphics = attached(&track.graphics);
+    if let Some(map) = map {
+        res.unknown_sections = map
+            .unknown_sections
+            .iter()
+            .map(|(_, x)| x.clone())
+            .collect();
+    }
+    res
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in &self.header {
+            writeln!(f, "{}: {}", name, value)?;
+        }
+        writeln!(f, "Resolution: {}x{}", self.resolution.0, self.resolution.1)?;
+        writeln!(f, "Styles: {}", self.style_count)?;
+        for (name, n) in &self.styles {
+            writeln!(f, "  {}: {} line(s)", name, n)?;
+        }
+        if !self.actors.is_empty() {
+            writeln!(f, "Actors: {}", self.actors.len())?;
+            for (name, n) in &self.actors {
+                writeln!(f, "  {}: {} line(s)", name, n)?;
+            }
+        }
+        writeln!(f, "Events: {}", self.events)?;
+        for (kind, n) in &self.kinds {
+            writeln!(f, "  {}: {}", kind, n)?;
+        }
+        if let (Some(first), Some(last)) = (self.first, self.last) {
+            writeln!(
+                f,
+                "Time: {} - {} ({} on screen)",
+                Timecode::from(first),
+                Timecode::from(last),
+                Timecode::from(self.on_screen)
+            )?;
+        }
+        if !self.fonts.is_empty() {
+            let fonts: Vec<_> = self.fonts.iter().map(String::as_str).collect();
+            writeln!(f, "Fonts used: {}", fonts.join(", "))?;
+        }
+        for (section, files) in &[
+            ("Embedded fonts", &self.attached_fonts),
+            ("Embedded graphics", &self.attached_graphics),
+        ] {
+            if files.is_empty() {
+                continue;
+            }
+            writeln!(f, "{}: {}", section, files.len())?;
+            for (name, size) in files.iter() {
+                writeln!(f, "  {} ({} bytes)", name, size)?;
+            }
+        }
+        if !self.unknown_sections.is_empty() {
+            writeln!(f, "Unknown sections: {}", self.unknown_sections.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Summary {
+    pub fn to_json(&self) -> Value {
+        let files = |files: &[(String, usize)]| -> Vec<Value> {
+            files
+                .iter()
+                .map(|(name, size)| json!({ "name": name, "size": size }))
+                .collect()
+        };
+        let header: serde_json::Map<_, _> = self
+            .header
+            .iter()
+            .map(|(k, v)| (k.to_string(), json!(v)))
+            .collect();
+        let kinds: serde_json::Map<_, _> = self
+            .kinds
+            .iter()
+            .map(|(k, v)| (k.to_string(), json!(v)))
+            .collect();
+        json!({
+            "header": header,
+            "resolution": { "x": self.resolution.0, "y": self.resolution.1 },
+            "styles": { "count": self.style_count, "lines": self.styles },
+            "actors": self.actors,
+            "events": { "count": self.events, "kinds": kinds },
+            "time": {
+                "first": self.first.map(|x| Timecode::from(x).to_string()),
+                "last": self.last.map(|x| Timecode::from(x).to_string()),
+                "on_screen": Timecode::from(self.on_screen).to_string(),
+            },
+            "fonts": self.fonts,
+            "attachments": {
+                "fonts": files(&self.attached_fonts),
+                "graphics": files(&self.attached_graphics),
+            },
+            "unknown_sections": self.unknown_sections,
+        })
+    }
+}
@@ -0,0 +1,607 @@
+// threeway.rs
+// merging two people's edits to the same script, given the version they both
+// started from. events are matched up the way diff does it, then everything
+// goes field by field: whoever changed a field wins, and if both changed it
+// differently that's a conflict. so one person retiming a line and another
+// retranslating it, or fixing a colour and a margin on the same style, isn't
+// a conflict.
+//
+// conflicting events keep both versions, fenced in by Comment events saying
+// <<<<<<< ours, ======= and >>>>>>> theirs, the way git marks them. styles
+// and header fields can't hold both, so those keep ours and get a Comment at
+// the top saying what theirs was.
+
+use std::borrow::Cow;
+
+use crate::ass::{
+    attachment::Attachment,
+    event::Event,
+    info::{ConfigKind, Header},
+    style::Style,
+    AssTrack,
+};
+use crate::diff::pair_events;
+
+pub struct Merged<'a> {
+    pub track: AssTrack<'a>,
+    pub conflicts: usize,
+}
+
+enum Pick<T> {
+    Take(Option<T>),
+    Conflict,
+}
+
+// None is not there (deleted, or never added).
+fn pick<T: PartialEq + Clone>(base: Option<&T>, ours: Option<&T>, theirs: Option<&T>) -> Pick<T> {
+    if ours == theirs || theirs == base {
+        Pick::Take(ours.cloned())
+    } else if ours == base {
+        Pick::Take(theirs.cloned())
+    } else {
+        Pick::Conflict
+    }
+}
+
+fn get<'f>(fields: &'f [(String, String)], name: &str) -> Option<&'f String> {
+    fields.iter().find(|(k, _)| k == name).map(|(_, v)| v)
+}
+
+// field by field. conflicts keep ours and come back as notes.
+fn merge_fields(
+    what: &str,
+    base: &[(String, String)],
+    ours: &[(String, String)],
+    theirs: &[(String, String)],
+    notes: &mut Vec<String>,
+) -> Vec<(String, String)> {
+    let mut names: Vec<&String> = Vec::new();
+    for (k, _) in ours.iter().chain(theirs).chain(base) {
+        if !names.contains(&k) {
+            names.push(k);
+        }
+    }
+    let mut res = Vec::new();
+    for name in names {
+        let (b, o, t) = (get(base, name), get(ours, name), get(theirs, name));
+        let value = match pick(b, o, t) {
+            Pick::Take(x) => x,
+            Pick::Conflict => {
+                notes.push(format!(
+                    "{} {}: ours {}, theirs {}",
+                    what,
+                    name,
+                    o.map_or("(unset)", |x| x),
+                    t.map_or("(unset)", |x| x)
+                ));
+                o.cloned()
+            }
+        };
+        if let Some(x) = value {
+            res.push((name.clone(), x));
+        }
+    }
+    res
+}
+
+fn owned_fields(fields: Vec<(&str, String)>) -> Vec<(String, String)> {
+    fields.into_iter().map(|(k, v)| (k.to_owned(), v)).collect()
+}
+
+fn merge_header(
+    base: &Header,
+    ours: &Header,
+    theirs: &Header,
+    notes: &mut Vec<String>,
+) -> Header<'static> {
+    let fields = merge_fields(
+        "header",
+        &owned_fields(base.fields()),
+        &owned_fields(ours.fields()),
+        &owned_fields(theirs.fields()),
+        notes,
+    );
+    let mut res = Header::default();
+    for (k, v) in &fields {
+        // they all came out of headers, so they all go back in.
+        if let Ok(x) = ConfigKind::parse(k, v) {
+            res.set(x);
+        }
+    }
+    res.into_owned()
+}
+
+fn merge_styles<'a>(
+    base: &[Style<'a>],
+    ours: &[Style<'a>],
+    theirs: &[Style<'a>],
+    notes: &mut Vec<String>,
+) -> Vec<Style<'a>> {
+    let mut names: Vec<&str> = Vec::new();
+    for style in ours.iter().chain(theirs).chain(base) {
+        if !names.contains(&&*style.name) {
+            names.push(&style.name);
+        }
+    }
+    fn find<'s, 'a>(styles: &'s [Style<'a>], name: &str) -> Option<&'s Style<'a>> {
+        styles.iter().find(|x| x.name == name)
+    }
+    let mut res = Vec::new();
+    for name in names {
+        let (b, o, t) = (find(base, name), find(ours, name), find(theirs, name));
+        let line = |x: Option<&Style>| x.map(|x| x.to_string());
+        match pick(line(b).as_ref(), line(o).as_ref(), line(t).as_ref()) {
+            Pick::Take(None) => continue,
+            Pick::Take(Some(x)) => {
+                let side = match line(o) == Some(x) {
+                    true => o,
+                    false => t,
+                };
+                res.extend(side.cloned());
+                continue;
+            }
+            Pick::Conflict => {}
+        }
+        let what = format!("style {}", name);
+        let (o, t) = match (o, t) {
+            (Some(o), Some(t)) => (o, t),
+            // deleted on one side and changed on the other. keep the changes.
+            (o, t) => {
+                let deleted_in = match o {
+                    None => "ours",
+                    Some(_) => "theirs",
+                };
+                notes.push(format!(
+                    "{}: deleted in {}, changed in the other",
+                    what, deleted_in
+                ));
+                res.extend(o.or(t).cloned());
+                continue;
+            }
+        };
+        let base_fields = b.map(|x| x.fields()).unwrap_or_default();
+        let merged = merge_fields(&what, &base_fields, &o.fields(), &t.fields(), notes);
+        let values: Vec<&str> = merged.iter().map(|(_, v)| v.as_str()).collect();
+        match Style::parse(&values.join(","), None) {
+            Ok(x) => res.push(x.into_owned()),
+            Err(_) => res.push(o.clone()),
+        }
+    }
+    res
+}
+
+// field by field like styles, so one side retiming a line and the other
+// retranslating it isn't a conflict. None if the same field changed both
+// ways.
+fn merge_event<'a>(base: &Event, ours: &Event, theirs: &Event) -> Option<Event<'a>> {
+    let fields = |x: &Event| {
+        let mut res = vec![("Descriptor".to_owned(), x.descriptor.to_string())];
+        res.extend(x.fields());
+        res
+    };
+    let mut clashes = Vec::new();
+    let merged = merge_fields(
+        "event",
+        &fields(base),
+        &fields(ours),
+        &fields(theirs),
+        &mut clashes,
+    );
+    if !clashes.is_empty() {
+        return None;
+    }
+    let descriptor = get(&merged, "Descriptor")?;
+    let values: Vec<&str> = merged
+        .iter()
+        .filter(|(k, _)| k != "Descriptor")
+        .map(|(_, v)| v.as_str())
+        .collect();
+    let line = values.join(",");
+    Event::parse(&line, Some(descriptor), None)
+        .ok()
+        .map(Event::into_owned)
+}
+
+fn marker<'a>(like: &Event<'a>, text: &str) -> Event<'a> {
+    Event {
+        descriptor: Cow::Borrowed("Comment"),
+        start_time: like.start_time,
+        end_time: like.end_time,
+        style: like.style.clone(),
+        text: Some(Cow::Owned(text.to_owned())),
+        ..Default::default()
+    }
+}
+
+fn conflict<'a>(ours: Vec<&Event<'a>>, theirs: Vec<&Event<'a>>) -> Vec<Event<'a>> {
+    let like = match ours.first().or(theirs.first()) {
+        Some(x) => *x,
+        None => return Vec::new(),
+    };
+    let mut res = vec![marker(like, "<<<<<<< ours")];
+    res.extend(ours.into_iter().cloned());
+    res.push(marker(like, "======="));
+    res.extend(theirs.into_iter().cloned());
+    res.push(marker(like, ">>>>>>> theirs"));
+    res
+}
+
+fn overlaps(a: &Event, b: &Event) -> bool {
+    a.start_time.as_millis() < b.end_time.as_millis()
+        && b.start_time.as_millis() < a.end_time.as_millis()
+}
+
+// the new events on one side that sit where a base event used to be, and
+// haven't been claimed by another one already.
+fn added_over(
+    was: &Event,
+    events: &[Event],
+    from_base: &[Option<usize>],
+    claimed: &[Option<usize>],
+) -> Vec<usize> {
+    (0..events.len())
+        .filter(|&m| from_base[m].is_none() && claimed[m].is_none() && overlaps(was, &events[m]))
+        .collect()
+}
+
+// the events on one side that are in the clash over base event n.
+fn in_clash<'s, 'a>(
+    events: &'s [Event<'a>],
+    clash: &[Option<usize>],
+    n: usize,
+) -> Vec<&'s Event<'a>> {
+    events
+        .iter()
+        .zip(clash)
+        .filter(|(_, x)| **x == Some(n))
+        .map(|(x, _)| x)
+        .collect()
+}
+
+pub fn merge<'a>(base: &AssTrack<'a>, ours: &AssTrack<'a>, theirs: &AssTrack<'a>) -> Merged<'a> {
+    let mut notes = Vec::new();
+    let header = merge_header(&base.header, &ours.header, &theirs.header, &mut notes);
+    let styles = merge_styles(&base.styles, &ours.styles, &theirs.styles, &mut notes);
+    let mut conflicts = notes.len();
+
+    // for every base event, what became of it on each side.
+    let mut in_ours = vec![None; base.events.len()];
+    let mut in_theirs = vec![None; base.events.len()];
+    let mut from_base_ours = vec![None; ours.events.len()];
+    let mut from_base_theirs = vec![None; theirs.events.len()];
+    for (n, m) in pair_events(&base.events, &ours.events) {
+        in_ours[n] = Some(m);
+        from_base_ours[m] = Some(n);
+    }
+    for (n, m) in pair_events(&base.events, &theirs.events) {
+        in_theirs[n] = Some(m);
+        from_base_theirs[m] = Some(n);
+    }
+    let line = |x: &Event| x.to_string();
+
+    // a line both sides rewrote past recognizing looks like the base event
+    // deleted and a new one added in its place, on each side. if the new
+    // ones don't agree, that's a conflict like any other.
+    let mut clash_ours = vec![None; ours.events.len()];
+    let mut clash_theirs = vec![None; theirs.events.len()];
+    for (n, was) in base.events.iter().enumerate() {
+        if in_ours[n].is_some() || in_theirs[n].is_some() {
+            continue;
+        }
+        let o = added_over(was, &ours.events, &from_base_ours, &clash_ours);
+        let t = added_over(was, &theirs.events, &from_base_theirs, &clash_theirs);
+        let lines = |events: &[Event], at: &[usize]| -> Vec<String> {
+            at.iter().map(|&m| line(&events[m])).collect()
+        };
+        if o.is_empty() || t.is_empty() || lines(&ours.events, &o) == lines(&theirs.events, &t) {
+            continue;
+        }
+        for m in o {
+            clash_ours[m] = Some(n);
+        }
+        for m in t {
+            clash_theirs[m] = Some(n);
+        }
+    }
+
+    // in ours' order, with what theirs brings slotted in by time.
+    let mut blocks: Vec<(u32, Vec<Event<'a>>)> = Vec::new();
+    for (m, event) in ours.events.iter().enumerate() {
+        let start = event.start_time.as_millis();
+        let n = match (from_base_ours[m], clash_ours[m]) {
+            (Some(x), _) => x,
+            (None, None) => {
+                blocks.push((start, vec![event.clone()]));
+                continue;
+            }
+            // the whole clash goes in where its first event on our side is.
+            (None, Some(n)) => {
+                if clash_ours[..m].contains(&Some(n)) {
+                    continue;
+                }
+                conflicts += 1;
+                blocks.push((
+                    start,
+                    conflict(
+                        in_clash(&ours.events, &clash_ours, n),
+                        in_clash(&theirs.events, &clash_theirs, n),
+                    ),
+                ));
+                continue;
+            }
+        };
+        let t = in_theirs[n].map(|x| &theirs.events[x]);
+        let b = &base.events[n];
+        match t {
+            Some(t) => match merge_event(b, event, t) {
+                Some(x) => blocks.push((x.start_time.as_millis(), vec![x])),
+                None => {
+                    conflicts += 1;
+                    blocks.push((start, conflict(vec![event], vec![t])));
+                }
+            },
+            // deleted in theirs, which only sticks if we left it alone.
+            None if line(b) == line(event) => {}
+            None => {
+                conflicts += 1;
+                blocks.push((start, conflict(vec![event], Vec::new())));
+            }
+        }
+    }
+
+    let mut extra: Vec<(u32, Vec<Event<'a>>)> = Vec::new();
+    for (m, event) in theirs.events.iter().enumerate() {
+        let start = event.start_time.as_millis();
+        match from_base_theirs[m] {
+            // in a clash, which went in with ours.
+            None if clash_theirs[m].is_some() => {}
+            // added by both is only added once.
+            None => {
+                let dup = ours
+                    .events
+                    .iter()
+                    .zip(&from_base_ours)
+                    .any(|(x, from)| from.is_none() && line(x) == line(event));
+                if !dup {
+                    extra.push((start, vec![event.clone()]));
+                }
+            }
+            // deleted in ours but changed in theirs. the other way round
+            // is handled above.
+            Some(n) if in_ours[n].is_none() && line(&base.events[n]) != line(event) => {
+                conflicts += 1;
+                extra.push((start, conflict(Vec::new(), vec![event])));
+            }
+            Some(_) => {}
+        }
+    }
+    for (start, events) in extra {
+        let at = blocks
+            .iter()
+            .position(|(x, _)| *x > start)
+            .unwrap_or(blocks.len());
+        blocks.insert(at, (start, events));
+    }
+
+    let mut events: Vec<Event<'a>> = notes
+        .iter()
+        .map(|x| Event {
+            descriptor: Cow::Borrowed("Comment"),
+            start_time: Default::default(),
+            end_time: Default::default(),
+            text: Some(Cow::Owned(format!("<<<<<<< conflict: {} >>>>>>>", x))),
+            ..Default::default()
+        })
+        .collect();
+    events.extend(blocks.into_iter().flat_map(|(_, x)| x));
+
+    // attachments by name: ours, less what theirs deleted, plus what theirs
+    // added.
+    let files = |base: &[Attachment<'a>], ours: &[Attachment<'a>], theirs: &[Attachment<'a>]| {
+        let has = |files: &[Attachment], name: &str| files.iter().any(|x| x.name() == name);
+        let mut res: Vec<_> = ours
+            .iter()
+            .filter(|x| has(theirs, x.name()) || !has(base, x.name()))
+            .cloned()
+            .collect();
+        res.extend(
+            theirs
+                .iter()
+                .filter(|x| !has(base, x.name()) && !has(ours, x.name()))
+                .cloned(),
+        );
+        res
+    };
+
+    Merged {
+        track: AssTrack {
+            header,
+            styles,
+            events,
+            fonts: files(&base.fonts, &ours.fonts, &theirs.fonts),
+            graphics: files(&base.graphics, &ours.graphics, &theirs.graphics),
+            styleformat: ours.styleformat.clone(),
+            eventformat: ours.eventformat.clone(),
+        },
+        conflicts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEAD: &str = "[Script Info]\nScriptType: v4.00+\n\n[V4+ Styles]\n\
+        Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, \
+        BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, \
+        BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+        Style: Default,Arial,18,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,\
+        0,0,1,2,3,2,20,20,20,1\n\n[Events]\n\
+        Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n";
+
+    // (start, end, text) in centiseconds, all Dialogue in Default.
+    fn track(events: &[(u32, u32, &str)]) -> String {
+        let time = |cs: u32| format!("0:00:{:02}.{:02}", cs / 100, cs % 100);
+        let mut s = HEAD.to_owned();
+        for (start, end, text) in events {
+            s += &format!(
+                "Dialogue: 0,{},{},Default,,0,0,0,,{}\n",
+                time(*start),
+                time(*end),
+                text
+            );
+        }
+        s
+    }
+
+    fn run(base: &str, ours: &str, theirs: &str) -> (Vec<String>, usize) {
+        let (b, o, t) = (
+            AssTrack::parse_track(base).unwrap(),
+            AssTrack::parse_track(ours).unwrap(),
+            AssTrack::parse_track(theirs).unwrap(),
+        );
+        let merged = merge(&b, &o, &t);
+        let events = merged.track.events.iter().map(|x| x.to_string()).collect();
+        (events, merged.conflicts)
+    }
+
+    fn texts(events: &[String]) -> Vec<&str> {
+        events
+            .iter()
+            .map(|x| x.rsplit(",,").next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn edits_to_different_lines_both_go_in() {
+        let base = track(&[(100, 300, "Hello"), (400, 600, "World")]);
+        let ours = track(&[(100, 300, "Hello there"), (400, 600, "World")]);
+        let theirs = track(&[(100, 300, "Hello"), (400, 600, "Wide world")]);
+        let (events, conflicts) = run(&base, &ours, &theirs);
+        assert_eq!(conflicts, 0);
+        assert_eq!(texts(&events), ["Hello there", "Wide world"]);
+    }
+
+    #[test]
+    fn different_fields_of_one_line_both_go_in() {
+        let base = track(&[(100, 300, "Hello")]);
+        let ours = track(&[(120, 300, "Hello")]);
+        let theirs = track(&[(100, 300, "Bonjour")]);
+        let (events, conflicts) = run(&base, &ours, &theirs);
+        assert_eq!(conflicts, 0);
+        assert_eq!(
+            events,
+            ["Dialogue: 0,0:00:01.20,0:00:03.00,Default,,0,0,0,,Bonjour"]
+        );
+    }
+
+    #[test]
+    fn same_field_changed_both_ways_conflicts() {
+        let base = track(&[(100, 300, "Hello")]);
+        let ours = track(&[(100, 300, "Hallo")]);
+        let theirs = track(&[(100, 300, "Bonjour")]);
+        let (events, conflicts) = run(&base, &ours, &theirs);
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            texts(&events),
+            [
+                "<<<<<<< ours",
+                "Hallo",
+                "=======",
+                "Bonjour",
+                ">>>>>>> theirs"
+            ]
+        );
+    }
+
+    #[test]
+    fn same_change_on_both_sides_is_not_a_conflict() {
+        let base = track(&[(100, 300, "Hello")]);
+        let ours = track(&[(100, 300, "Bonjour")]);
+        let (events, conflicts) = run(&base, &ours, &ours);
+        assert_eq!(conflicts, 0);
+        assert_eq!(texts(&events), ["Bonjour"]);
+    }
+
+    #[test]
+    fn delete_against_unchanged_deletes() {
+        let base = track(&[(100, 300, "Hello"), (400, 600, "World")]);
+        let ours = track(&[(400, 600, "World")]);
+        let (events, conflicts) = run(&base, &ours, &base);
+        assert_eq!(conflicts, 0);
+        assert_eq!(texts(&events), ["World"]);
+        let (events, conflicts) = run(&base, &base, &ours);
+        assert_eq!(conflicts, 0);
+        assert_eq!(texts(&events), ["World"]);
+    }
+
+    #[test]
+    fn delete_against_modify_conflicts() {
+        let base = track(&[(100, 300, "Hello"), (400, 600, "World")]);
+        let deleted = track(&[(400, 600, "World")]);
+        let modified = track(&[(100, 300, "Hello there"), (400, 600, "World")]);
+        let (events, conflicts) = run(&base, &deleted, &modified);
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            texts(&events),
+            [
+                "<<<<<<< ours",
+                "=======",
+                "Hello there",
+                ">>>>>>> theirs",
+                "World"
+            ]
+        );
+        let (events, conflicts) = run(&base, &modified, &deleted);
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            texts(&events),
+            [
+                "<<<<<<< ours",
+                "Hello there",
+                "=======",
+                ">>>>>>> theirs",
+                "World"
+            ]
+        );
+    }
+
+    #[test]
+    fn the_same_event_added_on_both_sides_goes_in_once() {
+        let base = track(&[(100, 300, "Hello")]);
+        let both = track(&[(100, 300, "Hello"), (400, 600, "World")]);
+        let (events, conflicts) = run(&base, &both, &both);
+        assert_eq!(conflicts, 0);
+        assert_eq!(texts(&events), ["Hello", "World"]);
+    }
+
+    #[test]
+    fn additions_on_each_side_are_slotted_in_by_time() {
+        let base = track(&[(100, 300, "One"), (700, 900, "Four")]);
+        let ours = track(&[(100, 300, "One"), (300, 500, "Two"), (700, 900, "Four")]);
+        let theirs = track(&[(100, 300, "One"), (500, 700, "Three"), (700, 900, "Four")]);
+        let (events, conflicts) = run(&base, &ours, &theirs);
+        assert_eq!(conflicts, 0);
+        assert_eq!(texts(&events), ["One", "Two", "Three", "Four"]);
+    }
+
+    #[test]
+    fn different_replacements_for_one_line_conflict() {
+        let base = track(&[(100, 300, "Hello"), (350, 850, "World")]);
+        let ours = track(&[(100, 300, "Hello"), (350, 800, "Mars")]);
+        let theirs = track(&[(100, 300, "Hello"), (360, 850, "Earth")]);
+        let (events, conflicts) = run(&base, &ours, &theirs);
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            texts(&events),
+            [
+                "Hello",
+                "<<<<<<< ours",
+                "Mars",
+                "=======",
+                "Earth",
+                ">>>>>>> theirs"
+            ]
+        );
+    }
+}
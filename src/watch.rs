@@ -0,0 +1,71 @@
+// watch.rs
+// rerunning something every time a file is saved. we watch the directory
+// rather than the file, since plenty of editors save by writing a new file
+// and renaming it over the old one, which a watch on the file itself loses.
+//
+// one save can be several writes in a row, so after the first change we wait
+// until it's been quiet for the debounce time before running.
+
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+// runs f once straight away and then after every change, until the watcher
+// goes away. f's errors are its own business, so it doesn't return any.
+pub fn watch<F>(path: &Path, debounce: Duration, mut f: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnMut(),
+{
+    let (dir, path) = resolve(path)?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+
+    let relevant = |event: &notify::Result<notify::Event>| match event {
+        Ok(x) => !matches!(x.kind, EventKind::Access(_)) && x.paths.contains(&path),
+        // better a run too many than a save missed.
+        Err(_) => true,
+    };
+
+    f();
+    loop {
+        match rx.recv() {
+            Ok(x) if relevant(&x) => {}
+            Ok(_) => continue,
+            Err(_) => return Ok(()),
+        }
+        // wait out the rest of the save. other files in the directory don't
+        // count as more of it, so they don't push the wait back.
+        let mut quiet_at = Instant::now() + debounce;
+        loop {
+            match rx.recv_timeout(quiet_at.saturating_duration_since(Instant::now())) {
+                Ok(x) if relevant(&x) => quiet_at = Instant::now() + debounce,
+                Ok(_) => {}
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+        f();
+    }
+}
+
+// the events come with the watched directory's path in front, so ours has
+// to be spelled the same way.
+fn resolve(path: &Path) -> std::io::Result<(PathBuf, PathBuf)> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a file path"))?;
+    let dir = match path.parent() {
+        Some(x) if !x.as_os_str().is_empty() => x,
+        _ => Path::new("."),
+    };
+    let dir = fs::canonicalize(dir)?;
+    let path = dir.join(name);
+    Ok((dir, path))
+}